@@ -0,0 +1,151 @@
+#![allow(non_snake_case)]
+
+#[macro_use]
+extern crate criterion;
+extern crate bolt;
+extern crate ff_bl as ff;
+extern crate pairing_bl as pairing;
+extern crate rand;
+extern crate serde;
+extern crate serde_json;
+
+use bolt::cl::{setup, BlindKeyPair, BlindPublicKey, PublicParams, Signature, SignatureProof};
+use bolt::nizk::NIZKSecretParams;
+use bolt::wallet::Wallet;
+use bolt::util;
+use criterion::{Criterion, black_box};
+use ff::{Field, PrimeField, Rand};
+use pairing::Engine;
+use pairing::bls12_381::Bls12;
+use pairing::bn256::Bn256;
+
+const L: usize = 5;
+
+fn bench_setup<E: Engine>() -> (PublicParams<E>, BlindKeyPair<E>, Vec<E::Fr>, Signature<E>) {
+    let rng = &mut rand::thread_rng();
+    let mpk = setup(rng);
+    let keypair = BlindKeyPair::<E>::generate(rng, &mpk, L);
+    let message: Vec<E::Fr> = (0..L).map(|_| E::Fr::rand(rng)).collect();
+    let sig = keypair.sign(rng, &message);
+    (mpk, keypair, message, sig)
+}
+
+/// commitment phase with one pairing and one target group exponentiation per base
+fn naive_prove_commitment<E: Engine>(pk: &BlindPublicKey<E>, mpk: &PublicParams<E>, h: &E::G1, t: &Vec<E::Fr>, tt: &E::Fr) -> E::Fqk {
+    let mut a = E::Fqk::one();
+    for j in 0..pk.Y2.len() {
+        let gy = E::pairing(*h, pk.Y2[j]).pow(t[j].into_repr());
+        a.mul_assign(&gy);
+    }
+    a.mul_assign(&E::pairing(*h, mpk.g2).pow(tt.into_repr()));
+    a
+}
+
+/// proof verification with one pairing and one target group exponentiation per base
+fn naive_verify_proof<E: Engine>(pk: &BlindPublicKey<E>, mpk: &PublicParams<E>, blindSig: &Signature<E>, p: &SignatureProof<E>, challenge: &E::Fr) -> bool {
+    let mut gx = E::pairing(blindSig.h, pk.X2).pow(challenge.into_repr());
+    for j in 0..pk.Y2.len() {
+        let gy = E::pairing(blindSig.h, pk.Y2[j]).pow(p.zsig[j].into_repr());
+        gx.mul_assign(&gy);
+    }
+    gx.mul_assign(&E::pairing(blindSig.h, mpk.g2).pow(p.zv.into_repr()));
+    let mut g = E::pairing(blindSig.H, mpk.g2).pow(challenge.into_repr());
+    g.mul_assign(&p.a);
+    gx == g
+}
+
+fn bench_signature_proof<E: Engine>(c: &mut Criterion, curve: &str) {
+    let rng = &mut rand::thread_rng();
+    let (mpk, keypair, mut message, sig) = bench_setup::<E>();
+    let t: Vec<E::Fr> = (0..L).map(|_| E::Fr::rand(rng)).collect();
+    let tt = E::Fr::rand(rng);
+    let bf = E::Fr::rand(rng);
+    let blindSig = keypair.public.blind(rng, &bf, &sig);
+
+    c.bench_function(&format!("{}/prove_commitment_naive", curve), |b| {
+        b.iter(|| naive_prove_commitment(&keypair.public, &mpk, &blindSig.h, &t, &tt))
+    });
+    c.bench_function(&format!("{}/prove_commitment", curve), |b| {
+        b.iter(|| keypair.public.prove_commitment(rng, &mpk, &sig, Some(t.clone()), Some(tt)))
+    });
+
+    let ps = keypair.public.prove_commitment(rng, &mpk, &sig, None, None);
+    let challenge = E::Fr::rand(rng);
+    let proof = keypair.public.prove_response(&ps, challenge, &mut message);
+    let prepared_pk = keypair.public.prepare(&mpk);
+
+    c.bench_function(&format!("{}/verify_proof_naive", curve), |b| {
+        b.iter(|| naive_verify_proof(&keypair.public, &mpk, &ps.blindSig, &proof, &challenge))
+    });
+    c.bench_function(&format!("{}/verify_proof", curve), |b| {
+        b.iter(|| keypair.public.verify_proof(&mpk, ps.blindSig.clone(), proof.clone(), challenge))
+    });
+    c.bench_function(&format!("{}/verify_proof_prepared", curve), |b| {
+        b.iter(|| prepared_pk.verify_proof(&ps.blindSig, &proof, challenge))
+    });
+}
+
+fn bench_signature_batch<E: Engine>(c: &mut Criterion, curve: &str) {
+    let rng = &mut rand::thread_rng();
+    let (mpk, keypair, _, _) = bench_setup::<E>();
+    let pk = keypair.get_public_key(&mpk);
+    let messages: Vec<Vec<E::Fr>> = (0..32).map(|_| (0..L).map(|_| E::Fr::rand(rng)).collect()).collect();
+    let signatures: Vec<Signature<E>> = messages.iter().map(|m| keypair.sign(rng, m)).collect();
+
+    c.bench_function(&format!("{}/verify_32_signatures", curve), |b| {
+        b.iter(|| messages.iter().zip(signatures.iter()).all(|(m, s)| pk.verify(&mpk, m, s)))
+    });
+    c.bench_function(&format!("{}/verify_batch_32_signatures", curve), |b| {
+        b.iter(|| pk.verify_batch(rng, &mpk, &messages, &signatures))
+    });
+}
+
+fn bench_nizk_verify<E: Engine>(c: &mut Criterion, curve: &str)
+    where <E as ff::ScalarEngine>::Fr: serde::Serialize + serde::de::DeserializeOwned,
+          <E as Engine>::G1: serde::Serialize + serde::de::DeserializeOwned,
+          <E as Engine>::G2: serde::Serialize + serde::de::DeserializeOwned
+{
+    let rng = &mut rand::thread_rng();
+    let secParams = NIZKSecretParams::<E>::setup(rng, 4);
+    let r = E::Fr::rand(rng);
+    let rprime = E::Fr::rand(rng);
+    let wpk = E::Fr::rand(rng);
+    let wallet1 = Wallet { channelId: E::Fr::rand(rng), wpk, bc: 100, bm: 100, close: None };
+    let wallet2 = Wallet { channelId: wallet1.channelId, wpk: E::Fr::rand(rng), bc: 90, bm: 110, close: None };
+    let commitment1 = secParams.pubParams.comParams.commit(&wallet1.as_fr_vec(), &r);
+    let commitment2 = secParams.pubParams.comParams.commit(&wallet2.as_fr_vec(), &rprime);
+    let blindPaymentToken = secParams.keypair.sign_blind(rng, &secParams.pubParams.mpk, commitment1);
+    let paymentToken = secParams.keypair.unblind(&r, &blindPaymentToken);
+    let proof = secParams.pubParams.prove(rng, wallet1, wallet2, commitment2.clone(), rprime, &paymentToken);
+    let epsilon = util::convert_int_to_fr::<E>(10);
+
+    // the prepared keys are not serialized, so a deserialized copy prepares them on every call
+    let ser = serde_json::to_vec(&secParams).unwrap();
+    let unpreparedParams: NIZKSecretParams<E> = serde_json::from_slice(&ser).unwrap();
+
+    c.bench_function(&format!("{}/nizk_verify_unprepared", curve), |b| {
+        b.iter(|| black_box(unpreparedParams.verify(proof.clone(), epsilon, &commitment2, wpk)))
+    });
+    c.bench_function(&format!("{}/nizk_verify", curve), |b| {
+        b.iter(|| black_box(secParams.verify(proof.clone(), epsilon, &commitment2, wpk)))
+    });
+}
+
+fn bench_bls12(c: &mut Criterion) {
+    bench_signature_proof::<Bls12>(c, "bls12");
+    bench_signature_batch::<Bls12>(c, "bls12");
+    bench_nizk_verify::<Bls12>(c, "bls12");
+}
+
+fn bench_bn256(c: &mut Criterion) {
+    bench_signature_proof::<Bn256>(c, "bn256");
+    bench_signature_batch::<Bn256>(c, "bn256");
+    bench_nizk_verify::<Bn256>(c, "bn256");
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_bls12, bench_bn256
+}
+criterion_main!(benches);
@@ -0,0 +1,137 @@
+#[macro_use]
+extern crate criterion;
+extern crate bolt;
+extern crate ff_bl as ff;
+extern crate pairing_bl as pairing;
+extern crate rand;
+extern crate serde;
+
+use bolt::bidirectional;
+use bolt::util;
+use criterion::{Criterion, BatchSize, black_box};
+use pairing::Engine;
+use pairing::bls12_381::Bls12;
+use pairing::bn256::Bn256;
+
+const B0_CUSTOMER: i64 = 1000;
+const B0_MERCHANT: i64 = 100;
+const PAY_INC: i64 = 10;
+
+struct Channel<E: Engine> {
+    channel_state: bidirectional::ChannelState<E>,
+    channel_token: bidirectional::ChannelToken<E>,
+    merch_state: bidirectional::MerchantState<E>,
+    merch_ledger: bidirectional::MerchantLedger<E>,
+    cust_state: bidirectional::CustomerState<E>,
+}
+
+fn init_channel<E: Engine>() -> Channel<E>
+    where <E as ff::ScalarEngine>::Fr: serde::Serialize,
+          <E as Engine>::G1: serde::Serialize,
+          <E as Engine>::G2: serde::Serialize
+{
+    let rng = &mut rand::thread_rng();
+    let mut channel_state = bidirectional::ChannelState::<E>::new(String::from("Channel A -> B"), false);
+    let (mut channel_token, merch_state, merch_ledger, channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob");
+    let cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, B0_CUSTOMER, B0_MERCHANT, "Alice").unwrap().unwrap();
+    Channel { channel_state, channel_token, merch_state, merch_ledger, cust_state }
+}
+
+fn establish<E: Engine>(ch: &mut Channel<E>) {
+    let rng = &mut rand::thread_rng();
+    let (com, com_proof) = bidirectional::establish_customer_generate_proof(rng, &ch.channel_token, &ch.cust_state);
+    let channel_id = ch.cust_state.get_wallet().channelId;
+    let close_token = bidirectional::establish_merchant_issue_close_token(rng, &ch.channel_state, &com, &com_proof, &channel_id,
+                                                                          B0_CUSTOMER, B0_MERCHANT, &ch.merch_state, &mut ch.merch_ledger).unwrap().unwrap();
+    assert!(ch.cust_state.verify_close_token(&ch.channel_state, &close_token));
+    let pay_token = bidirectional::establish_merchant_issue_pay_token(rng, &ch.channel_state, &com, &ch.merch_state);
+    assert!(bidirectional::establish_customer_final(&mut ch.channel_state, &mut ch.cust_state, &pay_token));
+}
+
+fn bench_protocol<E: Engine>(c: &mut Criterion, curve: &str)
+    where <E as ff::ScalarEngine>::Fr: serde::Serialize,
+          <E as Engine>::G1: serde::Serialize,
+          <E as Engine>::G2: serde::Serialize
+{
+    let rng = &mut rand::thread_rng();
+
+    c.bench_function(&format!("{}/init_merchant", curve), |b| {
+        b.iter(|| {
+            let mut channel_state = bidirectional::ChannelState::<E>::new(String::from("Channel A -> B"), false);
+            black_box(bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob"))
+        })
+    });
+
+    let ch = init_channel::<E>();
+    c.bench_function(&format!("{}/init_customer", curve), |b| {
+        b.iter_batched(|| ch.channel_token.clone(),
+                       |mut channel_token| bidirectional::init_customer(rng, &ch.channel_state, &mut channel_token, B0_CUSTOMER, B0_MERCHANT, "Alice"),
+                       BatchSize::SmallInput)
+    });
+
+    c.bench_function(&format!("{}/establish", curve), |b| {
+        b.iter_batched(|| init_channel::<E>(), |mut ch| establish(&mut ch), BatchSize::SmallInput)
+    });
+
+    let mut ch = init_channel::<E>();
+    establish(&mut ch);
+
+    c.bench_function(&format!("{}/pay_generate_proof", curve), |b| {
+        b.iter(|| bidirectional::generate_payment_proof(rng, &ch.channel_state, &ch.cust_state, PAY_INC))
+    });
+
+    let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &ch.channel_state, &ch.cust_state, PAY_INC);
+    c.bench_function(&format!("{}/pay_verify_proof", curve), |b| {
+        b.iter(|| bidirectional::verify_payment_proof(rng, &ch.channel_state, &payment, &ch.merch_state, &mut ch.merch_ledger))
+    });
+
+    let new_close_token = bidirectional::verify_payment_proof(rng, &ch.channel_state, &payment, &ch.merch_state, &mut ch.merch_ledger);
+    c.bench_function(&format!("{}/pay_generate_revoke_token", curve), |b| {
+        b.iter_batched(|| (ch.cust_state.clone(), new_cust_state.clone()),
+                       |(mut old_cust_state, new_cust_state)| bidirectional::generate_revoke_token(&ch.channel_state, &mut old_cust_state, new_cust_state, &new_close_token),
+                       BatchSize::SmallInput)
+    });
+
+    let revoke_token = bidirectional::generate_revoke_token(&ch.channel_state, &mut ch.cust_state, new_cust_state, &new_close_token);
+    c.bench_function(&format!("{}/pay_verify_revoke_token", curve), |b| {
+        b.iter(|| bidirectional::verify_revoke_token(&revoke_token, &mut ch.merch_ledger))
+    });
+
+    let new_pay_token = bidirectional::verify_revoke_token(&revoke_token, &mut ch.merch_ledger).unwrap().unwrap();
+    assert!(ch.cust_state.verify_pay_token(&ch.channel_state, &new_pay_token));
+
+    c.bench_function(&format!("{}/customer_close", curve), |b| {
+        b.iter(|| bidirectional::customer_close(&ch.channel_state, &ch.cust_state))
+    });
+
+    let cust_close = bidirectional::customer_close(&ch.channel_state, &ch.cust_state);
+    c.bench_function(&format!("{}/merchant_close", curve), |b| {
+        b.iter(|| bidirectional::merchant_close(&ch.channel_state, &ch.channel_token, &cust_close, &mut ch.merch_ledger))
+    });
+
+    let mut close_msg = cust_close.message.clone();
+    close_msg.close = Some(util::hash_to_fr::<E>(String::from("close").into_bytes()));
+    c.bench_function(&format!("{}/wtp_verify_cust_close_message", curve), |b| {
+        b.iter(|| bidirectional::wtp_verify_cust_close_message(&ch.channel_token, &cust_close.wpk, &close_msg, &cust_close.signature))
+    });
+
+    let merch_close = ch.merch_state.sign_revoke_message(String::from("merchant-address"), &Some(revoke_token.signature));
+    c.bench_function(&format!("{}/wtp_verify_merch_close_message", curve), |b| {
+        b.iter(|| bidirectional::wtp_verify_merch_close_message(&ch.channel_token, &merch_close))
+    });
+}
+
+fn bench_bls12(c: &mut Criterion) {
+    bench_protocol::<Bls12>(c, "bls12");
+}
+
+fn bench_bn256(c: &mut Criterion) {
+    bench_protocol::<Bn256>(c, "bn256");
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_bls12, bench_bn256
+}
+criterion_main!(benches);
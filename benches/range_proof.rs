@@ -0,0 +1,89 @@
+#![allow(non_snake_case)]
+
+#[macro_use]
+extern crate criterion;
+extern crate bolt;
+extern crate ff_bl as ff;
+extern crate pairing_bl as pairing;
+extern crate rand;
+
+use bolt::ccs08::{SecretParamsUL, RPSecretParams};
+use bolt::ped92::CSMultiParams;
+use bolt::util;
+use criterion::{Criterion, BenchmarkId};
+use ff::Rand;
+use pairing::Engine;
+use pairing::bls12_381::Bls12;
+use pairing::bn256::Bn256;
+
+// (u, l) pairs: the first ones vary the base u for a fixed number of digits l,
+// the last ones vary l for the base used by RPSecretParams::setup
+const UL_SWEEP: [(i64, i64); 8] = [(16, 4), (32, 4), (64, 4), (128, 4), (57, 2), (57, 4), (57, 6), (57, 8)];
+
+fn bench_ul<E: Engine>(c: &mut Criterion, curve: &str) {
+    let rng = &mut rand::thread_rng();
+    let csParams = CSMultiParams::<E>::setup_gen_params(rng, 1);
+
+    let mut group = c.benchmark_group(format!("{}/range_proof_ul", curve));
+    for &(u, l) in UL_SWEEP.iter() {
+        let id = format!("u={}/l={}", u, l);
+        group.bench_with_input(BenchmarkId::new("setup", &id), &(u, l), |b, &(u, l)| {
+            b.iter(|| SecretParamsUL::<E>::setup_ul(rng, u, l, csParams.clone()))
+        });
+
+        let secParams = SecretParamsUL::<E>::setup_ul(rng, u, l, csParams.clone());
+        let x = (u.pow(l as u32) - 1) / 2;
+        let r = E::Fr::rand(rng);
+        let C = csParams.commit(&vec!(util::convert_int_to_fr::<E>(x)), &r);
+        group.bench_with_input(BenchmarkId::new("prove", &id), &x, |b, &x| {
+            b.iter(|| secParams.pubParams.prove_ul(rng, x, r, C.clone(), 1, vec!{}))
+        });
+
+        let proof = secParams.pubParams.prove_ul(rng, x, r, C.clone(), 1, vec!{});
+        let ch = secParams.pubParams.compute_challenge(&proof);
+        group.bench_with_input(BenchmarkId::new("verify", &id), &proof, |b, proof| {
+            b.iter(|| secParams.pubParams.verify_ul(proof, ch, 1))
+        });
+    }
+    group.finish();
+}
+
+fn bench_range_proof<E: Engine>(c: &mut Criterion, curve: &str) {
+    let rng = &mut rand::thread_rng();
+    let csParams = CSMultiParams::<E>::setup_gen_params(rng, 1);
+
+    let mut group = c.benchmark_group(format!("{}/range_proof", curve));
+    for &b in [1000i64, 1000000, 1000000000].iter() {
+        let secParams = RPSecretParams::<E>::setup(rng, 0, b, csParams.clone());
+        let x = b / 2;
+        let r = E::Fr::rand(rng);
+        let C = csParams.commit(&vec!(util::convert_int_to_fr::<E>(x)), &r);
+        group.bench_with_input(BenchmarkId::new("prove", b), &x, |bh, &x| {
+            bh.iter(|| secParams.pubParams.prove(rng, x, C.clone(), r, 1, vec!{}))
+        });
+
+        let proof = secParams.pubParams.prove(rng, x, C.clone(), r, 1, vec!{});
+        let ch = secParams.pubParams.compute_challenge(&proof).unwrap();
+        group.bench_with_input(BenchmarkId::new("verify", b), &proof, |bh, proof| {
+            bh.iter(|| secParams.pubParams.verify(proof.clone(), ch, 1))
+        });
+    }
+    group.finish();
+}
+
+fn bench_bls12(c: &mut Criterion) {
+    bench_ul::<Bls12>(c, "bls12");
+    bench_range_proof::<Bls12>(c, "bls12");
+}
+
+fn bench_bn256(c: &mut Criterion) {
+    bench_ul::<Bn256>(c, "bn256");
+    bench_range_proof::<Bn256>(c, "bn256");
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_bls12, bench_bn256
+}
+criterion_main!(benches);
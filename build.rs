@@ -0,0 +1,24 @@
+#[cfg(feature = "header")]
+extern crate cbindgen;
+
+#[cfg(feature = "header")]
+fn generate_header() {
+    use std::env;
+    use std::path::Path;
+
+    // the per-curve shims are generated by macros, so cbindgen expands the crate first (see cbindgen.toml)
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_root_or_default(&crate_dir);
+    cbindgen::generate_with_config(&crate_dir, config)
+        .expect("unable to generate the C header")
+        .write_to_file(Path::new(&crate_dir).join("include").join("libbolt_ffi.h"));
+}
+
+#[cfg(not(feature = "header"))]
+fn generate_header() {}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/ffishim.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    generate_header();
+}
@@ -54,9 +54,9 @@ fn main() {
     let pay_inc = 20;
     let pay_inc2 = 10;
 
-    let (mut channel_token, mut merch_state, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob");
+    let (mut channel_token, merch_state, mut merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob");
 
-    let mut cust_state = bidirectional::init_customer(rng, &mut channel_token, b0_customer, b0_merchant, "Alice");
+    let mut cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, b0_customer, b0_merchant, "Alice").unwrap().unwrap();
 
     println!("{}", cust_state);
 
@@ -67,7 +67,7 @@ fn main() {
     // obtain close token for closing out channel
     let channel_id = channel_token.compute_channel_id();
     let option = bidirectional::establish_merchant_issue_close_token(rng, &channel_state, &com, &com_proof,
-                                                                                         &channel_id, b0_customer, b0_merchant, &merch_state);
+                                                                                         &channel_id, b0_customer, b0_merchant, &merch_state, &mut merch_ledger);
     let close_token= match option {
         Ok(n) => n.unwrap(),
         Err(e) => panic!("Failed - bidirectional::establish_merchant_issue_close_token(): {}", e)
@@ -87,13 +87,13 @@ fn main() {
     let (payment, new_cust_state, pay_time) = measure_two_arg!(bidirectional::generate_payment_proof(rng, &channel_state, &cust_state, pay_inc));
     println!(">> Time to generate payment proof: {} ms", pay_time);
 
-    let (new_close_token, verify_time) = measure_one_arg!(bidirectional::verify_payment_proof(rng, &channel_state, &payment, &mut merch_state));
+    let (new_close_token, verify_time) = measure_one_arg!(bidirectional::verify_payment_proof(rng, &channel_state, &payment, &merch_state, &mut merch_ledger));
     println!(">> Time to verify payment proof: {} ms", verify_time);
 
     let revoke_token = bidirectional::generate_revoke_token(&channel_state, &mut cust_state, new_cust_state, &new_close_token);
 
     // send revoke token and get pay-token in response
-    let new_pay_token_result = bidirectional::verify_revoke_token(&revoke_token, &mut merch_state);
+    let new_pay_token_result = bidirectional::verify_revoke_token(&revoke_token, &mut merch_ledger);
     let new_pay_token = handle_bolt_result!(new_pay_token_result);
 
     // verify the pay token and update internal state
@@ -104,13 +104,13 @@ fn main() {
     let (payment2, new_cust_state2, pay_time2) = measure_two_arg!(bidirectional::generate_payment_proof(rng, &channel_state, &cust_state, pay_inc2));
     println!(">> Time to generate payment proof 2: {} ms", pay_time2);
 
-    let (new_close_token2, verify_time2) = measure_one_arg!(bidirectional::verify_payment_proof(rng, &channel_state, &payment2, &mut merch_state));
+    let (new_close_token2, verify_time2) = measure_one_arg!(bidirectional::verify_payment_proof(rng, &channel_state, &payment2, &merch_state, &mut merch_ledger));
     println!(">> Time to verify payment proof 2: {} ms", verify_time2);
 
     let revoke_token2 = bidirectional::generate_revoke_token(&channel_state, &mut cust_state, new_cust_state2, &new_close_token2);
 
     // send revoke token and get pay-token in response
-    let new_pay_token_result2 = bidirectional::verify_revoke_token(&revoke_token2, &mut merch_state);
+    let new_pay_token_result2 = bidirectional::verify_revoke_token(&revoke_token2, &mut merch_ledger);
     let new_pay_token2 = handle_bolt_result!(new_pay_token_result2);
 
     // verify the pay token and update internal state
@@ -6,33 +6,38 @@ package main
 import "C"
 import (
 	"encoding/json"
-	"strings"
 	"fmt"
 )
 
 type setupResp struct {
-	ChannelState           string `json:"channel_state"`
-	ChannelToken           string `json:"channel_token"`
-	CustState              string `json:"cust_state"`
-	MerchState             string `json:"merch_state"`
-	Com                    string `json:"com"`
-	ComProof               string `json:"com_proof"`
-	IsTokenValid           bool   `json:"is_token_valid,string"`
-	IsEstablished          bool   `json:"is_established,string"`
-	IsPayValid             bool   `json:"is_pay_valid,string"`
-	Payment                string `json:"payment"`
-	CloseToken             string `json:"close_token"`
-	SenderCloseToken       string `json:"sender_close_token"`
-	ReceiverCondCloseToken string `json:"receiver_cond_close_token"`
-	RevokeToken            string `json:"revoke_token"`
-	PayToken               string `json:"pay_token"`
-	SenderPayToken         string `json:"sender_pay_token"`
-	ReceiverPayToken       string `json:"receiver_pay_token"`
-	CustClose              string `json:"cust_close"`
-	MerchClose             string `json:"merch_close"`
-	Wpk                    string `json:"wpk"`
-	Error                  string `json:"error"`
-	Result                 string `json:"result"`
+	ChannelState           string     `json:"channel_state"`
+	ChannelToken           string     `json:"channel_token"`
+	CustState              string     `json:"cust_state"`
+	MerchSecrets           string     `json:"merch_secrets"`
+	MerchLedger            string     `json:"merch_ledger"`
+	Com                    string     `json:"com"`
+	ComProof               string     `json:"com_proof"`
+	IsTokenValid           bool       `json:"is_token_valid"`
+	IsEstablished          bool       `json:"is_established"`
+	IsPayValid             bool       `json:"is_pay_valid"`
+	Payment                string     `json:"payment"`
+	CloseToken             string     `json:"close_token"`
+	SenderCloseToken       string     `json:"sender_close_token"`
+	ReceiverCondCloseToken string     `json:"receiver_cond_close_token"`
+	RevokeToken            string     `json:"revoke_token"`
+	PayToken               string     `json:"pay_token"`
+	SenderPayToken         string     `json:"sender_pay_token"`
+	ReceiverPayToken       string     `json:"receiver_pay_token"`
+	CustClose              string     `json:"cust_close"`
+	MerchClose             string     `json:"merch_close"`
+	Wpk                    string     `json:"wpk"`
+	Error                  *boltError `json:"error"`
+	Result                 bool       `json:"result"`
+}
+
+type boltError struct {
+	Code    int    `json:"code"`
+	Message string `json:"message"`
 }
 
 type ChannelState struct {
@@ -59,39 +64,47 @@ type PubParams struct {
 }
 
 type RpPubParams struct {
-	ComParams  ComParams            `json:"csParams"`
-	L          int64                `json:"l"`
-	U          int64                `json:"u"`
-	Mpk        MPK                  `json:"mpk"`
-	Pk         PublicKey            `json:"pk"`
-	Signatures map[string]Signature `json:"signatures"`
-}
-
+	ComParams  ComParams   `json:"csParams"`
+	L          int64       `json:"l"`
+	U          int64       `json:"u"`
+	Mpk        MPK         `json:"mpk"`
+	Pk         PublicKey   `json:"pk"`
+	Signatures []string    `json:"signatures"`
+}
+
+type MerchSecrets struct {
+	Id           string     `json:"id"`
+	Sk           string     `json:"sk"`
+	ClSk         SecretKey  `json:"cl_sk"`
+	RpSk         SecretKey  `json:"rp_sk"`
+	Epoch        uint32     `json:"epoch"`
+	OldClSk      *SecretKey `json:"old_cl_sk"`
+	OldRpSk      *SecretKey `json:"old_rp_sk"`
+	OldPubParams *PubParams `json:"old_pub_params"`
+}
+
+// MerchState is a merchant state loaded from its secrets by BidirectionalLoadMerchant,
+// which stays in Rust until it is released with Free
 type MerchState struct {
-	Id         string                `json:"id"`
-	KeyPair    KeyPair               `json:"keypair"`
-	NizkParams NIZKParams            `json:"nizkParams"`
-	Pk         string                `json:"pk"`
-	Sk         string                `json:"sk"`
-	ComParams  ComParams             `json:"comParams"`
-	Keys       map[string]RevokedKey `json:"keys"`
-	PayTokens  map[string]Signature  `json:"pay_tokens"`
+	state *C.MerchantStateBls12
 }
 
-type RevokedKey struct {
-	Wpk         string  `json:"wpk"`
-	RevokeToken *string `json:"revoke_token"`
+type MerchLedger struct {
+	Keys      map[string]RevokedKey    `json:"keys"`
+	PayTokens map[string]Signature     `json:"pay_tokens"`
+	Channels  map[string]ChannelRecord `json:"channels"`
 }
 
-type NIZKParams struct {
-	PubParams PubParams `json:"pubParams"`
-	KeyPair   KeyPair   `json:"keypair"`
-	RpParams  RpParams  `json:"rpParams"`
+type ChannelRecord struct {
+	InitCustBalance  int64  `json:"init_cust_balance"`
+	InitMerchBalance int64  `json:"init_merch_balance"`
+	KeyEpoch         uint32 `json:"key_epoch"`
+	Closed           bool   `json:"closed"`
 }
 
-type RpParams struct {
-	PubParams RpPubParams `json:"pubParams"`
-	KeyPair   KeyPair     `json:"kp"`
+type RevokedKey struct {
+	Wpk         string  `json:"wpk"`
+	RevokeToken *string `json:"revoke_token"`
 }
 
 type CustState struct {
@@ -128,11 +141,6 @@ type Wallet struct {
 	Close     []string `json:"close"`
 }
 
-type KeyPair struct {
-	Secret SecretKey `json:"secret"`
-	Public PublicKey `json:"public"`
-}
-
 type SecretKey struct {
 	X []string   `json:"x"`
 	Y [][]string `json:"y"`
@@ -243,36 +251,65 @@ func BidirectionalChannelSetup(name string, channelSupport bool) (ChannelState,
 	return channelState, err
 }
 
-func BidirectionalInitMerchant(channelState ChannelState, nameMerchant string) (ChannelToken, MerchState, ChannelState, error) {
+func BidirectionalInitMerchant(channelState ChannelState, nameMerchant string) (ChannelToken, MerchSecrets, MerchLedger, ChannelState, error) {
 	serChannelState, err := json.Marshal(channelState)
 	if err != nil {
-		return ChannelToken{}, MerchState{}, ChannelState{}, err
+		return ChannelToken{}, MerchSecrets{}, MerchLedger{}, ChannelState{}, err
 	}
 	resp := C.GoString(C.ffishim_bls12_init_merchant(C.CString(string(serChannelState)), C.CString(nameMerchant)))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return ChannelToken{}, MerchState{}, ChannelState{}, err
+		return ChannelToken{}, MerchSecrets{}, MerchLedger{}, ChannelState{}, err
 	}
-	merchState := MerchState{}
-	err = json.Unmarshal([]byte(r.MerchState), &merchState)
+	merchSecrets := MerchSecrets{}
+	err = json.Unmarshal([]byte(r.MerchSecrets), &merchSecrets)
 	if err != nil {
-		return ChannelToken{}, MerchState{}, ChannelState{}, err
+		return ChannelToken{}, MerchSecrets{}, MerchLedger{}, ChannelState{}, err
+	}
+	merchLedger := MerchLedger{}
+	err = json.Unmarshal([]byte(r.MerchLedger), &merchLedger)
+	if err != nil {
+		return ChannelToken{}, MerchSecrets{}, MerchLedger{}, ChannelState{}, err
 	}
 	err = json.Unmarshal([]byte(r.ChannelState), &channelState)
 	if err != nil {
-		return ChannelToken{}, MerchState{}, ChannelState{}, err
+		return ChannelToken{}, MerchSecrets{}, MerchLedger{}, ChannelState{}, err
 	}
 	channelToken := ChannelToken{}
 	err = json.Unmarshal([]byte(r.ChannelToken), &channelToken)
-	return channelToken, merchState, channelState, err
+	return channelToken, merchSecrets, merchLedger, channelState, err
+}
+
+func BidirectionalLoadMerchant(merchSecrets MerchSecrets, channelState ChannelState) (MerchState, error) {
+	serMerchSecrets, err := json.Marshal(merchSecrets)
+	if err != nil {
+		return MerchState{}, err
+	}
+	serChannelState, err := json.Marshal(channelState)
+	if err != nil {
+		return MerchState{}, err
+	}
+	state := C.ffishim_bls12_load_merchant(C.CString(string(serMerchSecrets)), C.CString(string(serChannelState)))
+	if state == nil {
+		return MerchState{}, fmt.Errorf("merchant secrets do not match the channel state")
+	}
+	return MerchState{state: state}, nil
 }
 
-func BidirectionalInitCustomer(channelToken ChannelToken, balanceCustomer int, balanceMerchant int, nameCustomer string) (ChannelToken, CustState, error) {
+func (merchState MerchState) Free() {
+	C.ffishim_bls12_free_merchant(merchState.state)
+}
+
+func BidirectionalInitCustomer(channelState ChannelState, channelToken ChannelToken, balanceCustomer int, balanceMerchant int, nameCustomer string) (ChannelToken, CustState, error) {
+	serChannelState, err := json.Marshal(channelState)
+	if err != nil {
+		return ChannelToken{}, CustState{}, err
+	}
 	serChannelToken, err := json.Marshal(channelToken)
 	if err != nil {
 		return ChannelToken{}, CustState{}, err
 	}
-	resp := C.GoString(C.ffishim_bls12_init_customer(C.CString(string(serChannelToken)), C.longlong(balanceCustomer), C.longlong(balanceMerchant), C.CString(nameCustomer)))
+	resp := C.GoString(C.ffishim_bls12_init_customer(C.CString(string(serChannelState)), C.CString(string(serChannelToken)), C.int64_t(balanceCustomer), C.int64_t(balanceMerchant), C.CString(nameCustomer)))
 	r, err := processCResponse(resp)
 	if err != nil {
 		return ChannelToken{}, CustState{}, err
@@ -336,35 +373,39 @@ func BidirectionalGenerateChannelID(channelToken ChannelToken) (error) {
 
 
 
-func BidirectionalEstablishMerchantIssueCloseToken(channelState ChannelState, com Commitment, comProof CommitmentProof, channelId []string, initCustBal int, initMerchBal int, merchState MerchState) (Signature, error) {
+func BidirectionalEstablishMerchantIssueCloseToken(channelState ChannelState, com Commitment, comProof CommitmentProof, channelId []string, initCustBal int, initMerchBal int, merchState MerchState, merchLedger MerchLedger) (Signature, MerchLedger, error) {
 	serChannelState, err := json.Marshal(channelState)
 	if err != nil {
-		return Signature{}, err
+		return Signature{}, MerchLedger{}, err
 	}
 	serCom, err := json.Marshal(com)
 	if err != nil {
-		return Signature{}, err
+		return Signature{}, MerchLedger{}, err
 	}
-	serMerchState, err := json.Marshal(merchState)
+	serMerchLedger, err := json.Marshal(merchLedger)
 	if err != nil {
-		return Signature{}, err
+		return Signature{}, MerchLedger{}, err
 	}
 	serComProof, err := json.Marshal(comProof)
 	if err != nil {
-		return Signature{}, err
+		return Signature{}, MerchLedger{}, err
 	}
 	serChannelId, err := json.Marshal(channelId)
 	if err != nil {
-		return Signature{}, err
+		return Signature{}, MerchLedger{}, err
 	}
-	resp := C.GoString(C.ffishim_bls12_establish_merchant_issue_close_token(C.CString(string(serChannelState)), C.CString(string(serCom)), C.CString(string(serComProof)), C.CString(string(serChannelId)), C.longlong(initCustBal), C.longlong(initMerchBal), C.CString(string(serMerchState))))
+	resp := C.GoString(C.ffishim_bls12_establish_merchant_issue_close_token(C.CString(string(serChannelState)), C.CString(string(serCom)), C.CString(string(serComProof)), C.CString(string(serChannelId)), C.int64_t(initCustBal), C.int64_t(initMerchBal), merchState.state, C.CString(string(serMerchLedger))))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return Signature{}, err
+		return Signature{}, MerchLedger{}, err
+	}
+	err = json.Unmarshal([]byte(r.MerchLedger), &merchLedger)
+	if err != nil {
+		return Signature{}, MerchLedger{}, err
 	}
 	closeToken := Signature{}
 	err = json.Unmarshal([]byte(r.CloseToken), &closeToken)
-	return closeToken, err
+	return closeToken, merchLedger, err
 }
 
 func BidirectionalEstablishMerchantIssuePayToken(channelState ChannelState, com Commitment, merchState MerchState) (Signature, error) {
@@ -376,11 +417,7 @@ func BidirectionalEstablishMerchantIssuePayToken(channelState ChannelState, com
 	if err != nil {
 		return Signature{}, err
 	}
-	serMerchState, err := json.Marshal(merchState)
-	if err != nil {
-		return Signature{}, err
-	}
-	resp := C.GoString(C.ffishim_bls12_establish_merchant_issue_pay_token(C.CString(string(serChannelState)), C.CString(string(serCom)), C.CString(string(serMerchState))))
+	resp := C.GoString(C.ffishim_bls12_establish_merchant_issue_pay_token(C.CString(string(serChannelState)), C.CString(string(serCom)), merchState.state))
 	r, err := processCResponse(resp)
 	if err != nil {
 		return Signature{}, err
@@ -451,7 +488,7 @@ func BidirectionalPayGeneratePaymentProof(channelState ChannelState, custState C
 	if err != nil {
 		return Payment{}, CustState{}, err
 	}
-	resp := C.GoString(C.ffishim_bls12_pay_generate_payment_proof(C.CString(string(serChannelState)), C.CString(string(serCustState)), C.longlong(amount)))
+	resp := C.GoString(C.ffishim_bls12_pay_generate_payment_proof(C.CString(string(serChannelState)), C.CString(string(serCustState)), C.int64_t(amount)))
 	r, err := processCResponse(resp)
 	if err != nil {
 		return Payment{}, CustState{}, err
@@ -465,67 +502,67 @@ func BidirectionalPayGeneratePaymentProof(channelState ChannelState, custState C
 	return payProof, custState, err
 }
 
-func BidirectionalPayVerifyPaymentProof(channelState ChannelState, payProof Payment, merchState MerchState) (Signature, MerchState, error) {
+func BidirectionalPayVerifyPaymentProof(channelState ChannelState, payProof Payment, merchState MerchState, merchLedger MerchLedger) (Signature, MerchLedger, error) {
 	serChannelState, err := json.Marshal(channelState)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
 	serPayProof, err := json.Marshal(payProof)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
-	serMerchState, err := json.Marshal(merchState)
+	serMerchLedger, err := json.Marshal(merchLedger)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
-	resp := C.GoString(C.ffishim_bls12_pay_verify_payment_proof(C.CString(string(serChannelState)), C.CString(string(serPayProof)), C.CString(string(serMerchState))))
+	resp := C.GoString(C.ffishim_bls12_pay_verify_payment_proof(C.CString(string(serChannelState)), C.CString(string(serPayProof)), merchState.state, C.CString(string(serMerchLedger))))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
-	err = json.Unmarshal([]byte(r.MerchState), &merchState)
+	err = json.Unmarshal([]byte(r.MerchLedger), &merchLedger)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
 	closeToken := &Signature{}
 	err = json.Unmarshal([]byte(r.CloseToken), closeToken)
-	return *closeToken, merchState, err
+	return *closeToken, merchLedger, err
 }
 
-func BidirectionalPayVerifyMultiplePaymentProofs(channelState ChannelState, senderPayProof Payment, receiverPayProof Payment, merchState MerchState) (Signature, Signature, MerchState, error) {
+func BidirectionalPayVerifyMultiplePaymentProofs(channelState ChannelState, senderPayProof Payment, receiverPayProof Payment, merchState MerchState, merchLedger MerchLedger) (Signature, Signature, MerchLedger, error) {
 	serChannelState, err := json.Marshal(channelState)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	serSenderPayProof, err := json.Marshal(senderPayProof)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	serReceiverPayProof, err := json.Marshal(receiverPayProof)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
-	serMerchState, err := json.Marshal(merchState)
+	serMerchLedger, err := json.Marshal(merchLedger)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
-	resp := C.GoString(C.ffishim_bls12_pay_verify_multiple_payment_proofs(C.CString(string(serChannelState)), C.CString(string(serSenderPayProof)), C.CString(string(serReceiverPayProof)), C.CString(string(serMerchState))))
+	resp := C.GoString(C.ffishim_bls12_pay_verify_multiple_payment_proofs(C.CString(string(serChannelState)), C.CString(string(serSenderPayProof)), C.CString(string(serReceiverPayProof)), merchState.state, C.CString(string(serMerchLedger))))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
-	err = json.Unmarshal([]byte(r.MerchState), &merchState)
+	err = json.Unmarshal([]byte(r.MerchLedger), &merchLedger)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	senderCloseToken := &Signature{}
 	err = json.Unmarshal([]byte(r.SenderCloseToken), senderCloseToken)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	receiverCondCloseToken := &Signature{}
 	err = json.Unmarshal([]byte(r.ReceiverCondCloseToken), receiverCondCloseToken)
-	return *senderCloseToken, *receiverCondCloseToken, merchState, err
+	return *senderCloseToken, *receiverCondCloseToken, merchLedger, err
 }
 
 func BidirectionalPayGenerateRevokeToken(channelState ChannelState, custState CustState, newCustState CustState, closeToken Signature) (RevokeToken, CustState, error) {
@@ -559,59 +596,59 @@ func BidirectionalPayGenerateRevokeToken(channelState ChannelState, custState Cu
 	return revokeToken, custState, err
 }
 
-func BidirectionalPayVerifyRevokeToken(revokeToken RevokeToken, merchState MerchState) (Signature, MerchState, error) {
-	serMerchState, err := json.Marshal(merchState)
+func BidirectionalPayVerifyRevokeToken(revokeToken RevokeToken, merchLedger MerchLedger) (Signature, MerchLedger, error) {
+	serMerchLedger, err := json.Marshal(merchLedger)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
 	serRevokeToken, err := json.Marshal(revokeToken)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
-	resp := C.GoString(C.ffishim_bls12_pay_verify_revoke_token(C.CString(string(serRevokeToken)), C.CString(string(serMerchState))))
+	resp := C.GoString(C.ffishim_bls12_pay_verify_revoke_token(C.CString(string(serRevokeToken)), C.CString(string(serMerchLedger))))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
-	err = json.Unmarshal([]byte(r.MerchState), &merchState)
+	err = json.Unmarshal([]byte(r.MerchLedger), &merchLedger)
 	if err != nil {
-		return Signature{}, MerchState{}, err
+		return Signature{}, MerchLedger{}, err
 	}
 	payToken := &Signature{}
 	err = json.Unmarshal([]byte(r.PayToken), payToken)
-	return *payToken, merchState, err
+	return *payToken, merchLedger, err
 }
 
-func BidirectionalPayVerifyMultipleRevokeTokens(senderRevokeToken RevokeToken, receiverRevokeToken RevokeToken, merchState MerchState) (Signature, Signature, MerchState, error) {
-	serMerchState, err := json.Marshal(merchState)
+func BidirectionalPayVerifyMultipleRevokeTokens(senderRevokeToken RevokeToken, receiverRevokeToken RevokeToken, merchLedger MerchLedger) (Signature, Signature, MerchLedger, error) {
+	serMerchLedger, err := json.Marshal(merchLedger)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	serSenderRevokeToken, err := json.Marshal(senderRevokeToken)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	serReceiverRevokeToken, err := json.Marshal(receiverRevokeToken)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
-	resp := C.GoString(C.ffishim_bls12_pay_verify_multiple_revoke_tokens(C.CString(string(serSenderRevokeToken)), C.CString(string(serReceiverRevokeToken)), C.CString(string(serMerchState))))
+	resp := C.GoString(C.ffishim_bls12_pay_verify_multiple_revoke_tokens(C.CString(string(serSenderRevokeToken)), C.CString(string(serReceiverRevokeToken)), C.CString(string(serMerchLedger))))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
-	err = json.Unmarshal([]byte(r.MerchState), &merchState)
+	err = json.Unmarshal([]byte(r.MerchLedger), &merchLedger)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	senderPayToken := &Signature{}
 	err = json.Unmarshal([]byte(r.SenderPayToken), senderPayToken)
 	if err != nil {
-		return Signature{}, Signature{}, MerchState{}, err
+		return Signature{}, Signature{}, MerchLedger{}, err
 	}
 	receiverPayToken := &Signature{}
 	err = json.Unmarshal([]byte(r.ReceiverPayToken), receiverPayToken)
-	return *senderPayToken, *receiverPayToken, merchState, err
+	return *senderPayToken, *receiverPayToken, merchLedger, err
 }
 
 func BidirectionalPayVerifyPaymentToken(channelState ChannelState, custState CustState, payToken Signature) (CustState, bool, error) {
@@ -655,70 +692,73 @@ func BidirectionalCustomerClose(channelState ChannelState, custState CustState)
 	return custClose, err
 }
 
-func BidirectionalMerchantClose(channelState ChannelState, channelToken ChannelToken, serAddress string, custClose CustClose, merchState MerchState) (string, string, string, error) {
+func BidirectionalMerchantClose(channelState ChannelState, channelToken ChannelToken, serAddress string, custClose CustClose, merchState MerchState, merchLedger MerchLedger) (string, string, MerchLedger, string, error) {
 	serChannelState, err := json.Marshal(channelState)
 	if err != nil {
-		return "", "", "", err
+		return "", "", MerchLedger{}, "", err
 	}
-	serMerchState, err := json.Marshal(merchState)
+	serMerchLedger, err := json.Marshal(merchLedger)
 	if err != nil {
-		return "", "", "", err
+		return "", "", MerchLedger{}, "", err
 	}
 	serChannelToken, err := json.Marshal(channelToken)
 	if err != nil {
-		return "", "", "", err
+		return "", "", MerchLedger{}, "", err
 	}
 	serCustClose, err := json.Marshal(custClose)
 	if err != nil {
-		return "", "", "", err
+		return "", "", MerchLedger{}, "", err
 	}
-	resp := C.GoString(C.ffishim_bls12_merchant_close(C.CString(string(serChannelState)), C.CString(string(serChannelToken)), C.CString(serAddress), C.CString(string(serCustClose)), C.CString(string(serMerchState))))
+	resp := C.GoString(C.ffishim_bls12_merchant_close(C.CString(string(serChannelState)), C.CString(string(serChannelToken)), C.CString(serAddress), C.CString(string(serCustClose)), merchState.state, C.CString(string(serMerchLedger))))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return "", "", "", err
+		return "", "", MerchLedger{}, "", err
+	}
+	// a valid close marks the channel as closed, so the ledger is returned with or without a dispute
+	if r.MerchLedger != "" {
+		err = json.Unmarshal([]byte(r.MerchLedger), &merchLedger)
+		if err != nil {
+			return "", "", MerchLedger{}, "", err
+		}
+	}
+	if r.Error != nil {
+		return r.Wpk, r.MerchClose, merchLedger, r.Error.Message, nil
 	}
-	return r.Wpk, r.MerchClose, r.Error, nil
+	return r.Wpk, r.MerchClose, merchLedger, "", nil
 }
 
-func BidirectionalWtpVerifyCustCloseMessage(channelToken ChannelToken, serWpk string, serCloseMsg string, serCloseToken string) (string, error) {
+func BidirectionalWtpVerifyCustCloseMessage(channelToken ChannelToken, serWpk string, serCloseMsg string, serCloseToken string) (bool, error) {
 	serChannelToken, err := json.Marshal(channelToken)
 	if err != nil {
-		return "", err
+		return false, err
 	}
 	resp := C.GoString(C.ffishim_bls12_wtp_verify_cust_close_message(C.CString(string(serChannelToken)), C.CString(serWpk), C.CString(serCloseMsg), C.CString(string(serCloseToken))))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return "", err
+		return false, err
 	}
 	return r.Result, nil
 }
 
-func BidirectionalWtpVerifyMerchCloseMessage(channelToken ChannelToken, serWpk string, serMerchClose string) (string, error) {
+func BidirectionalWtpVerifyMerchCloseMessage(channelToken ChannelToken, serWpk string, serMerchClose string) (bool, error) {
 	serChannelToken, err := json.Marshal(channelToken)
 	if err != nil {
-		return "", err
+		return false, err
 	}
 	resp := C.GoString(C.ffishim_bls12_wtp_verify_merch_close_message(C.CString(string(serChannelToken)), C.CString(serWpk), C.CString(serMerchClose)))
 	r, err := processCResponse(resp)
 	if err != nil {
-		return "", err
+		return false, err
 	}
 	return r.Result, nil
 }
 
 func processCResponse(resp string) (*setupResp, error) {
-	resp = cleanJson(resp)
 	r := &setupResp{}
 	err := json.Unmarshal([]byte(resp), r)
 	return r, err
 }
 
-func cleanJson(in string) string {
-	resp := strings.ReplaceAll(in, "\"", "\\\"")
-	resp = strings.ReplaceAll(resp, "'", "\"")
-	return resp
-}
-
 func btoi(b bool) int {
 	if b {
 		return 1
@@ -6,37 +6,56 @@ import (
 )
 
 func Test_ChannelSetup(t *testing.T) {
-	_, channelToken, merchState, custState, err := setup(1000, 100)
+	_, channelToken, merchState, merchLedger, custState, err := setup(1000, 100)
 	assert.Nil(t, err)
 
 	assert.NotEqual(t, MerchState{}, merchState)
+	merchState.Free()
+	assert.Empty(t, merchLedger.Channels)
 	assert.NotEqual(t, CustState{}, custState)
 	assert.NotEqual(t, ChannelToken{}, channelToken)
 }
 
-func setup(b0Cust int, b0Merch int) (ChannelState, ChannelToken, MerchState, CustState, error) {
+func Test_LoadMerchantRejectsOtherChannel(t *testing.T) {
 	channelState, err := BidirectionalChannelSetup("Test Channel", false)
+	assert.Nil(t, err)
+	_, merchSecrets, _, _, err := BidirectionalInitMerchant(channelState, "Bob")
+	assert.Nil(t, err)
+	_, _, _, otherChannelState, err := BidirectionalInitMerchant(channelState, "Carol")
+	assert.Nil(t, err)
+
+	_, err = BidirectionalLoadMerchant(merchSecrets, otherChannelState)
+	assert.NotNil(t, err)
+}
+
+func setup(b0Cust int, b0Merch int) (ChannelState, ChannelToken, MerchState, MerchLedger, CustState, error) {
+	channelState, err := BidirectionalChannelSetup("Test Channel", false)
+	if err != nil {
+		return ChannelState{}, ChannelToken{}, MerchState{}, MerchLedger{}, CustState{}, err
+	}
+	channelToken, merchSecrets, merchLedger, channelState, err := BidirectionalInitMerchant(channelState, "Bob")
 	if err != nil {
-		return ChannelState{}, ChannelToken{}, MerchState{}, CustState{}, err
+		return ChannelState{}, ChannelToken{}, MerchState{}, MerchLedger{}, CustState{}, err
 	}
-	channelToken, merchState, channelState, err := BidirectionalInitMerchant(channelState, "Bob")
+	merchState, err := BidirectionalLoadMerchant(merchSecrets, channelState)
 	if err != nil {
-		return ChannelState{}, ChannelToken{}, MerchState{}, CustState{}, err
+		return ChannelState{}, ChannelToken{}, MerchState{}, MerchLedger{}, CustState{}, err
 	}
-	channelToken, custState, err := BidirectionalInitCustomer(channelToken, b0Cust, b0Merch, "Alice")
-	return channelState, channelToken, merchState, custState, err
+	channelToken, custState, err := BidirectionalInitCustomer(channelState, channelToken, b0Cust, b0Merch, "Alice")
+	return channelState, channelToken, merchState, merchLedger, custState, err
 }
 
 func Test_Establish(t *testing.T) {
 	b0Cust := 1000
 	b0Merch := 100
-	channelState, channelToken, merchState, custState, err := setup(b0Cust, b0Merch)
+	channelState, channelToken, merchState, merchLedger, custState, err := setup(b0Cust, b0Merch)
 	assert.Nil(t, err)
+	defer merchState.Free()
 
 	channelToken, custState, com, comProof, err := BidirectionalEstablishCustomerGenerateProof(channelToken, custState)
 	assert.Nil(t, err)
 
-	closeToken, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custState.Wallet.ChannelId, b0Cust, b0Merch, merchState)
+	closeToken, merchLedger, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custState.Wallet.ChannelId, b0Cust, b0Merch, merchState, merchLedger)
 	assert.Nil(t, err)
 	assert.NotNil(t, closeToken)
 
@@ -57,11 +76,12 @@ func Test_Establish(t *testing.T) {
 func Test_Pay(t *testing.T) {
 	b0Cust := 1000
 	b0Merch := 100
-	channelState, channelToken, merchState, custState, err := setup(b0Cust, b0Merch)
+	channelState, channelToken, merchState, merchLedger, custState, err := setup(b0Cust, b0Merch)
 	assert.Nil(t, err)
+	defer merchState.Free()
 	channelToken, custState, com, comProof, err := BidirectionalEstablishCustomerGenerateProof(channelToken, custState)
 	assert.Nil(t, err)
-	closeToken, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custState.Wallet.ChannelId, b0Cust, b0Merch, merchState)
+	closeToken, merchLedger, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custState.Wallet.ChannelId, b0Cust, b0Merch, merchState, merchLedger)
 	assert.Nil(t, err)
 	_, channelState, custState, err = BidirectionalVerifyCloseToken(channelState, custState, closeToken)
 	assert.Nil(t, err)
@@ -72,11 +92,11 @@ func Test_Pay(t *testing.T) {
 
 	payment, newCustState, err := BidirectionalPayGeneratePaymentProof(channelState, custState, 10)
 	assert.Nil(t, err)
-	closeToken, merchState, err = BidirectionalPayVerifyPaymentProof(channelState, payment, merchState)
+	closeToken, merchLedger, err = BidirectionalPayVerifyPaymentProof(channelState, payment, merchState, merchLedger)
 	assert.Nil(t, err)
 	revokeToken, custState, err := BidirectionalPayGenerateRevokeToken(channelState, custState, newCustState, closeToken)
 	assert.Nil(t, err)
-	payToken, merchState, err = BidirectionalPayVerifyRevokeToken(revokeToken, merchState)
+	payToken, merchLedger, err = BidirectionalPayVerifyRevokeToken(revokeToken, merchLedger)
 	assert.Nil(t, err)
 	custState, isTokenValid, err := BidirectionalPayVerifyPaymentToken(channelState, custState, payToken)
 	assert.Nil(t, err)
@@ -89,13 +109,16 @@ func Test_IntermediaryPay(t *testing.T) {
 	b0Intermediary := 100
 	channelState, err := BidirectionalChannelSetup("Test Channel", false)
 	assert.Nil(t, err)
-	channelToken, merchState, channelState, err := BidirectionalInitMerchant(channelState, "Hub")
+	channelToken, merchSecrets, merchLedger, channelState, err := BidirectionalInitMerchant(channelState, "Hub")
 	assert.Nil(t, err)
-	channelToken, custStateAlice, err := BidirectionalInitCustomer(channelToken, b0Alice, b0Intermediary, "Alice")
+	merchState, err := BidirectionalLoadMerchant(merchSecrets, channelState)
+	assert.Nil(t, err)
+	defer merchState.Free()
+	channelToken, custStateAlice, err := BidirectionalInitCustomer(channelState, channelToken, b0Alice, b0Intermediary, "Alice")
 	assert.Nil(t, err)
 	channelToken, custStateAlice, com, comProof, err := BidirectionalEstablishCustomerGenerateProof(channelToken, custStateAlice)
 	assert.Nil(t, err)
-	closeToken, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custStateAlice.Wallet.ChannelId, b0Alice, b0Intermediary, merchState)
+	closeToken, merchLedger, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custStateAlice.Wallet.ChannelId, b0Alice, b0Intermediary, merchState, merchLedger)
 	assert.Nil(t, err)
 	_, channelState, custStateAlice, err = BidirectionalVerifyCloseToken(channelState, custStateAlice, closeToken)
 	assert.Nil(t, err)
@@ -103,11 +126,11 @@ func Test_IntermediaryPay(t *testing.T) {
 	assert.Nil(t, err)
 	_, channelState, custStateAlice, err = BidirectionalEstablishCustomerFinal(channelState, custStateAlice, payToken)
 	assert.Nil(t, err)
-	channelToken, custStateBob, err := BidirectionalInitCustomer(channelToken, b0Bob, b0Intermediary, "Bob")
+	channelToken, custStateBob, err := BidirectionalInitCustomer(channelState, channelToken, b0Bob, b0Intermediary, "Bob")
 	assert.Nil(t, err)
 	channelToken, custStateBob, com, comProof, err = BidirectionalEstablishCustomerGenerateProof(channelToken, custStateBob)
 	assert.Nil(t, err)
-	closeToken, err = BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custStateBob.Wallet.ChannelId, b0Bob, b0Intermediary, merchState)
+	closeToken, merchLedger, err = BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custStateBob.Wallet.ChannelId, b0Bob, b0Intermediary, merchState, merchLedger)
 	assert.Nil(t, err)
 	_, channelState, custStateBob, err = BidirectionalVerifyCloseToken(channelState, custStateBob, closeToken)
 	assert.Nil(t, err)
@@ -120,13 +143,13 @@ func Test_IntermediaryPay(t *testing.T) {
 	assert.Nil(t, err)
 	paymentB, newCustStateBob, err := BidirectionalPayGeneratePaymentProof(channelState, custStateBob, -10)
 	assert.Nil(t, err)
-	closeTokenA, closeTokenB, merchState, err := BidirectionalPayVerifyMultiplePaymentProofs(channelState, paymentA, paymentB, merchState)
+	closeTokenA, closeTokenB, merchLedger, err := BidirectionalPayVerifyMultiplePaymentProofs(channelState, paymentA, paymentB, merchState, merchLedger)
 	assert.Nil(t, err)
 	revokeTokenA, custStateAlice, err := BidirectionalPayGenerateRevokeToken(channelState, custStateAlice, newCustStateAlice, closeTokenA)
 	assert.Nil(t, err)
 	revokeTokenB, custStateBob, err := BidirectionalPayGenerateRevokeToken(channelState, custStateBob, newCustStateBob, closeTokenB)
 	assert.Nil(t, err)
-	payTokenA, payTokenB, merchState, err := BidirectionalPayVerifyMultipleRevokeTokens(revokeTokenA, revokeTokenB, merchState)
+	payTokenA, payTokenB, merchLedger, err := BidirectionalPayVerifyMultipleRevokeTokens(revokeTokenA, revokeTokenB, merchLedger)
 	assert.Nil(t, err)
 	custStateAlice, isTokenValid, err := BidirectionalPayVerifyPaymentToken(channelState, custStateAlice, payTokenA)
 	assert.Nil(t, err)
@@ -139,11 +162,12 @@ func Test_IntermediaryPay(t *testing.T) {
 func Test_Close(t *testing.T) {
 	b0Cust := 1000
 	b0Merch := 100
-	channelState, channelToken, merchState, custState, err := setup(b0Cust, b0Merch)
+	channelState, channelToken, merchState, merchLedger, custState, err := setup(b0Cust, b0Merch)
 	assert.Nil(t, err)
+	defer merchState.Free()
 	channelToken, custState, com, comProof, err := BidirectionalEstablishCustomerGenerateProof(channelToken, custState)
 	assert.Nil(t, err)
-	closeToken, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custState.Wallet.ChannelId, b0Cust, b0Merch, merchState)
+	closeToken, merchLedger, err := BidirectionalEstablishMerchantIssueCloseToken(channelState, com, comProof, custState.Wallet.ChannelId, b0Cust, b0Merch, merchState, merchLedger)
 	assert.Nil(t, err)
 	_, channelState, custState, err = BidirectionalVerifyCloseToken(channelState, custState, closeToken)
 	assert.Nil(t, err)
@@ -154,19 +178,22 @@ func Test_Close(t *testing.T) {
 
 	payment, newCustState, err := BidirectionalPayGeneratePaymentProof(channelState, custState, 10)
 	assert.Nil(t, err)
-	closeToken, merchState, err = BidirectionalPayVerifyPaymentProof(channelState, payment, merchState)
+	closeToken, merchLedger, err = BidirectionalPayVerifyPaymentProof(channelState, payment, merchState, merchLedger)
 	assert.Nil(t, err)
 	revokeToken, custState, err := BidirectionalPayGenerateRevokeToken(channelState, custState, newCustState, closeToken)
 	assert.Nil(t, err)
-	payToken, merchState, err = BidirectionalPayVerifyRevokeToken(revokeToken, merchState)
+	payToken, merchLedger, err = BidirectionalPayVerifyRevokeToken(revokeToken, merchLedger)
 	assert.Nil(t, err)
 	custState, _, err = BidirectionalPayVerifyPaymentToken(channelState, custState, payToken)
 	assert.Nil(t, err)
 
 	custClose, err := BidirectionalCustomerClose(channelState, custState)
 	assert.Nil(t, err)
-	_, _, Err, err := BidirectionalMerchantClose(channelState, channelToken, "onChainAddress", custClose, merchState)
+	_, _, merchLedger, Err, err := BidirectionalMerchantClose(channelState, channelToken, "onChainAddress", custClose, merchState, merchLedger)
 	assert.Nil(t, err)
 	assert.Equal(t, "merchant_close - Could not find entry for wpk & revoke token pair. Valid close!", Err)
+	for _, channel := range merchLedger.Channels {
+		assert.True(t, channel.Closed)
+	}
 }
 
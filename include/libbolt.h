@@ -15,31 +15,37 @@ using namespace rapidjson;
 extern "C" {
 #endif
 
+// merchant states loaded from their secrets stay in Rust behind opaque pointers
+typedef struct MerchantState_Bls12 MerchantStateBls12;
+typedef struct MerchantState_Bn256 MerchantStateBn256;
+
 // channel init
-char* ffishim_bls12_channel_setup(const char *channel_name, unsigned int third_party_support);
+char* ffishim_bls12_channel_setup(const char *channel_name, uint32_t third_party_support);
 char* ffishim_bls12_init_merchant(const char *ser_channel_state, const char *name_ptr);
-char* ffishim_bls12_init_customer(const char *ser_channel_token, long long int balance_customer,  long long int balance_merchant, const char *name_ptr);
+MerchantStateBls12* ffishim_bls12_load_merchant(const char *ser_merch_secrets, const char *ser_channel_state);
+void ffishim_bls12_free_merchant(MerchantStateBls12 *merch_state);
+char* ffishim_bls12_init_customer(const char *ser_channel_state, const char *ser_channel_token, int64_t balance_customer,  int64_t balance_merchant, const char *name_ptr);
 
 // channel establish protocol routines
 char* ffishim_bls12_establish_customer_generate_proof(const char *ser_channel_token, const char *ser_customer_wallet);
 char* ffishim_bls12_generate_channel_id(const char *ser_channel_token);
-char* ffishim_bls12_establish_merchant_issue_close_token(const char *ser_channel_state, const char *ser_com, const char *ser_com_proof, const char *ser_pk_c, long long int init_cust_bal, long long int init_merch_bal, const char *ser_merch_state);
-char* ffishim_bls12_establish_merchant_issue_pay_token(const char *ser_channel_state, const char *ser_com, const char *ser_merch_state);
+char* ffishim_bls12_establish_merchant_issue_close_token(const char *ser_channel_state, const char *ser_com, const char *ser_com_proof, const char *ser_pk_c, int64_t init_cust_bal, int64_t init_merch_bal, const MerchantStateBls12 *merch_state, const char *ser_merch_ledger);
+char* ffishim_bls12_establish_merchant_issue_pay_token(const char *ser_channel_state, const char *ser_com, const MerchantStateBls12 *merch_state);
 char* ffishim_bls12_verify_close_token(const char *ser_channel_state, const char *ser_customer_wallet, const char *ser_close_token);
 char* ffishim_bls12_establish_customer_final(const char *ser_channel_state, const char *ser_customer_wallet, const char *ser_pay_token);
 
 // channel pay protocol routines
-char* ffishim_bls12_pay_generate_payment_proof(const char *ser_channel_state, const char *ser_customer_wallet, long long int amount);
-char* ffishim_bls12_pay_verify_payment_proof(const char *ser_channel_state, const char *ser_pay_proof, const char *ser_merch_state);
-char* ffishim_bls12_pay_verify_multiple_payment_proofs(const char *ser_channel_state, const char *ser_sender_pay_proof, const char *ser_receiver_pay_proof, const char *ser_merch_state);
+char* ffishim_bls12_pay_generate_payment_proof(const char *ser_channel_state, const char *ser_customer_wallet, int64_t amount);
+char* ffishim_bls12_pay_verify_payment_proof(const char *ser_channel_state, const char *ser_pay_proof, const MerchantStateBls12 *merch_state, const char *ser_merch_ledger);
+char* ffishim_bls12_pay_verify_multiple_payment_proofs(const char *ser_channel_state, const char *ser_sender_pay_proof, const char *ser_receiver_pay_proof, const MerchantStateBls12 *merch_state, const char *ser_merch_ledger);
 char* ffishim_bls12_pay_generate_revoke_token(const char *ser_channel_state, const char *ser_cust_state, const char *ser_new_cust_state, const char *ser_close_token);
-char* ffishim_bls12_pay_verify_revoke_token(const char *ser_revoke_token, const char *ser_merch_state);
-char* ffishim_bls12_pay_verify_multiple_revoke_tokens(const char *ser_sender_revoke_token, const char *ser_receiver_revoke_token, const char *ser_merch_state);
+char* ffishim_bls12_pay_verify_revoke_token(const char *ser_revoke_token, const char *ser_merch_ledger);
+char* ffishim_bls12_pay_verify_multiple_revoke_tokens(const char *ser_sender_revoke_token, const char *ser_receiver_revoke_token, const char *ser_merch_ledger);
 char* ffishim_bls12_pay_verify_payment_token(const char *ser_channel_state, const char *ser_cust_state, const char *ser_pay_token);
 
 // closing routines for both sides
 char* ffishim_bls12_customer_close(const char *ser_channel_state, const char *ser_cust_state);
-char* ffishim_bls12_merchant_close(const char *ser_channel_state, const char *ser_channel_token, const char *ser_address, const char *ser_cust_close, const char *ser_merch_state);
+char* ffishim_bls12_merchant_close(const char *ser_channel_state, const char *ser_channel_token, const char *ser_address, const char *ser_cust_close, const MerchantStateBls12 *merch_state, const char *ser_merch_ledger);
 
 // WTP logic for on-chain validation of closing messages
 char* ffishim_bls12_wtp_verify_cust_close_message(const char *ser_channel_token, const char *ser_wpk, const char *ser_close_msg, const char *ser_close_token);
@@ -47,21 +53,68 @@ char* ffishim_bls12_wtp_verify_merch_close_message(const char *ser_channel_token
 
 char* ffishim_bls12_wtp_check_wpk(const char *wpk);
 
-#ifdef __cplusplus
-
-  const char* string_replace_all(const char* previous_string, char old_char, char new_char)
-  {
-    std::string s(previous_string);
-    std::string old_c(1,old_char);
-    std::string new_c(1,new_char);
-    size_t index;
-    while ((index = s.find(old_c)) != std::string::npos) {
-        s.replace(index, 1, new_c);
-    }
+// bn256 variants of the routines above
+char* ffishim_bn256_channel_setup(const char *channel_name, uint32_t third_party_support);
+char* ffishim_bn256_init_merchant(const char *ser_channel_state, const char *name_ptr);
+MerchantStateBn256* ffishim_bn256_load_merchant(const char *ser_merch_secrets, const char *ser_channel_state);
+void ffishim_bn256_free_merchant(MerchantStateBn256 *merch_state);
+char* ffishim_bn256_init_customer(const char *ser_channel_state, const char *ser_channel_token, int64_t balance_customer,  int64_t balance_merchant, const char *name_ptr);
+char* ffishim_bn256_establish_customer_generate_proof(const char *ser_channel_token, const char *ser_customer_wallet);
+char* ffishim_bn256_generate_channel_id(const char *ser_channel_token);
+char* ffishim_bn256_establish_merchant_issue_close_token(const char *ser_channel_state, const char *ser_com, const char *ser_com_proof, const char *ser_pk_c, int64_t init_cust_bal, int64_t init_merch_bal, const MerchantStateBn256 *merch_state, const char *ser_merch_ledger);
+char* ffishim_bn256_establish_merchant_issue_pay_token(const char *ser_channel_state, const char *ser_com, const MerchantStateBn256 *merch_state);
+char* ffishim_bn256_verify_close_token(const char *ser_channel_state, const char *ser_customer_wallet, const char *ser_close_token);
+char* ffishim_bn256_establish_customer_final(const char *ser_channel_state, const char *ser_customer_wallet, const char *ser_pay_token);
+char* ffishim_bn256_pay_generate_payment_proof(const char *ser_channel_state, const char *ser_customer_wallet, int64_t amount);
+char* ffishim_bn256_pay_verify_payment_proof(const char *ser_channel_state, const char *ser_pay_proof, const MerchantStateBn256 *merch_state, const char *ser_merch_ledger);
+char* ffishim_bn256_pay_verify_multiple_payment_proofs(const char *ser_channel_state, const char *ser_sender_pay_proof, const char *ser_receiver_pay_proof, const MerchantStateBn256 *merch_state, const char *ser_merch_ledger);
+char* ffishim_bn256_pay_generate_revoke_token(const char *ser_channel_state, const char *ser_cust_state, const char *ser_new_cust_state, const char *ser_close_token);
+char* ffishim_bn256_pay_verify_revoke_token(const char *ser_revoke_token, const char *ser_merch_ledger);
+char* ffishim_bn256_pay_verify_multiple_revoke_tokens(const char *ser_sender_revoke_token, const char *ser_receiver_revoke_token, const char *ser_merch_ledger);
+char* ffishim_bn256_pay_verify_payment_token(const char *ser_channel_state, const char *ser_cust_state, const char *ser_pay_token);
+char* ffishim_bn256_customer_close(const char *ser_channel_state, const char *ser_cust_state);
+char* ffishim_bn256_merchant_close(const char *ser_channel_state, const char *ser_channel_token, const char *ser_address, const char *ser_cust_close, const MerchantStateBn256 *merch_state, const char *ser_merch_ledger);
+char* ffishim_bn256_wtp_verify_cust_close_message(const char *ser_channel_token, const char *ser_wpk, const char *ser_close_msg, const char *ser_close_token);
+char* ffishim_bn256_wtp_verify_merch_close_message(const char *ser_channel_token, const char *ser_wpk, const char *ser_merch_close);
+char* ffishim_bn256_wtp_check_wpk(const char *wpk);
+
+void ffishim_free_string(char *pointer);
+
+// handle-based API: states stay in Rust behind opaque pointers
+typedef struct BoltChannel BoltChannel;
+typedef struct BoltMerchant BoltMerchant;
+typedef struct BoltCustomer BoltCustomer;
+
+BoltChannel* bolt_channel_new(const char *channel_name, uint32_t third_party_support);
+void bolt_channel_free(BoltChannel *channel);
+char* bolt_channel_export(const BoltChannel *channel);
+BoltChannel* bolt_channel_import(const char *ser_channel);
+
+BoltMerchant* bolt_merchant_new(BoltChannel *channel, const char *name_ptr);
+void bolt_merchant_free(BoltMerchant *merchant);
+char* bolt_merchant_export(const BoltMerchant *merchant);
+BoltMerchant* bolt_merchant_import(const BoltChannel *channel, const char *ser_merchant);
+char* bolt_merchant_channel_token(const BoltMerchant *merchant);
+char* bolt_merchant_ledger(const BoltMerchant *merchant);
+char* bolt_merchant_establish_issue_close_token(BoltMerchant *merchant, const BoltChannel *channel, const char *ser_com, const char *ser_com_proof, const char *ser_channel_id, int64_t init_cust_bal, int64_t init_merch_bal);
+char* bolt_merchant_establish_issue_pay_token(const BoltMerchant *merchant, const BoltChannel *channel, const char *ser_com);
+char* bolt_merchant_pay_verify_payment_proof(BoltMerchant *merchant, const BoltChannel *channel, const char *ser_pay_proof);
+char* bolt_merchant_pay_verify_revoke_token(BoltMerchant *merchant, const char *ser_revoke_token);
+char* bolt_merchant_close(BoltMerchant *merchant, const BoltChannel *channel, const char *ser_channel_token, const char *ser_cust_close, const char *ser_address);
+
+BoltCustomer* bolt_customer_new(const BoltChannel *channel, const char *ser_channel_token, int64_t balance_customer, int64_t balance_merchant, const char *name_ptr);
+void bolt_customer_free(BoltCustomer *customer);
+char* bolt_customer_export(const BoltCustomer *customer);
+BoltCustomer* bolt_customer_import(const char *ser_customer);
+char* bolt_customer_establish_generate_proof(const BoltCustomer *customer);
+char* bolt_customer_establish_verify_close_token(BoltCustomer *customer, const BoltChannel *channel, const char *ser_close_token);
+char* bolt_customer_establish_final(BoltCustomer *customer, BoltChannel *channel, const char *ser_pay_token);
+char* bolt_customer_pay_generate_payment_proof(BoltCustomer *customer, const BoltChannel *channel, int64_t amount);
+char* bolt_customer_pay_generate_revoke_token(BoltCustomer *customer, const BoltChannel *channel, const char *ser_close_token);
+char* bolt_customer_pay_verify_payment_token(BoltCustomer *customer, const BoltChannel *channel, const char *ser_pay_token);
+char* bolt_customer_close(const BoltCustomer *customer, const BoltChannel *channel);
 
-    printf("STRING: %s\n", s.c_str());
-    return s.c_str();
-  }
+#ifdef __cplusplus
 
   int wtp_check_wpk(const char *wpk)
   {
@@ -83,12 +136,16 @@ char* ffishim_bls12_wtp_check_wpk(const char *wpk);
 
     Document d;
     d.Parse(return_json);
+    // Errors are reported as {"error": {"code": .., "message": ..}}
+    if (d.HasMember("error")) {
+      return 0;
+    }
     // Make sure we arent going to get an error when indexing into the JSON
     assert(d.HasMember("result"));
     Value& s = d["result"];
 
     // If the return_value is true, then return 1.  Otherwise, just assume 0
-    if( std::string(s.GetString()).compare(std::string("true")) == 0)
+    if (s.IsBool() && s.GetBool())
     {
       return 1;
     }
@@ -103,16 +160,20 @@ char* ffishim_bls12_wtp_check_wpk(const char *wpk);
   int wtp_verify_merch_close_message(const char *channel_token, const char *wpk, const char *merch_close)
   {
     // Call into Rust
-    const char* return_json = string_replace_all(ffishim_bls12_wtp_verify_merch_close_message(channel_token, wpk, merch_close), '\'', '\"');
+    const char* return_json = ffishim_bls12_wtp_verify_merch_close_message(channel_token, wpk, merch_close);
 
     Document d;
     d.Parse(return_json);
+    // Errors are reported as {"error": {"code": .., "message": ..}}
+    if (d.HasMember("error")) {
+      return 0;
+    }
     // Make sure we arent going to get an error when indexing into the JSON
     assert(d.HasMember("result"));
     Value& s = d["result"];
 
     // If the return_value is true, then return 1.  Otherwise, just assume 0
-    if( std::string(s.GetString()).compare(std::string("true")) == 0)
+    if (s.IsBool() && s.GetBool())
     {
       return 1;
     }
@@ -1,6 +1,6 @@
 import unittest
-import libbolt
-import ast, random, json
+import bolt
+import random, json
 
 def rand_hex(stringLength=10):
     """Generate a random hex string of fixed length """
@@ -8,7 +8,7 @@ def rand_hex(stringLength=10):
     return ''.join(random.choice(hex_letters) for i in range(stringLength))
 
 def malformed_token(token):
-    token_dict = ast.literal_eval(token)
+    token_dict = json.loads(token)
     updated_token = {}
     for k,v in token_dict.items():
         if type(v) == str:
@@ -22,55 +22,54 @@ def malformed_proof(proof):
     bad_proof = bad_proof.replace("1", "2")
     return bad_proof
 
+def establish_channel(test, channel_state, channel_token, cust_state, merch_state, merch_ledger):
+    (com, com_proof) = bolt.establish_customer_generate_proof(channel_token, cust_state)
+
+    close_token = bolt.establish_merchant_issue_close_token(channel_state, com, com_proof, channel_token.channel_id(),
+                                                            cust_state.cust_balance, cust_state.merch_balance, merch_state, merch_ledger)
+    test.assertTrue(bolt.establish_customer_verify_close_token(channel_state, cust_state, close_token))
+
+    pay_token = bolt.establish_merchant_issue_pay_token(channel_state, com, merch_state)
+    test.assertTrue(bolt.establish_customer_final(channel_state, cust_state, pay_token))
+
+def pay_on_channel(test, channel_state, cust_state, merch_state, merch_ledger, amount):
+    (payment_proof, new_cust_state) = bolt.pay_generate_payment_proof(channel_state, cust_state, amount)
+    new_close_token = bolt.pay_verify_payment_proof(channel_state, payment_proof, merch_state, merch_ledger)
+    revoke_token = bolt.pay_generate_revoke_token(channel_state, cust_state, new_cust_state, new_close_token)
+    pay_token = bolt.pay_verify_revoke_token(revoke_token, merch_ledger)
+    test.assertTrue(bolt.pay_verify_payment_token(channel_state, cust_state, pay_token))
+
 class BoltEstablishTests(unittest.TestCase):
     def setUp(self):
-        self.bolt = libbolt.Libbolt('target/{}/{}bolt.{}'.format(libbolt.mode, libbolt.prefix, libbolt.ext))
-        self.channel_state = self.bolt.channel_setup("Test Channel")
+        self.channel_state = bolt.ChannelState("Test Channel")
         self.b0_cust = 1000
         self.b0_merch = 100
-        (self.channel_token, self.merch_state, self.channel_state) = self.bolt.bidirectional_init_merchant(self.channel_state, "Bob")
-        (channel_token, self.cust_state) = self.bolt.bidirectional_init_customer(self.channel_token, self.b0_cust, self.b0_merch, "Alice")
+        (channel_token, self.merch_state, self.merch_ledger) = bolt.init_merchant(self.channel_state, "Bob")
+        # each customer sets its own public key on a copy of the merchant's channel token
+        channel_token_json = channel_token.to_json()
+        self.channel_token = bolt.ChannelToken.from_json(channel_token_json)
+        self.cust_state = bolt.init_customer(self.channel_state, self.channel_token, self.b0_cust, self.b0_merch, "Alice")
 
         # generate some bad stuff here
         larger_b0_cust = 2000
-        (channel_token_bad, self.cust_state_bad) = self.bolt.bidirectional_init_customer(self.channel_token, larger_b0_cust, self.b0_merch, "Alice")
-
-        # set them
-        self.channel_token = channel_token
-        self.channel_token_bad = channel_token_bad
-
+        self.channel_token_bad = bolt.ChannelToken.from_json(channel_token_json)
+        self.cust_state_bad = bolt.init_customer(self.channel_state, self.channel_token_bad, larger_b0_cust, self.b0_merch, "Alice")
 
     def test_establish_works_okay(self):
         """
         Establish protocol common case works
         """
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(self.channel_token, self.cust_state)
-
-        cust_state_dict = json.loads(cust_state)
-        close_token = self.bolt.bidirectional_establish_merchant_issue_close_token(self.channel_state, com, com_proof, cust_state_dict["wallet"]["channelId"], self.b0_cust, self.b0_merch, self.merch_state)
-        self.assertTrue(close_token is not None)
-
-        (is_token_valid, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(self.channel_state, cust_state, close_token)
-        self.assertTrue(is_token_valid)
-
-        pay_token = self.bolt.bidirectional_establish_merchant_issue_pay_token(channel_state, com, self.merch_state)
-        self.assertTrue(pay_token is not None)
-
-        (is_channel_established, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_final(channel_state, cust_state, pay_token)
-
-        self.assertTrue(is_channel_established)
-        #print("Establish protocol works as expected.")
+        establish_channel(self, self.channel_state, self.channel_token, self.cust_state, self.merch_state, self.merch_ledger)
+        self.assertTrue(self.channel_state.channel_established)
 
     def test_establish_merchant_issue_close_token_fail_as_expected(self):
         """
         Initial com proof fails as expected when commitment opening doesn't match expected initial customer and merchant balances
         """
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(self.channel_token_bad, self.cust_state_bad)
+        (com, com_proof) = bolt.establish_customer_generate_proof(self.channel_token_bad, self.cust_state_bad)
 
-        cust_state_dict = json.loads(cust_state)
-        close_token = self.bolt.bidirectional_establish_merchant_issue_close_token(self.channel_state, com, com_proof, cust_state_dict["wallet"]["channelId"], self.b0_cust, self.b0_merch, self.merch_state)
-        self.assertTrue(close_token is None)
-        #print("Establish protocol fail works as expected.")
+        with self.assertRaises(bolt.BoltError):
+            bolt.establish_merchant_issue_close_token(self.channel_state, com, com_proof, self.channel_token_bad.channel_id(), self.b0_cust, self.b0_merch, self.merch_state, self.merch_ledger)
 
     def test_establish_customer_verify_close_token_fail_as_expected(self):
         """
@@ -78,319 +77,223 @@ class BoltEstablishTests(unittest.TestCase):
         """
         close_token = json.dumps({"h":"b896166d76a7bd02565b6431dca27da4c290e234edfbca8d9189f78311e18f66a138684c91efdf7fd1c4b192bf27f68e",
                                   "H":"add6c20994749185fb7d44f8f5f1f3dbbcd250e4922a9c6c9017c25dda670d94c4b279b7f0fccd56916bf737a29a1938"})
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(self.channel_token, self.cust_state)
-
-        (is_token_valid, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(self.channel_state, cust_state, close_token)
-        self.assertTrue(is_token_valid is False)
+        self.assertFalse(bolt.establish_customer_verify_close_token(self.channel_state, self.cust_state, close_token))
 
     def test_establish_merchant_issue_pay_token_fail_as_expected(self):
         """
         Specifying a different commitment leads to an invalid pay token as expected
         """
         bad_com = json.dumps({"c":"852a57e24a2192e1cea19157e44f92d58369751f2012bc1f4a4312a89a63c74a92a4cb1d362b37ae0eda3b3bd1333502"})
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(self.channel_token, self.cust_state)
+        (com, com_proof) = bolt.establish_customer_generate_proof(self.channel_token, self.cust_state)
 
-        cust_state_dict = json.loads(cust_state)
-        close_token = self.bolt.bidirectional_establish_merchant_issue_close_token(self.channel_state, com, com_proof, cust_state_dict["wallet"]["channelId"], self.b0_cust, self.b0_merch, self.merch_state)
-        self.assertTrue(close_token is not None)
+        close_token = bolt.establish_merchant_issue_close_token(self.channel_state, com, com_proof, self.channel_token.channel_id(), self.b0_cust, self.b0_merch, self.merch_state, self.merch_ledger)
+        self.assertTrue(bolt.establish_customer_verify_close_token(self.channel_state, self.cust_state, close_token))
 
-        (is_token_valid, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(self.channel_state, cust_state, close_token)
-        self.assertTrue(is_token_valid)
-
-        pay_token = self.bolt.bidirectional_establish_merchant_issue_pay_token(channel_state, bad_com, self.merch_state)
-        self.assertTrue(pay_token is not None)
-
-        (is_channel_established, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_final(channel_state, cust_state, pay_token)
-
-        self.assertFalse(is_channel_established)
+        pay_token = bolt.establish_merchant_issue_pay_token(self.channel_state, bad_com, self.merch_state)
+        self.assertFalse(bolt.establish_customer_final(self.channel_state, self.cust_state, pay_token))
 
     def test_establish_not_complete_without_close_token(self):
         """
         Test that missing close token prevents the customer from establishing
         """
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(self.channel_token, self.cust_state)
-
-        pay_token = self.bolt.bidirectional_establish_merchant_issue_pay_token(self.channel_state, com, self.merch_state)
-        self.assertTrue(pay_token is not None)
+        (com, com_proof) = bolt.establish_customer_generate_proof(self.channel_token, self.cust_state)
 
-        (is_channel_established, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_final(self.channel_state, cust_state, pay_token)
-
-        self.assertFalse(is_channel_established)
+        pay_token = bolt.establish_merchant_issue_pay_token(self.channel_state, com, self.merch_state)
+        self.assertFalse(bolt.establish_customer_final(self.channel_state, self.cust_state, pay_token))
 
     def test_error_handling_with_serialization(self):
         """
         Test that malformed close and/or pay token results in failure
-        :return:
         """
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(self.channel_token, self.cust_state)
-
-        cust_state_dict = json.loads(cust_state)
-        close_token = self.bolt.bidirectional_establish_merchant_issue_close_token(self.channel_state, com, com_proof, cust_state_dict["wallet"]["channelId"], self.b0_cust, self.b0_merch, self.merch_state)
-        self.assertTrue(close_token is not None)
+        (com, com_proof) = bolt.establish_customer_generate_proof(self.channel_token, self.cust_state)
 
-        malformed_close_token = malformed_token(close_token)
-        (is_token_valid, bad_channel_state, bad_cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(self.channel_state, cust_state, malformed_close_token)
-        self.assertTrue(is_token_valid is None)
+        close_token = bolt.establish_merchant_issue_close_token(self.channel_state, com, com_proof, self.channel_token.channel_id(), self.b0_cust, self.b0_merch, self.merch_state, self.merch_ledger)
+        with self.assertRaises(ValueError):
+            bolt.establish_customer_verify_close_token(self.channel_state, self.cust_state, malformed_token(close_token))
 
-        (is_token_valid, self.channel_state, cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(self.channel_state, cust_state, close_token)
+        self.assertTrue(bolt.establish_customer_verify_close_token(self.channel_state, self.cust_state, close_token))
 
-        pay_token = self.bolt.bidirectional_establish_merchant_issue_pay_token(self.channel_state, com, self.merch_state)
-        malformed_pay_token = malformed_token(pay_token)
-
-        (is_channel_established, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_final(self.channel_state, cust_state, malformed_pay_token)
-        self.assertFalse(is_channel_established)
+        pay_token = bolt.establish_merchant_issue_pay_token(self.channel_state, com, self.merch_state)
+        with self.assertRaises(ValueError):
+            bolt.establish_customer_final(self.channel_state, self.cust_state, malformed_token(pay_token))
+        self.assertFalse(self.channel_state.channel_established)
 
 class BoltPayTests(unittest.TestCase):
     def setUp(self):
         """
         Setup init customer/merchant state and establish phase of Bolt protocol
-        :return:
         """
-        self.bolt = libbolt.Libbolt('target/{}/{}bolt.{}'.format(libbolt.mode, libbolt.prefix, libbolt.ext))
-        self.channel_state = self.bolt.channel_setup("Test Channel")
-        self.b0_cust = 500
-        self.b0_merch = 10
-        (self.channel_token, self.merch_state, self.channel_state) = self.bolt.bidirectional_init_merchant(self.channel_state, "Bob")
-        (self.channel_token, self.cust_state) = self.bolt.bidirectional_init_customer(self.channel_token, self.b0_cust, self.b0_merch, "Alice")
-
-        (self.channel_token, self.cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(self.channel_token, self.cust_state)
-
-        cust_state_dict = json.loads(self.cust_state)
-        close_token = self.bolt.bidirectional_establish_merchant_issue_close_token(self.channel_state, com, com_proof, cust_state_dict["wallet"]["channelId"], self.b0_cust, self.b0_merch, self.merch_state)
-        self.assertTrue(close_token is not None)
-
-        (is_token_valid, self.channel_state, self.cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(self.channel_state, self.cust_state, close_token)
-        self.assertTrue(is_token_valid)
-
-        pay_token = self.bolt.bidirectional_establish_merchant_issue_pay_token(self.channel_state, com, self.merch_state)
-        self.assertTrue(pay_token is not None)
-
-        (is_channel_established, self.channel_state, self.cust_state) = self.bolt.bidirectional_establish_customer_final(self.channel_state, self.cust_state, pay_token)
-
-        self.assertTrue(is_channel_established)
+        self.channel_state = bolt.ChannelState("Test Channel")
+        (self.channel_token, self.merch_state, self.merch_ledger) = bolt.init_merchant(self.channel_state, "Bob")
+        self.cust_state = bolt.init_customer(self.channel_state, self.channel_token, 500, 10, "Alice")
+        establish_channel(self, self.channel_state, self.channel_token, self.cust_state, self.merch_state, self.merch_ledger)
 
     def test_pay_protocol_works(self):
         """
         Payment protocol works
-        :return:
         """
-        amount = 10
-        (payment_proof, new_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(self.channel_state, self.cust_state, amount)
-
-        (new_close_token, self.merch_state) = self.bolt.bidirectional_pay_verify_payment_proof(self.channel_state, payment_proof, self.merch_state)
+        pay_on_channel(self, self.channel_state, self.cust_state, self.merch_state, self.merch_ledger, 10)
+        self.assertEqual(self.cust_state.cust_balance, 490)
+        self.assertEqual(self.cust_state.merch_balance, 20)
 
-        (revoke_token, self.cust_state) = self.bolt.bidirectional_pay_generate_revoke_token(self.channel_state, self.cust_state, new_cust_state, new_close_token)
-
-        (pay_token, self.merch_state) = self.bolt.bidirectional_pay_verify_revoke_token(revoke_token, self.merch_state)
+    def test_pay_protocol_works_with_restored_merchant_state(self):
+        """
+        Merchant state restored from its secrets and the channel state accepts payments
+        """
+        merch_secrets = self.merch_state.export_secrets_json()
+        merch_state = bolt.MerchantState.from_secrets_json(merch_secrets, self.channel_state)
+        merch_ledger = bolt.MerchantLedger.from_json(self.merch_ledger.to_json())
+        pay_on_channel(self, self.channel_state, self.cust_state, merch_state, merch_ledger, 10)
+        self.assertEqual(self.cust_state.cust_balance, 490)
 
-        (self.cust_state, is_pay_valid) = self.bolt.bidirectional_pay_verify_payment_token(self.channel_state, self.cust_state, pay_token)
-        self.assertTrue(is_pay_valid)
+        with self.assertRaises(bolt.BoltError):
+            bolt.MerchantState.from_secrets_json(merch_secrets, bolt.ChannelState("Other Channel"))
 
     def test_pay_protocol_bad_payment_proof_fail_handled(self):
         """
         Payment protocol fails as expected when customer sends a bad payment proof
-        :return:
         """
-        amount = 15
-        (payment_proof, new_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(self.channel_state, self.cust_state, amount)
+        (payment_proof, new_cust_state) = bolt.pay_generate_payment_proof(self.channel_state, self.cust_state, 15)
 
-        bad_payment_proof = malformed_proof(payment_proof)
-        (new_close_token, self.merch_state) = self.bolt.bidirectional_pay_verify_payment_proof(self.channel_state, bad_payment_proof, self.merch_state)
-        self.assertTrue(new_close_token is None)
+        with self.assertRaises((ValueError, bolt.BoltError)):
+            bolt.pay_verify_payment_proof(self.channel_state, malformed_proof(payment_proof), self.merch_state, self.merch_ledger)
 
     def test_pay_protocol_bad_close_token_fail_handled(self):
         """
         Payment protocol fails as expected when merchant returns a malformed/bad close token
-        :return:
         """
-        amount = 10
-        (payment_proof, new_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(self.channel_state, self.cust_state, amount)
-
-        (new_close_token, self.merch_state) = self.bolt.bidirectional_pay_verify_payment_proof(self.channel_state, payment_proof, self.merch_state)
-        bad_close_token = malformed_token(new_close_token)
+        (payment_proof, new_cust_state) = bolt.pay_generate_payment_proof(self.channel_state, self.cust_state, 10)
+        new_close_token = bolt.pay_verify_payment_proof(self.channel_state, payment_proof, self.merch_state, self.merch_ledger)
 
-        (revoke_token, self.cust_state) = self.bolt.bidirectional_pay_generate_revoke_token(self.channel_state, self.cust_state, new_cust_state, bad_close_token)
-        self.assertTrue(revoke_token is None)
+        with self.assertRaises((ValueError, bolt.BoltError)):
+            bolt.pay_generate_revoke_token(self.channel_state, self.cust_state, new_cust_state, malformed_token(new_close_token))
+        # the customer keeps its current state
+        self.assertEqual(self.cust_state.cust_balance, 500)
 
     def test_pay_protocol_bad_revoke_token_fail_handled(self):
         """
         Payment protocol fails as expected when customer sends a bad revoke token
-        :return:
         """
-        amount = 20
-        (payment_proof, new_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(self.channel_state, self.cust_state, amount)
-
-        (new_close_token, self.merch_state) = self.bolt.bidirectional_pay_verify_payment_proof(self.channel_state, payment_proof, self.merch_state)
-
-        (revoke_token, self.cust_state) = self.bolt.bidirectional_pay_generate_revoke_token(self.channel_state, self.cust_state, new_cust_state, new_close_token)
+        (payment_proof, new_cust_state) = bolt.pay_generate_payment_proof(self.channel_state, self.cust_state, 20)
+        new_close_token = bolt.pay_verify_payment_proof(self.channel_state, payment_proof, self.merch_state, self.merch_ledger)
+        revoke_token = bolt.pay_generate_revoke_token(self.channel_state, self.cust_state, new_cust_state, new_close_token)
 
-        bad_revoke_token = malformed_token(revoke_token)
-        (pay_token, merch_state) = self.bolt.bidirectional_pay_verify_revoke_token(bad_revoke_token, self.merch_state)
-        self.assertTrue(pay_token is None)
+        with self.assertRaises((ValueError, bolt.BoltError)):
+            bolt.pay_verify_revoke_token(malformed_token(revoke_token), self.merch_ledger)
 
     def test_pay_protocol_bad_payment_token_fail_handled(self):
         """
         Payment protocol fails as expected when merchant returns a malformed pay token
-        :return:
         """
-        amount = 25
-        (payment_proof, new_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(self.channel_state, self.cust_state, amount)
+        (payment_proof, new_cust_state) = bolt.pay_generate_payment_proof(self.channel_state, self.cust_state, 25)
+        new_close_token = bolt.pay_verify_payment_proof(self.channel_state, payment_proof, self.merch_state, self.merch_ledger)
+        revoke_token = bolt.pay_generate_revoke_token(self.channel_state, self.cust_state, new_cust_state, new_close_token)
+        pay_token = bolt.pay_verify_revoke_token(revoke_token, self.merch_ledger)
 
-        (new_close_token, self.merch_state) = self.bolt.bidirectional_pay_verify_payment_proof(self.channel_state, payment_proof, self.merch_state)
+        with self.assertRaises(ValueError):
+            bolt.pay_verify_payment_token(self.channel_state, self.cust_state, malformed_token(pay_token))
 
-        (revoke_token, self.cust_state) = self.bolt.bidirectional_pay_generate_revoke_token(self.channel_state, self.cust_state, new_cust_state, new_close_token)
-
-        (pay_token, self.merch_state) = self.bolt.bidirectional_pay_verify_revoke_token(revoke_token, self.merch_state)
-        bad_pay_token = malformed_token(pay_token)
+class BoltCloseTests(unittest.TestCase):
+    def setUp(self):
+        self.channel_state = bolt.ChannelState("Test Channel")
+        (self.channel_token, self.merch_state, self.merch_ledger) = bolt.init_merchant(self.channel_state, "Bob")
+        self.cust_state = bolt.init_customer(self.channel_state, self.channel_token, 100, 10, "Alice")
 
-        (cust_state, is_pay_valid) = self.bolt.bidirectional_pay_verify_payment_token(self.channel_state, self.cust_state, bad_pay_token)
-        self.assertTrue(is_pay_valid is None)
+    def test_customer_close_requires_established_channel(self):
+        with self.assertRaises(bolt.BoltError):
+            bolt.customer_close(self.channel_state, self.cust_state)
 
-class BoltMultiChannelTests(unittest.TestCase):
-    def setUp(self):
+    def test_merchant_detects_revoked_close(self):
         """
-        Setup init customer/merchant state and establish phase of Bolt protocol
-        :return:
+        Merchant can dispute a customer close on a revoked state
         """
-        self.bolt = libbolt.Libbolt('target/{}/{}bolt.{}'.format(libbolt.mode, libbolt.prefix, libbolt.ext))
-        self.channel_state = self.bolt.channel_setup("Test Channel")
-        self.b0_alice = self.b0_charlie = 150
-        self.b0_merch = 5
-        (self.channel_token, self.merch_state, self.channel_state) = self.bolt.bidirectional_init_merchant(self.channel_state, "Bob")
-
-        (self.channel_token_a, self.alice_state) = self.bolt.bidirectional_init_customer(self.channel_token, self.b0_alice, self.b0_merch, "Alice")
-
-        (self.channel_token_c, self.charlie_state) = self.bolt.bidirectional_init_customer(self.channel_token, self.b0_charlie, self.b0_merch, "Charlie")
-
-    def _establish_channel(self, channel_token, channel_state, cust_state, pkc, b0_cust, b0_merch):
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(channel_token, cust_state)
+        establish_channel(self, self.channel_state, self.channel_token, self.cust_state, self.merch_state, self.merch_ledger)
+        old_cust_close = bolt.customer_close(self.channel_state, self.cust_state)
+        pay_on_channel(self, self.channel_state, self.cust_state, self.merch_state, self.merch_ledger, 10)
 
-        close_token = self.bolt.bidirectional_establish_merchant_issue_close_token(channel_state, com, com_proof, pkc, b0_cust, b0_merch, self.merch_state)
-        self.assertTrue(close_token is not None)
+        cust_close = bolt.customer_close(self.channel_state, self.cust_state)
+        with self.assertRaises(bolt.BoltError):
+            bolt.merchant_close(self.channel_state, self.channel_token, "merch-address", cust_close, self.merch_state, self.merch_ledger)
+        # the close is valid, so the channel is marked as closed in the ledger
+        merch_ledger = json.loads(self.merch_ledger.to_json())
+        self.assertTrue(all(channel["closed"] for channel in merch_ledger["channels"].values()))
 
-        (is_token_valid, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(channel_state, cust_state, close_token)
-        self.assertTrue(is_token_valid)
+        (wpk, merch_close) = bolt.merchant_close(self.channel_state, self.channel_token, "merch-address", old_cust_close, self.merch_state, self.merch_ledger)
+        self.assertTrue(bolt.wtp_verify_merch_close_message(self.channel_token, wpk, merch_close))
 
-        pay_token = self.bolt.bidirectional_establish_merchant_issue_pay_token(channel_state, com, self.merch_state)
-        self.assertTrue(pay_token is not None)
 
-        (is_channel_established, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_final(channel_state, cust_state, pay_token)
-        self.assertTrue(is_channel_established)
-
-        return channel_token, channel_state, cust_state
-
-    def _pay_on_channel(self, channel_state, cust_state, amount):
-        (payment_proof, new_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(channel_state, cust_state, amount)
-
-        (new_close_token, self.merch_state) = self.bolt.bidirectional_pay_verify_payment_proof(channel_state, payment_proof, self.merch_state)
-
-        (revoke_token, cust_state) = self.bolt.bidirectional_pay_generate_revoke_token(channel_state, cust_state, new_cust_state, new_close_token)
-
-        (pay_token, self.merch_state) = self.bolt.bidirectional_pay_verify_revoke_token(revoke_token, self.merch_state)
-
-        (cust_state, is_pay_valid) = self.bolt.bidirectional_pay_verify_payment_token(channel_state, cust_state, pay_token)
-        self.assertTrue(is_pay_valid)
+class BoltMultiChannelTests(unittest.TestCase):
+    def setUp(self):
+        """
+        Setup init customer/merchant state and establish phase of Bolt protocol
+        """
+        self.channel_state = bolt.ChannelState("Test Channel")
+        (channel_token, self.merch_state, self.merch_ledger) = bolt.init_merchant(self.channel_state, "Bob")
 
-        return channel_state, cust_state
+        self.channel_token_a = bolt.ChannelToken.from_json(channel_token.to_json())
+        self.alice_state = bolt.init_customer(self.channel_state, self.channel_token_a, 150, 5, "Alice")
 
+        self.channel_token_c = bolt.ChannelToken.from_json(channel_token.to_json())
+        self.charlie_state = bolt.init_customer(self.channel_state, self.channel_token_c, 150, 5, "Charlie")
 
     def test_multiple_channels_work(self):
         """Establishing concurrent channels with a merchant works as expected
         """
-        alice_cust_state_dict = json.loads(self.alice_state)
-        self.channel_token_a, self.channel_state_a, alice_cust_state = self._establish_channel(self.channel_token_a, self.channel_state,
-                                                                                               self.alice_state, alice_cust_state_dict["wallet"]["channelId"],
-                                                                                               self.b0_alice, self.b0_merch)
-
-        charlie_cust_state_dict = json.loads(self.charlie_state)
-        self.channel_token_c, self.channel_state_c, charlie_cust_state = self._establish_channel(self.channel_token_c, self.channel_state,
-                                                                                                 self.charlie_state, charlie_cust_state_dict["wallet"]["channelId"],
-                                                                                                 self.b0_charlie, self.b0_merch)
+        channel_state_a = bolt.ChannelState.from_json(self.channel_state.to_json())
+        establish_channel(self, channel_state_a, self.channel_token_a, self.alice_state, self.merch_state, self.merch_ledger)
 
-        self.channel_state_a, alice_cust_state = self._pay_on_channel(self.channel_state_a, alice_cust_state, 15)
-        #print("Alice cust state => ", alice_cust_state)
-        self.channel_state_c, charlie_cust_state = self._pay_on_channel(self.channel_state_c, charlie_cust_state, 10)
-        self.channel_state_c, charlie_cust_state = self._pay_on_channel(self.channel_state_c, charlie_cust_state, 20)
-        #print("Charlie cust state => ", charlie_cust_state)
-
-        alice_bal = json.loads(alice_cust_state)["cust_balance"]
-        charlie_bal = json.loads(charlie_cust_state)["cust_balance"]
-
-        self.assertTrue(alice_bal != charlie_bal)
+        channel_state_c = bolt.ChannelState.from_json(self.channel_state.to_json())
+        establish_channel(self, channel_state_c, self.channel_token_c, self.charlie_state, self.merch_state, self.merch_ledger)
 
+        pay_on_channel(self, channel_state_a, self.alice_state, self.merch_state, self.merch_ledger, 15)
+        pay_on_channel(self, channel_state_c, self.charlie_state, self.merch_state, self.merch_ledger, 10)
+        pay_on_channel(self, channel_state_c, self.charlie_state, self.merch_state, self.merch_ledger, 20)
 
+        self.assertEqual(self.alice_state.cust_balance, 135)
+        self.assertEqual(self.charlie_state.cust_balance, 120)
 
 class BoltIntermediaryTests(unittest.TestCase):
     def setUp(self):
         """
         Setup init alice/bob/intermediary state and establish phase of Bolt protocol
-        :return:
         """
-        self.bolt = libbolt.Libbolt('target/{}/{}bolt.{}'.format(libbolt.mode, libbolt.prefix, libbolt.ext))
-        self.channel_state = self.bolt.channel_setup("Test Channel")
-        self.b0_alice = self.b0_bob = 100
-        self.b0_intermediary = 100
-        (self.channel_token, self.merch_state, self.channel_state) = self.bolt.bidirectional_init_merchant(self.channel_state, "Hub")
-
-        (self.channel_token_a, self.alice_state) = self.bolt.bidirectional_init_customer(self.channel_token, self.b0_alice, self.b0_intermediary, "Alice")
-
-        (self.channel_token_c, self.bob_state) = self.bolt.bidirectional_init_customer(self.channel_token, self.b0_bob, self.b0_intermediary, "Bob")
+        self.channel_state = bolt.ChannelState("Test Channel", third_party=True)
+        (channel_token, self.merch_state, self.merch_ledger) = bolt.init_merchant(self.channel_state, "Hub")
 
+        self.channel_token_a = bolt.ChannelToken.from_json(channel_token.to_json())
+        self.alice_state = bolt.init_customer(self.channel_state, self.channel_token_a, 100, 100, "Alice")
 
-    def _establish_channel(self, channel_token, channel_state, cust_state, pkc, b0_cust, b0_merch):
-        (channel_token, cust_state, com, com_proof) = self.bolt.bidirectional_establish_customer_generate_proof(channel_token, cust_state)
-
-        close_token = self.bolt.bidirectional_establish_merchant_issue_close_token(channel_state, com, com_proof, pkc, b0_cust, b0_merch, self.merch_state)
-        self.assertTrue(close_token is not None)
-
-        (is_token_valid, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_verify_close_token(channel_state, cust_state, close_token)
-        self.assertTrue(is_token_valid)
-
-        pay_token = self.bolt.bidirectional_establish_merchant_issue_pay_token(channel_state, com, self.merch_state)
-        self.assertTrue(pay_token is not None)
-
-        (is_channel_established, channel_state, cust_state) = self.bolt.bidirectional_establish_customer_final(channel_state, cust_state, pay_token)
-        self.assertTrue(is_channel_established)
-
-        return channel_token, channel_state, cust_state
-
+        self.channel_token_b = bolt.ChannelToken.from_json(channel_token.to_json())
+        self.bob_state = bolt.init_customer(self.channel_state, self.channel_token_b, 100, 100, "Bob")
 
     def test_payment_with_intermediary_works(self):
         """Making a payment using an intermediary works
         """
-        alice_cust_state_dict = json.loads(self.alice_state)
-        self.channel_token_a, self.channel_state_a, alice_cust_state = self._establish_channel(self.channel_token_a, self.channel_state,
-                                                                                               self.alice_state, alice_cust_state_dict["wallet"]["channelId"],
-                                                                                               self.b0_alice, self.b0_intermediary)
-
-        bob_cust_state_dict = json.loads(self.bob_state)
-        self.channel_token_b, self.channel_state_c, bob_cust_state = self._establish_channel(self.channel_token_c, self.channel_state,
-                                                                                                 self.bob_state, bob_cust_state_dict["wallet"]["channelId"],
-                                                                                                 self.b0_bob, self.b0_intermediary)
+        establish_channel(self, bolt.ChannelState.from_json(self.channel_state.to_json()), self.channel_token_a, self.alice_state, self.merch_state, self.merch_ledger)
+        establish_channel(self, bolt.ChannelState.from_json(self.channel_state.to_json()), self.channel_token_b, self.bob_state, self.merch_state, self.merch_ledger)
 
         #A prepares payment A -> I
-        (payment_proof_a, new_alice_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(self.channel_state, alice_cust_state, 10)
+        (payment_proof_a, new_alice_state) = bolt.pay_generate_payment_proof(self.channel_state, self.alice_state, 10)
         #B prepares payment I -> B
-        (payment_proof_b, new_bob_cust_state) = self.bolt.bidirectional_pay_generate_payment_proof(self.channel_state, bob_cust_state, -10)
+        (payment_proof_b, new_bob_state) = bolt.pay_generate_payment_proof(self.channel_state, self.bob_state, -10)
+        #I rejects a payment proof that does not verify
+        bad_payment_proof_b = json.loads(payment_proof_b)
+        bad_payment_proof_b["com"] = json.loads(payment_proof_a)["com"]
+        with self.assertRaises(bolt.BoltError):
+            bolt.pay_verify_multiple_payment_proofs(self.channel_state, payment_proof_a, json.dumps(bad_payment_proof_b), self.merch_state, self.merch_ledger)
         #I verifies payment proofs
-        (new_close_token_a, cond_close_token_b, self.merch_state) = self.bolt.bidirectional_pay_verify_multiple_payment_proofs(self.channel_state, payment_proof_a, payment_proof_b, self.merch_state)
-        #A generates revoke token
-        (revoke_token_a, alice_cust_state) = self.bolt.bidirectional_pay_generate_revoke_token(self.channel_state, alice_cust_state, new_alice_cust_state, new_close_token_a)
-        #B generates revoke token
-        (revoke_token_b, bob_cust_state) = self.bolt.bidirectional_pay_generate_revoke_token(self.channel_state, bob_cust_state, new_bob_cust_state, cond_close_token_b)
+        (close_token_a, cond_close_token_b) = bolt.pay_verify_multiple_payment_proofs(self.channel_state, payment_proof_a, payment_proof_b, self.merch_state, self.merch_ledger)
+        #A and B generate revoke tokens
+        revoke_token_a = bolt.pay_generate_revoke_token(self.channel_state, self.alice_state, new_alice_state, close_token_a)
+        revoke_token_b = bolt.pay_generate_revoke_token(self.channel_state, self.bob_state, new_bob_state, cond_close_token_b)
         #I verifies both revoke tokens
-        (pay_token_a, pay_token_b, self.merch_state) = self.bolt.bidirectional_pay_verify_multiple_revoke_tokens(revoke_token_a, revoke_token_b, self.merch_state)
-        #A verifies payment token
-        (alice_cust_state, is_pay_valid_a) = self.bolt.bidirectional_pay_verify_payment_token(self.channel_state, alice_cust_state, pay_token_a)
-        self.assertTrue(is_pay_valid_a)
-        #B verifies payment token
-        (bob_cust_state, is_pay_valid_b) = self.bolt.bidirectional_pay_verify_payment_token(self.channel_state, bob_cust_state, pay_token_b)
-        self.assertTrue(is_pay_valid_b)
-
-        alice_bal = json.loads(alice_cust_state)["cust_balance"]
-        bob_bal = json.loads(bob_cust_state)["cust_balance"]
-        self.assertTrue(alice_bal == 90)
-        self.assertTrue(bob_bal == 110)
+        (pay_token_a, pay_token_b) = bolt.pay_verify_multiple_revoke_tokens(revoke_token_a, revoke_token_b, self.merch_ledger)
+        #A and B verify their payment tokens
+        self.assertTrue(bolt.pay_verify_payment_token(self.channel_state, self.alice_state, pay_token_a))
+        self.assertTrue(bolt.pay_verify_payment_token(self.channel_state, self.bob_state, pay_token_b))
+
+        self.assertEqual(self.alice_state.cust_balance, 90)
+        self.assertEqual(self.bob_state.cust_balance, 110)
 
 
 if __name__ == '__main__':
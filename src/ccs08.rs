@@ -7,11 +7,17 @@ Asiacrypt 2008
 
 use super::*;
 use rand::Rng;
-use cl::{Signature, PublicParams, setup, BlindKeyPair, ProofState, SignatureProof, BlindPublicKey};
+use cl::{Signature, PublicParams, SecretKey, setup_from_seed, BlindKeyPair, ProofState, SignatureProof, BlindPublicKey, PreparedBlindPublicKey};
 use ped92::{Commitment, CSMultiParams};
 use pairing::{Engine, CurveProjective};
 use ff::PrimeField;
-use std::collections::HashMap;
+use serde::de::{self, Deserializer, Visitor, SeqAccess, MapAccess};
+use serde::ser::{Serializer, SerializeSeq};
+use core::marker::PhantomData;
+use util::{Validate, validate_point, validate_vec};
+
+/// Public seed of the public params of the digit signatures (see cl::setup_from_seed)
+pub const RANGE_PROOF_PUBLIC_PARAMS_SEED: &'static [u8] = b"libbolt range proof public params";
 
 /**
 paramsUL contains elements generated by the verifier, which are necessary for the prover.
@@ -20,9 +26,12 @@ This must be computed in a trusted setup.
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, <E as pairing::Engine>::G1: serde::Serialize, <E as pairing::Engine>::G2: serde::Serialize"))]
 #[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>, <E as pairing::Engine>::G1: serde::Deserialize<'de>, <E as pairing::Engine>::G2: serde::Deserialize<'de>"))]
+#[serde(from = "ParamsULRepr<E>")]
 pub struct ParamsUL<E: Engine> {
     pub mpk: PublicParams<E>,
-    pub signatures: HashMap<String, Signature<E>>,
+    // signatures[i] is the signature on the digit i
+    #[serde(serialize_with = "serialize_signatures")]
+    pub signatures: Vec<Signature<E>>,
     pub csParams: CSMultiParams<E>,
     pk: BlindPublicKey<E>,
     // u determines the amount of signatures we need in the public params.
@@ -33,6 +42,103 @@ pub struct ParamsUL<E: Engine> {
     // verifier`s performance we want to minize it.
     // Namely, we have 2*l pairings for the prover and 3*l for the verifier.
     l: i64,
+    // prepared G2 elements of pk, not serialized but rebuilt on deserialization (see prepare())
+    #[serde(skip)]
+    preparedPk: Option<PreparedBlindPublicKey<E>>,
+}
+
+// ParamsUL as serialized, which is deserialized and then prepared
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>, <E as pairing::Engine>::G1: serde::Deserialize<'de>, <E as pairing::Engine>::G2: serde::Deserialize<'de>"))]
+struct ParamsULRepr<E: Engine> {
+    mpk: PublicParams<E>,
+    #[serde(deserialize_with = "deserialize_signatures")]
+    signatures: Vec<Signature<E>>,
+    csParams: CSMultiParams<E>,
+    pk: BlindPublicKey<E>,
+    u: i64,
+    l: i64,
+}
+
+impl<E: Engine> From<ParamsULRepr<E>> for ParamsUL<E> {
+    fn from(repr: ParamsULRepr<E>) -> Self {
+        let mut params = ParamsUL { mpk: repr.mpk, signatures: repr.signatures, csParams: repr.csParams, pk: repr.pk, u: repr.u, l: repr.l, preparedPk: None };
+        params.prepare();
+        params
+    }
+}
+
+// a digit signature, compressed (see serialize_signatures) or as a Signature, as serialized before
+#[derive(Deserialize)]
+#[serde(untagged)]
+#[serde(bound(deserialize = "Signature<E>: Deserialize<'de>"))]
+enum DigitSignature<E: Engine> {
+    Compressed(String),
+    Legacy(Signature<E>),
+}
+
+impl<E: Engine> DigitSignature<E> {
+    fn into_signature(self) -> Result<Signature<E>, String> {
+        match self {
+            DigitSignature::Compressed(ser) => {
+                let bytes = hex::decode(&ser).map_err(|err| format!("invalid digit signature: {}", err))?;
+                let (h, H) = bytes.split_at(bytes.len() / 2);
+                Ok(Signature { h: util::decompress_point(h)?, H: util::decompress_point(H)? })
+            },
+            DigitSignature::Legacy(signature) => Ok(signature)
+        }
+    }
+}
+
+// each digit signature is serialized as the hex encoding of compress(h) || compress(H) (see util::compress_point)
+fn serialize_signatures<S: Serializer, E: Engine>(signatures: &Vec<Signature<E>>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_seq(Some(signatures.len()))?;
+    for signature in signatures.iter() {
+        let mut bytes = util::compress_point(&signature.h);
+        bytes.extend(util::compress_point(&signature.H));
+        seq.serialize_element(&hex::encode(&bytes))?;
+    }
+    seq.end()
+}
+
+struct SignaturesVisitor<E: Engine>(PhantomData<E>);
+
+impl<'de, E: Engine> Visitor<'de> for SignaturesVisitor<E> where Signature<E>: Deserialize<'de> {
+    type Value = Vec<Signature<E>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of digit signatures")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut signatures = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(signature) = seq.next_element::<DigitSignature<E>>()? {
+            signatures.push(signature.into_signature().map_err(de::Error::custom)?);
+        }
+        Ok(signatures)
+    }
+
+    // params serialized before the signatures were indexed by digit are a map keyed by i.to_string()
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries: Vec<(usize, Signature<E>)> = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((digit, signature)) = map.next_entry::<String, DigitSignature<E>>()? {
+            let i = digit.parse::<usize>().map_err(|_| de::Error::custom(format!("invalid digit: {}", digit)))?;
+            entries.push((i, signature.into_signature().map_err(de::Error::custom)?));
+        }
+        entries.sort_by_key(|entry| entry.0);
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.0 != i {
+                return Err(de::Error::custom(format!("no unique signature for digit {}", i)));
+            }
+        }
+        Ok(entries.into_iter().map(|entry| entry.1).collect())
+    }
+}
+
+fn deserialize_signatures<'de, D: Deserializer<'de>, E: Engine>(deserializer: D) -> Result<Vec<Signature<E>>, D::Error>
+    where Signature<E>: Deserialize<'de>
+{
+    deserializer.deserialize_any(SignaturesVisitor(PhantomData))
 }
 
 /**
@@ -107,6 +213,22 @@ pub struct RangeProof<E: Engine> {
     pub p2: ProofUL<E>,
 }
 
+impl<E: Engine> Validate for ProofUL<E> {
+    fn validate(&self) -> Result<(), String> {
+        validate_vec(&self.V, "V")?;
+        validate_point(&self.D, "D")?;
+        self.comm.validate()?;
+        validate_vec(&self.sigProofs, "sigProofs")
+    }
+}
+
+impl<E: Engine> Validate for RangeProof<E> {
+    fn validate(&self) -> Result<(), String> {
+        self.p1.validate().map_err(|err| format!("p1: {}", err))?;
+        self.p2.validate().map_err(|err| format!("p2: {}", err))
+    }
+}
+
 /**
 params contains elements generated by the verifier, which are necessary for the prover.
 This must be computed in a trusted setup.
@@ -139,56 +261,147 @@ impl<E: Engine> SecretParamsUL<E> {
         order to get smaller parameters, at the cost of having worse performance.
     */
     pub fn setup_ul<R: Rng>(rng: &mut R, u: i64, l: i64, csParams: CSMultiParams<E>) -> Self {
-        let mpk = setup(rng);
+        let mpk = setup_from_seed(RANGE_PROOF_PUBLIC_PARAMS_SEED);
         let kp = BlindKeyPair::<E>::generate(rng, &mpk, 1);
 
-        let mut signatures: HashMap<String, Signature<E>> = HashMap::new();
+        let mut signatures: Vec<Signature<E>> = Vec::with_capacity(u as usize);
         for i in 0..u {
             let sig_i = kp.sign(rng, &vec! {E::Fr::from_str(i.to_string().as_str()).unwrap()});
-            signatures.insert(i.to_string(), sig_i);
+            signatures.push(sig_i);
         }
-        let pubParams = ParamsUL { mpk, signatures, csParams, pk: kp.public.clone(), u, l };
+        let mut pubParams = ParamsUL { mpk, signatures, csParams, pk: kp.public.clone(), u, l, preparedPk: None };
+        pubParams.prepare();
 
         SecretParamsUL{pubParams, kp}
     }
 
+    /**
+        from_secret restores the secret params from the public params and the signing key of the
+        digit signatures. Returns None if the key does not match the public params.
+    */
+    pub fn from_secret(pubParams: ParamsUL<E>, secret: SecretKey<E>) -> Option<Self> {
+        let public = BlindPublicKey::from_secret(&pubParams.mpk, &secret);
+        if public != pubParams.pk {
+            return None;
+        }
+        Some(SecretParamsUL { pubParams, kp: BlindKeyPair { secret, public } })
+    }
+
+    /**
+        prepare caches the fixed G2 elements of the signing key in the public parameters
+        (see ParamsUL::prepare).
+    */
+    pub fn prepare(&mut self) {
+        self.pubParams.prepare();
+    }
+
     /**
         verify_ul is used to validate the ZKRP proof. It returns true iff the proof is valid.
+        The verification only needs the public parameters (see ParamsUL::verify_ul).
     */
     pub fn verify_ul(&self, proof: &ProofUL<E>, ch: E::Fr, k: usize) -> bool {
-        let r1 = self.verify_part1(&proof, ch.clone(), k);
-        let r2 = self.verify_part2(&proof, ch.clone());
+        self.pubParams.verify_ul(proof, ch, k)
+    }
+}
+
+impl<E: Engine> ParamsUL<E> {
+    /**
+        validate checks parameters received from the verifier before proving with them: the
+        shape of the parameters and, with a single batched pairing check, that signatures[i]
+        is a valid signature on i for every digit i < u. Returns the first problem found.
+    */
+    pub fn validate<R: Rng>(&self, csprng: &mut R) -> Result<(), String> {
+        if self.u < 2 || self.l < 1 {
+            return Err(format!("invalid range proof parameters u = {}, l = {}", self.u, self.l));
+        }
+        if self.u.checked_pow(self.l as u32).is_none() || self.l > u32::max_value() as i64 {
+            return Err(String::from("u^l does not fit in an i64"));
+        }
+        if self.signatures.len() != self.u as usize {
+            return Err(format!("expected {} digit signatures, got {}", self.u, self.signatures.len()));
+        }
+        if self.pk.Y1.len() != 1 || self.pk.Y2.len() != 1 {
+            return Err(String::from("digit signing key must sign a single message"));
+        }
+        if self.csParams.pub_bases.is_empty() {
+            return Err(String::from("commitment parameters are empty"));
+        }
+        // signatures are unblinded, i.e. checked with a zero blinding factor
+        let messages: Vec<Vec<E::Fr>> = (0..self.u)
+            .map(|i| vec! {E::Fr::from_str(i.to_string().as_str()).unwrap(), E::Fr::zero()})
+            .collect();
+        if !self.pk.verify_batch(csprng, &self.mpk, &messages, &self.signatures) {
+            return Err(String::from("digit signatures do not verify"));
+        }
+        Ok(())
+    }
+
+    /**
+        prepare caches the fixed G2 elements of the signing key for the pairings in verify_ul.
+        The cache is not serialized, and is rebuilt when the params are deserialized.
+    */
+    pub fn prepare(&mut self) {
+        self.preparedPk = Some(self.pk.prepare(&self.mpk));
+    }
+
+    /**
+        verify_ul is used to validate the ZKRP proof. It returns true iff the proof is valid.
+        It only uses the public parameters, so anyone holding them (e.g., a watchtower or the
+        customer itself) can check a proof.
+    */
+    pub fn verify_ul(&self, proof: &ProofUL<E>, ch: E::Fr, k: usize) -> bool {
+        let (r1, r2) = util::join(|| self.verify_part1(&proof, ch.clone(), k),
+                                  || self.verify_part2(&proof, ch.clone()));
         r1 && r2
     }
 
     fn verify_part2(&self, proof: &ProofUL<E>, challenge: E::Fr) -> bool {
-        let mut r2 = true;
-        for i in 0..self.pubParams.l as usize {
-            let subResult = self.kp.public.verify_proof(&self.pubParams.mpk, proof.V[i].clone(), proof.sigProofs[i].clone(), challenge);
-
-            r2 = r2 && subResult;
+        let prepared;
+        let preparedPk = match self.preparedPk {
+            Some(ref pk) => pk,
+            None => {
+                prepared = self.pk.prepare(&self.mpk);
+                &prepared
+            }
+        };
+        let l = self.l as usize;
+        if proof.V.len() < l || proof.sigProofs.len() < l {
+            return false;
         }
-        r2
+        // each digit proof is checked independently
+        let digits: Vec<(&Signature<E>, &SignatureProof<E>)> = proof.V.iter().zip(proof.sigProofs.iter()).take(l).collect();
+        let results = util::par_map(&digits, |(V, sigProof)| preparedPk.verify_proof(V, sigProof, challenge));
+        results.iter().all(|r| *r)
     }
 
     fn verify_part1(&self, proof: &ProofUL<E>, challenge: E::Fr, k: usize) -> bool {
+        // reject truncated proofs (and a k outside the commitment) before indexing into them
+        let l = self.l as usize;
+        let bases = self.csParams.pub_bases.len();
+        if k == 0 || k >= bases || proof.sigProofs.len() < l || proof.zs.len() != bases - 2 {
+            return false;
+        }
+        if proof.sigProofs.iter().take(l).any(|sigProof| sigProof.zsig.len() != self.pk.Y1.len()) {
+            return false;
+        }
+
         let mut D = proof.comm.c.clone();
         D.mul_assign(challenge);
         D.negate();
-        let mut hzr = self.pubParams.csParams.pub_bases[0].clone();
+        let mut hzr = self.csParams.pub_bases[0].clone();
         hzr.mul_assign(proof.zr);
         D.add_assign(&hzr);
-        for i in 0..self.pubParams.l as usize {
-            let ui = self.pubParams.u.pow(i as u32);
-            let mut aux = self.pubParams.csParams.pub_bases[k].clone();
-            for j in 0..self.kp.public.Y1.len() {
+        for i in 0..l {
+            let ui = self.u.pow(i as u32);
+            let mut aux = self.csParams.pub_bases[k].clone();
+            for j in 0..self.pk.Y1.len() {
                 let mut muizsigi = proof.sigProofs[i].zsig[j];
                 muizsigi.mul_assign(&E::Fr::from_str(&ui.to_string()).unwrap());
                 aux.mul_assign(muizsigi);
             }
             D.add_assign(&aux);
         }
-        for i in 1..self.pubParams.csParams.pub_bases.len() {
+        for i in 1..self.csParams.pub_bases.len() {
             let j: usize;
             if i < k {
                 j = i - 1;
@@ -197,15 +410,12 @@ impl<E: Engine> SecretParamsUL<E> {
             } else {
                 continue;
             }
-            let mut g = self.pubParams.csParams.pub_bases[i].clone();
+            let mut g = self.csParams.pub_bases[i].clone();
             g.mul_assign(proof.zs[j].into_repr());
             D.add_assign(&g);
         }
         D == proof.D
     }
-}
-
-impl<E: Engine> ParamsUL<E> {
 
     /**
         prove_ul method is used to produce the ZKRP proof that secret x belongs to the interval [0,U^L).
@@ -223,6 +433,17 @@ impl<E: Engine> ParamsUL<E> {
         self.prove_ul_response(r, C, &proofUlState, c, k, otherM)
     }
 
+    /**
+        compute_challenge recomputes the Fiat-Shamir challenge of a proof produced by prove_ul.
+    */
+    pub fn compute_challenge(&self, proof: &ProofUL<E>) -> E::Fr {
+        let mut a = Vec::<E::Fqk>::with_capacity(self.l as usize);
+        for sigProof in proof.sigProofs.iter() {
+            a.push(sigProof.a);
+        }
+        hash::<E>(a, vec!(proof.D.clone()))
+    }
+
     pub fn prove_ul_commitment<R: Rng>(&self, rng: &mut R, x: i64, k: usize, sOptional: Option<Vec<E::Fr>>, mOptional: Option<E::Fr>) -> ProofULState<E> {
         if x > ((self.u as i128).pow(self.l as u32) - 1) as i64 || x < 0 {
             panic!("x is not within the range.");
@@ -240,7 +461,7 @@ impl<E: Engine> ParamsUL<E> {
         let mut hm = self.csParams.pub_bases[0].clone();
         hm.mul_assign(m);
         for i in 0..self.l as usize {
-            let signature = self.signatures.get(&decx[i].to_string()).unwrap();
+            let signature = &self.signatures[decx[i] as usize];
             let proofState = self.pk.prove_commitment(rng, &self.mpk, &signature, None, None);
 
             V.push(proofState.blindSig.clone());
@@ -323,7 +544,7 @@ fn hash<E: Engine>(a: Vec<E::Fqk>, D: Vec<E::G1>) -> E::Fr {
     }
     a_vec.extend(x_vec);
 
-    util::hash_to_fr::<E>(a_vec)
+    util::hash_to_challenge::<E>(a_vec)
 }
 
 /*
@@ -366,25 +587,48 @@ impl<E: Engine> RPSecretParams<E> {
     }
 
     /**
-        Verify is responsible for validating the range proof.
+        Verify is responsible for validating the range proof (see RPPublicParams::verify).
+    */
+    pub fn verify(&self, proof: RangeProof<E>, ch: E::Fr, k: usize) -> bool {
+        self.pubParams.verify(proof, ch, k)
+    }
+
+    pub fn compute_challenge(&self, proof: &RangeProof<E>) -> Option<E::Fr> {
+        self.pubParams.compute_challenge(proof)
+    }
+}
+
+impl<E: Engine> RPPublicParams<E> {
+    /**
+        Verify is responsible for validating the range proof. Only the public parameters are
+        needed, so any party can check a proof.
     */
     pub fn verify(&self, proof: RangeProof<E>, ch: E::Fr, k: usize) -> bool {
+        if proof.validate().is_err() {
+            return false;
+        }
         let first = self.p.verify_ul(&proof.p1, ch.clone(), k);
         let second = self.p.verify_ul(&proof.p2, ch.clone(), k);
         first & &second
     }
 
-    pub fn compute_challenge(&self, proof: &RangeProof<E>) -> E::Fr {
-        let mut a = Vec::<E::Fqk>::with_capacity(self.p.pubParams.l as usize);
-        for i in 0..proof.p1.sigProofs.len() {
+    /**
+        compute_challenge recomputes the Fiat-Shamir challenge of a proof produced by prove.
+        Returns None if either half of the proof does not carry one digit proof per digit.
+    */
+    pub fn compute_challenge(&self, proof: &RangeProof<E>) -> Option<E::Fr> {
+        let l = self.p.l as usize;
+        if proof.p1.sigProofs.len() != l || proof.p2.sigProofs.len() != l {
+            return None;
+        }
+        let mut a = Vec::<E::Fqk>::with_capacity(2 * l);
+        for i in 0..l {
             a.push(proof.p1.sigProofs[i].a);
             a.push(proof.p2.sigProofs[i].a);
         }
-        hash::<E>(a, vec!(proof.p1.D.clone(), proof.p2.D.clone()))
+        Some(hash::<E>(a, vec!(proof.p1.D.clone(), proof.p2.D.clone())))
     }
-}
 
-impl<E: Engine> RPPublicParams<E> {
     /**
         Prove method is responsible for generating the zero knowledge range proof.
     */
@@ -442,10 +686,8 @@ impl<E: Engine> RPPublicParams<E> {
 mod tests {
     use super::*;
     use pairing::bls12_381::{Bls12, G1, Fr};
-    use time::PreciseTime;
-    use std::ops::Add;
-    use core::mem;
     use rand::rngs::ThreadRng;
+    use cl::setup;
 
     #[test]
     fn setup_ul_works() {
@@ -454,11 +696,101 @@ mod tests {
 
         let secParams = SecretParamsUL::<Bls12>::setup_ul(rng, 2, 3, csParams.clone());
         assert_eq!(secParams.pubParams.signatures.len(), 2);
-        for (m, s) in secParams.pubParams.signatures {
-            assert_eq!(secParams.kp.public.verify_blind(&secParams.pubParams.mpk, &vec! {Fr::from_str(m.to_string().as_str()).unwrap()}, &Fr::zero(), &s), true);
+        for (m, s) in secParams.pubParams.signatures.iter().enumerate() {
+            assert_eq!(secParams.kp.public.verify_blind(&secParams.pubParams.mpk, &vec! {Fr::from_str(m.to_string().as_str()).unwrap()}, &Fr::zero(), s), true);
         }
     }
 
+    #[test]
+    fn validate_works() {
+        let rng = &mut rand::thread_rng();
+        let csParams = CSMultiParams::setup_gen_params(rng, 1);
+        let secParams = SecretParamsUL::<Bls12>::setup_ul(rng, 8, 3, csParams.clone());
+        assert_eq!(secParams.pubParams.validate(rng), Ok(()));
+
+        let mut swapped = secParams.pubParams.clone();
+        swapped.signatures.swap(2, 5);
+        assert!(swapped.validate(rng).is_err());
+
+        let mut missing = secParams.pubParams.clone();
+        missing.signatures.pop();
+        assert!(missing.validate(rng).is_err());
+
+        // signatures under a different key
+        let other = SecretParamsUL::<Bls12>::setup_ul(rng, 8, 3, csParams.clone());
+        let mut forged = secParams.pubParams.clone();
+        forged.signatures = other.pubParams.signatures.clone();
+        assert!(forged.validate(rng).is_err());
+
+        let mut overflow = secParams.pubParams.clone();
+        overflow.l = 64;
+        assert!(overflow.validate(rng).is_err());
+    }
+
+    #[test]
+    fn deserialize_legacy_signatures_works() {
+        let rng = &mut rand::thread_rng();
+        let csParams = CSMultiParams::setup_gen_params(rng, 1);
+        let secParams = SecretParamsUL::<Bls12>::setup_ul(rng, 4, 2, csParams.clone());
+        let ser = serde_json::to_string(&secParams.pubParams).unwrap();
+
+        // params serialized with the signatures in a map keyed by digit
+        let mut legacy: serde_json::Value = serde_json::from_str(&ser).unwrap();
+        let mut signatures = serde_json::Map::new();
+        for (i, s) in secParams.pubParams.signatures.iter().enumerate().rev() {
+            signatures.insert(i.to_string(), serde_json::to_value(s).unwrap());
+        }
+        legacy["signatures"] = serde_json::Value::Object(signatures.clone());
+        let pubParams: ParamsUL<Bls12> = serde_json::from_value(legacy.clone()).unwrap();
+        assert_eq!(serde_json::to_string(&pubParams).unwrap(), ser);
+
+        signatures.remove("2");
+        legacy["signatures"] = serde_json::Value::Object(signatures);
+        assert!(serde_json::from_value::<ParamsUL<Bls12>>(legacy.clone()).is_err());
+
+        // params serialized with uncompressed signatures in a sequence
+        let uncompressed: Vec<serde_json::Value> = secParams.pubParams.signatures.iter().map(|s| serde_json::to_value(s).unwrap()).collect();
+        legacy["signatures"] = serde_json::Value::Array(uncompressed);
+        let pubParams: ParamsUL<Bls12> = serde_json::from_value(legacy).unwrap();
+        assert_eq!(serde_json::to_string(&pubParams).unwrap(), ser);
+    }
+
+    #[test]
+    fn signatures_are_compressed() {
+        let rng = &mut rand::thread_rng();
+        let csParams = CSMultiParams::setup_gen_params(rng, 1);
+        let secParams = SecretParamsUL::<Bls12>::setup_ul(rng, 16, 2, csParams.clone());
+        let ser = serde_json::to_string(&secParams.pubParams).unwrap();
+
+        // each signature is 2 compressed G1 points, hex encoded
+        let value: serde_json::Value = serde_json::from_str(&ser).unwrap();
+        for ser_s in value["signatures"].as_array().unwrap() {
+            assert_eq!(ser_s.as_str().unwrap().len(), 2 * 2 * 48);
+        }
+        let pubParams: ParamsUL<Bls12> = serde_json::from_str(&ser).unwrap();
+        assert_eq!(pubParams.signatures, secParams.pubParams.signatures);
+
+        // smaller than the legacy map of uncompressed signatures
+        let mut legacy = value.clone();
+        let mut signatures = serde_json::Map::new();
+        for (i, s) in secParams.pubParams.signatures.iter().enumerate() {
+            signatures.insert(i.to_string(), serde_json::to_value(s).unwrap());
+        }
+        legacy["signatures"] = serde_json::Value::Object(signatures);
+        let ser_legacy = serde_json::to_string(&legacy).unwrap();
+        assert!(ser.len() < ser_legacy.len());
+        let legacyParams: ParamsUL<Bls12> = serde_json::from_str(&ser_legacy).unwrap();
+        assert_eq!(legacyParams.signatures, secParams.pubParams.signatures);
+
+        // invalid points are rejected
+        let mut bad = value.clone();
+        bad["signatures"][0] = serde_json::Value::String("00".repeat(96));
+        assert!(serde_json::from_value::<ParamsUL<Bls12>>(bad).is_err());
+        let mut truncated = value;
+        truncated["signatures"][0] = serde_json::Value::String("00".repeat(10));
+        assert!(serde_json::from_value::<ParamsUL<Bls12>>(truncated).is_err());
+    }
+
     #[test]
     fn prove_ul_works() {
         let rng = &mut rand::thread_rng();
@@ -495,7 +827,7 @@ mod tests {
         let C = csParams.commit(&vec!(modx), &fr.clone());
         let proof = secParams.pubParams.prove_ul(rng, 10, fr, C, 1, vec!{});
         let ch = compute_challenge(secParams.pubParams.clone(), &proof);
-        assert_eq!(secParams.verify_part1(&proof, ch, 1), true);
+        assert_eq!(secParams.pubParams.verify_part1(&proof, ch, 1), true);
     }
 
     #[test]
@@ -508,7 +840,7 @@ mod tests {
         let C = csParams.commit(&vec!(modx), &fr.clone());
         let proof = secParams.pubParams.prove_ul(rng, 10, fr, C, 1, vec!{});
         let ch = compute_challenge(secParams.pubParams.clone(), &proof);
-        assert_eq!(secParams.verify_part2(&proof, ch), true);
+        assert_eq!(secParams.pubParams.verify_part2(&proof, ch), true);
     }
 
     #[test]
@@ -539,6 +871,63 @@ mod tests {
         assert_eq!(secParams.verify_ul(&proof, ch, 2), true);
     }
 
+    #[test]
+    fn verify_ul_with_public_params_works() {
+        let rng = &mut rand::thread_rng();
+        let csParams = CSMultiParams::setup_gen_params(rng, 1);
+        let secParams = SecretParamsUL::<Bls12>::setup_ul(rng, 2, 4, csParams.clone());
+        let fr = Fr::rand(rng);
+        let modx = Fr::from_str(&(10.to_string())).unwrap();
+        let C = csParams.commit(&vec!(modx), &fr.clone());
+        let proof = secParams.pubParams.prove_ul(rng, 10, fr, C, 1, vec!{});
+
+        // a verifier only receives the serialized public params
+        let ser = serde_json::to_vec(&secParams.pubParams).unwrap();
+        let mut pubParams: ParamsUL<Bls12> = serde_json::from_slice(&ser).unwrap();
+        assert!(pubParams.preparedPk.is_some());
+        let ch = pubParams.compute_challenge(&proof);
+        assert_eq!(pubParams.verify_ul(&proof, ch, 1), true);
+        assert_eq!(pubParams.verify_ul(&proof, Fr::rand(rng), 1), false);
+
+        // and without the cache, the keys are prepared on every verification
+        pubParams.preparedPk = None;
+        assert_eq!(pubParams.verify_ul(&proof, ch, 1), true);
+    }
+
+    #[test]
+    fn verify_ul_rejects_truncated_proofs() {
+        let rng = &mut rand::thread_rng();
+        let csParams = CSMultiParams::setup_gen_params(rng, 3);
+        let secParams = SecretParamsUL::<Bls12>::setup_ul(rng, 2, 4, csParams.clone());
+        let fr = Fr::rand(rng);
+        let modx = Fr::from_str(&(10.to_string())).unwrap();
+        let fr1 = Fr::rand(rng);
+        let fr2 = Fr::rand(rng);
+        let C = csParams.commit(&vec!(fr1, modx, fr2), &fr.clone());
+        let proof = secParams.pubParams.prove_ul(rng, 10, fr, C, 2, vec!{fr1, fr2});
+        let ch = compute_challenge(secParams.pubParams.clone(), &proof);
+        assert_eq!(secParams.pubParams.verify_ul(&proof, ch, 2), true);
+
+        let mut truncated = proof.clone();
+        truncated.sigProofs.pop();
+        assert_eq!(secParams.pubParams.verify_ul(&truncated, ch, 2), false);
+
+        let mut truncated = proof.clone();
+        truncated.sigProofs[1].zsig.clear();
+        assert_eq!(secParams.pubParams.verify_ul(&truncated, ch, 2), false);
+
+        let mut truncated = proof.clone();
+        truncated.zs.pop();
+        assert_eq!(secParams.pubParams.verify_ul(&truncated, ch, 2), false);
+
+        let mut truncated = proof.clone();
+        truncated.V.truncate(1);
+        assert_eq!(secParams.pubParams.verify_ul(&truncated, ch, 2), false);
+
+        assert_eq!(secParams.pubParams.verify_ul(&proof, ch, 0), false);
+        assert_eq!(secParams.pubParams.verify_ul(&proof, ch, 4), false);
+    }
+
     #[test]
     fn prove_and_verify_works() {
         let rng = &mut rand::thread_rng();
@@ -548,7 +937,7 @@ mod tests {
         let modx = Fr::from_str(&(10.to_string())).unwrap();
         let C = csParams.commit(&vec!(modx), &fr.clone());
         let proof = secParams.pubParams.prove(rng, 10, C, fr, 1, vec!{});
-        let ch = secParams.compute_challenge(&proof);
+        let ch = secParams.compute_challenge(&proof).unwrap();
 
         assert_eq!(secParams.verify(proof, ch, 1), true);
     }
@@ -564,62 +953,94 @@ mod tests {
         let fr2 = Fr::rand(rng);
         let C = csParams.commit(&vec!(fr1, modx, fr2), &fr.clone());
         let proof = secParams.pubParams.prove(rng, 10, C, fr, 2, vec!{fr1, fr2});
-        let ch = secParams.compute_challenge(&proof);
+        let ch = secParams.compute_challenge(&proof).unwrap();
 
         assert_eq!(secParams.verify(proof, ch, 2), true);
     }
 
     #[test]
-    #[should_panic(expected = "x is not within the range")]
-    fn prove_not_in_range() {
+    fn verify_with_public_params_works() {
+        let rng = &mut rand::thread_rng();
+        let csParams = CSMultiParams::setup_gen_params(rng, 3);
+        let secParams = RPSecretParams::<Bls12>::setup(rng, 2, 25, csParams.clone());
+        let fr = Fr::rand(rng);
+        let modx = Fr::from_str(&(10.to_string())).unwrap();
+        let fr1 = Fr::rand(rng);
+        let fr2 = Fr::rand(rng);
+        let C = csParams.commit(&vec!(fr1, modx, fr2), &fr.clone());
+        let proof = secParams.pubParams.prove(rng, 10, C, fr, 2, vec!{fr1, fr2});
+
+        let ser = serde_json::to_vec(&secParams.pubParams).unwrap();
+        let pubParams: RPPublicParams<Bls12> = serde_json::from_slice(&ser).unwrap();
+        let ch = pubParams.compute_challenge(&proof).unwrap();
+        assert_eq!(ch, secParams.compute_challenge(&proof).unwrap());
+        assert_eq!(pubParams.verify(proof.clone(), ch, 2), true);
+        assert_eq!(pubParams.verify(proof, ch, 1), false);
+    }
+
+    #[test]
+    fn compute_challenge_rejects_truncated_proofs() {
         let rng = &mut rand::thread_rng();
         let csParams = CSMultiParams::setup_gen_params(rng, 1);
         let secParams = RPSecretParams::<Bls12>::setup(rng, 2, 25, csParams.clone());
         let fr = Fr::rand(rng);
-        let modx = Fr::from_str(&(26.to_string())).unwrap();
+        let modx = Fr::from_str(&(10.to_string())).unwrap();
         let C = csParams.commit(&vec!(modx), &fr.clone());
-        secParams.pubParams.prove(rng, 26, C, fr, 1, vec!{});
+        let proof = secParams.pubParams.prove(rng, 10, C, fr, 1, vec!{});
+        let ch = secParams.pubParams.compute_challenge(&proof).unwrap();
+
+        let mut truncated = proof.clone();
+        truncated.p2.sigProofs.pop();
+        assert!(secParams.pubParams.compute_challenge(&truncated).is_none());
+        assert_eq!(secParams.pubParams.verify(truncated, ch, 1), false);
+
+        let mut truncated = proof.clone();
+        truncated.p1.sigProofs.truncate(1);
+        assert!(secParams.pubParams.compute_challenge(&truncated).is_none());
+        assert_eq!(secParams.pubParams.verify(truncated, ch, 1), false);
+
+        let mut extended = proof.clone();
+        let extra = extended.p2.sigProofs[0].clone();
+        extended.p2.sigProofs.push(extra);
+        assert!(secParams.pubParams.compute_challenge(&extended).is_none());
+    }
+
+    #[test]
+    fn verify_rejects_invalid_points() {
+        let rng = &mut rand::thread_rng();
+        let csParams = CSMultiParams::setup_gen_params(rng, 3);
+        let secParams = RPSecretParams::<Bls12>::setup(rng, 2, 25, csParams.clone());
+        let fr = Fr::rand(rng);
+        let modx = Fr::from_str(&(10.to_string())).unwrap();
+        let fr1 = Fr::rand(rng);
+        let fr2 = Fr::rand(rng);
+        let C = csParams.commit(&vec!(fr1, modx, fr2), &fr.clone());
+        let proof = secParams.pubParams.prove(rng, 10, C, fr, 2, vec!{fr1, fr2});
+        let ch = secParams.compute_challenge(&proof).unwrap();
+        assert!(proof.validate().is_ok());
+        assert_eq!(secParams.verify(proof.clone(), ch, 2), true);
+
+        let mut bad_proof = proof.clone();
+        bad_proof.p2.V[0].h = G1::zero();
+        assert_eq!(bad_proof.validate().err().unwrap(), "p2: V[0]: signature h is the identity or not in the prime order subgroup");
+        assert_eq!(secParams.verify(bad_proof, ch, 2), false);
+
+        let mut bad_proof = proof.clone();
+        bad_proof.p1.comm.c = util::non_subgroup_g1();
+        assert_eq!(bad_proof.validate().err().unwrap(), "p1: commitment c is the identity or not in the prime order subgroup");
+        assert_eq!(secParams.verify(bad_proof, ch, 2), false);
     }
 
     #[test]
-    #[ignore]
-    fn prove_and_verify_performance() {
+    #[should_panic(expected = "x is not within the range")]
+    fn prove_not_in_range() {
         let rng = &mut rand::thread_rng();
-        let mut averageSetup = time::Duration::nanoseconds(0);
-        let mut averageSetupSize = 0;
-        let mut averageProve = time::Duration::nanoseconds(0);
-        let mut averageProofSize = 0;
-        let mut averageVerify = time::Duration::nanoseconds(0);
-        let iter = 5;
-        for _i in 0..iter {
-            let a = rng.gen_range(0, 1000000);
-            let b = rng.gen_range(a, 1000000);
-            let x = rng.gen_range(a, b);
-
-            let sSetup = PreciseTime::now();
-            let csParams = CSMultiParams::setup_gen_params(rng, 1);
-            let secParams = RPSecretParams::<Bls12>::setup(rng, a, b, csParams.clone());
-            averageSetup = averageSetup.add(sSetup.to(PreciseTime::now()));
-            averageSetupSize += mem::size_of_val(&secParams.pubParams);
-
-            let sProve = PreciseTime::now();
-            let fr = Fr::rand(rng);
-            let modx = Fr::from_str(&(x.to_string())).unwrap();
-            let C = csParams.commit(&vec!(modx), &fr.clone());
-            let proof = secParams.pubParams.prove(rng, x, C, fr, 1, vec!{});
-            averageProve = averageProve.add(sProve.to(PreciseTime::now()));
-            averageProofSize += mem::size_of_val(&proof);
-
-            let sVerify = PreciseTime::now();
-            let ch = secParams.compute_challenge(&proof);
-            secParams.verify(proof, ch, 1);
-            averageVerify = averageVerify.add(sVerify.to(PreciseTime::now()));
-        }
-        print!("Setup: {}\n", averageSetup.num_milliseconds() / iter);
-        print!("Setup size: {}\n", averageSetupSize / iter as usize);
-        print!("Prove: {}\n", averageProve.num_milliseconds() / iter);
-        print!("Proof size: {}\n", averageProofSize / iter as usize);
-        print!("Verify: {}\n", averageVerify.num_milliseconds() / iter);
+        let csParams = CSMultiParams::setup_gen_params(rng, 1);
+        let secParams = RPSecretParams::<Bls12>::setup(rng, 2, 25, csParams.clone());
+        let fr = Fr::rand(rng);
+        let modx = Fr::from_str(&(26.to_string())).unwrap();
+        let C = csParams.commit(&vec!(modx), &fr.clone());
+        secParams.pubParams.prove(rng, 26, C, fr, 1, vec!{});
     }
 
     #[test]
@@ -659,8 +1080,8 @@ mod tests {
         assert_eq!(public_params.p.signatures.len(), 57);
         assert_eq!(public_params.p.u, 57);
         assert_eq!(public_params.p.l, 1);
-        for (m, s) in public_params.p.signatures {
-            assert_eq!(secParams.p.kp.public.verify_blind(&public_params.p.mpk, &vec! {Fr::from_str(m.to_string().as_str()).unwrap()}, &Fr::zero(), &s), true);
+        for (m, s) in public_params.p.signatures.iter().enumerate() {
+            assert_eq!(secParams.p.kp.public.verify_blind(&public_params.p.mpk, &vec! {Fr::from_str(m.to_string().as_str()).unwrap()}, &Fr::zero(), s), true);
         }
     }
 
@@ -704,10 +1125,6 @@ mod tests {
     }
 
     fn compute_challenge<E: Engine>(pubParams: ParamsUL<E>, proof: &ProofUL<E>) -> E::Fr {
-        let mut a = Vec::<E::Fqk>::with_capacity(pubParams.l as usize);
-        for sigProof in proof.sigProofs.clone() {
-            a.push(sigProof.a);
-        }
-        hash::<E>(a, vec!(proof.D.clone()))
+        pubParams.compute_challenge(proof)
     }
 }
@@ -2,14 +2,16 @@ use super::*;
 use pairing::Engine;
 use cl::{BlindKeyPair, Signature};
 use ped92::{Commitment, CSMultiParams, CommitmentProof};
-use util::{hash_pubkey_to_fr, hash_to_fr, RevokedMessage, hash_to_slice};
+use util::{hash_pubkey_to_fr, hash_to_fr, hash_to_field, hash160, compress_point, RevokedMessage, hash_to_slice,
+           is_vec_g1_equal, Secret, SecretScalar, HASH_TO_FIELD_VERSION, DST_CHANNEL_ID};
+use zeroize::Zeroizing;
 use rand::Rng;
-use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 use nizk::{NIZKPublicParams, NIZKSecretParams, NIZKProof};
 use wallet::Wallet;
+#[cfg(feature = "std")]
 use std::error::Error;
-use std::fmt;
+use core::fmt;
 
 #[derive(Debug)]
 pub struct BoltError {
@@ -29,6 +31,7 @@ impl fmt::Display for BoltError {
     }
 }
 
+#[cfg(feature = "std")]
 impl Error for BoltError {
     fn description(&self) -> &str {
         &self.details
@@ -108,20 +111,130 @@ impl<E: Engine> ChannelToken<E> {
         return !self.pk_c.is_none();
     }
 
-    pub fn compute_channel_id(&self) -> E::Fr
-        where <E as pairing::Engine>::G1: serde::Serialize,
-              <E as pairing::Engine>::G2: serde::Serialize,
-              <E as ff::ScalarEngine>::Fr: serde::Serialize
-    {
+    ///
+    /// to_bytes - canonical encoding of the channel token:
+    /// pk_c || pk_m || X || Y_1 .. Y_l || mpk.g1 || mpk.g2 || comParams.pub_bases,
+    /// with the secp256k1 public keys (33 bytes) and the curve points in compressed form.
+    /// This is the encoding parsed by wtp_utils::reconstruct_channel_token.
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
         if self.pk_c.is_none() {
             panic!("pk_c is not initialized yet");
         }
-        let input = serde_json::to_vec(&self).unwrap();
+        let mut output = Vec::new();
+        output.extend_from_slice(&self.pk_c.unwrap().serialize());
+        output.extend_from_slice(&self.pk_m.serialize());
+        output.extend(compress_point(&self.cl_pk_m.X));
+        for y in self.cl_pk_m.Y.iter() {
+            output.extend(compress_point(y));
+        }
+        output.extend(compress_point(&self.mpk.g1));
+        output.extend(compress_point(&self.mpk.g2));
+        for g in self.comParams.pub_bases.iter() {
+            output.extend(compress_point(g));
+        }
+        return output;
+    }
+
+    ///
+    /// compute_channel_id - the channel id is the canonical encoding (see to_bytes) hashed
+    /// into Fr with the channel id domain separation tag
+    ///
+    pub fn compute_channel_id(&self) -> E::Fr {
+        return hash_to_field::<E>(HASH_TO_FIELD_VERSION, DST_CHANNEL_ID, &self.to_bytes());
+    }
+
+    ///
+    /// compute_channel_id_hash160 - HASH160 (RIPEMD160 of SHA256) of the canonical encoding,
+    /// for identifying the channel in a Bitcoin/Zcash script
+    ///
+    pub fn compute_channel_id_hash160(&self) -> [u8; 20] {
+        return hash160(&self.to_bytes());
+    }
+
+    ///
+    /// validate - checks the channel token sent by the merchant against the channel params:
+    /// the keys and commitment bases must be those of pub_params, with consistent lengths, and
+    /// made of valid points. Returns a description of the first inconsistency found.
+    ///
+    pub fn validate(&self, channel: &ChannelState<E>) -> Result<(), String> {
+        let cp = match channel.cp {
+            Some(ref cp) => cp,
+            None => return Err(String::from("channel params are not initialized"))
+        };
+        let pub_params = &cp.pub_params;
+
+        if !pub_params.verify_public_params() {
+            return Err(String::from("public params are not derived from the public seeds"));
+        }
+        if self.mpk != pub_params.mpk {
+            return Err(String::from("mpk does not match the channel params"));
+        }
+        if pub_params.pk.Y1.len() != cp.l || pub_params.pk.Y2.len() != cp.l {
+            return Err(format!("merchant public key has {} messages, expected {}", pub_params.pk.Y2.len(), cp.l));
+        }
+        if !pub_params.pk.is_well_formed(&pub_params.mpk) {
+            return Err(String::from("merchant public key is not well formed"));
+        }
+        if self.cl_pk_m.Y.len() != cp.l {
+            return Err(format!("cl_pk_m has {} messages, expected {}", self.cl_pk_m.Y.len(), cp.l));
+        }
+        if self.cl_pk_m != pub_params.pk.get_pub_key() {
+            return Err(String::from("cl_pk_m does not match the merchant public key in the channel params"));
+        }
+        if self.comParams.pub_bases.len() != cp.l + 1 {
+            return Err(format!("comParams has {} bases, expected {}", self.comParams.pub_bases.len(), cp.l + 1));
+        }
+        if self.comParams.pub_bases[0] != pub_params.mpk.g1 || !is_vec_g1_equal::<E>(&self.comParams.pub_bases[1..].to_vec(), &pub_params.pk.Y1) {
+            return Err(String::from("comParams are not derived from the merchant public key"));
+        }
+        if self.comParams != pub_params.comParams {
+            return Err(String::from("comParams do not match the channel params"));
+        }
+        Ok(())
+    }
+}
+
+///
+/// Channel token update - the channel token of a migrated channel, signed by the merchant (under pk_m)
+/// together with the channel token the channel was established with (see MerchantState::sign_channel_token_update)
+///
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, \
+<E as pairing::Engine>::G1: serde::Serialize, \
+<E as pairing::Engine>::G2: serde::Serialize"
+))]
+#[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>, \
+<E as pairing::Engine>::G1: serde::Deserialize<'de>, \
+<E as pairing::Engine>::G2: serde::Deserialize<'de>"
+))]
+pub struct ChannelTokenUpdate<E: Engine> {
+    pub channel_token: ChannelToken<E>,
+    pub signature: secp256k1::Signature,
+}
 
-        return hash_to_fr::<E>(input);
+impl<E: Engine> ChannelTokenUpdate<E> {
+    // signed message: "update" || old channel token || new channel token (see ChannelToken::to_bytes)
+    fn message(channel_token: &ChannelToken<E>, new_channel_token: &ChannelToken<E>) -> secp256k1::Message {
+        let mut msg = Vec::new();
+        msg.extend(b"update");
+        msg.extend(channel_token.to_bytes());
+        msg.extend(new_channel_token.to_bytes());
+        secp256k1::Message::from_slice(&hash_to_slice(&msg)).unwrap()
     }
 
-    // add a method to compute hash on chain: SHA256 + RIPEMD160?
+    ///
+    /// verify - checks that the update keeps the customer and merchant of the channel token the channel
+    /// was established with, and is signed under its pk_m
+    ///
+    pub fn verify(&self, channel_token: &ChannelToken<E>) -> bool {
+        if !channel_token.is_init() || self.channel_token.pk_c != channel_token.pk_c || self.channel_token.pk_m != channel_token.pk_m {
+            return false;
+        }
+        let secp = secp256k1::Secp256k1::verification_only();
+        let msg = ChannelTokenUpdate::message(channel_token, &self.channel_token);
+        secp.verify(&msg, &self.signature, &channel_token.pk_m).is_ok()
+    }
 }
 
 ///
@@ -163,37 +276,52 @@ impl<E: Engine> ChannelState<E> {
     }
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 struct WalletKeyPair {
     pub wpk: secp256k1::PublicKey,
-    pub wsk: secp256k1::SecretKey,
+    pub wsk: Secret<secp256k1::SecretKey>,
 }
 
 ///
-/// Customer state
+/// Secrets of the customer state, as returned by CustomerState::export_secrets
 ///
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize"))]
+#[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>"))]
+pub struct CustomerSecrets<E: Engine> {
+    pub sk_c: Secret<secp256k1::SecretKey>,
+    pub wsk: Secret<secp256k1::SecretKey>,
+    // wsk of the previous wallet, until it is revoked
+    pub old_wsk: Option<Secret<secp256k1::SecretKey>>,
+    pub t: SecretScalar<E::Fr>,
+}
+
+///
+/// Customer state - serializes without the secret keys and the commitment randomness.
+/// The state is persisted with export and restored with import.
+///
+#[derive(Clone, Serialize)]
 #[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, \
 <E as pairing::Engine>::G1: serde::Serialize, \
 <E as pairing::Engine>::G2: serde::Serialize"
 ))]
-#[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>, \
-<E as pairing::Engine>::G1: serde::Deserialize<'de>, \
-<E as pairing::Engine>::G2: serde::Deserialize<'de>"
-))]
 pub struct CustomerState<E: Engine> {
     pub name: String,
     pub pk_c: secp256k1::PublicKey,
-    sk_c: secp256k1::SecretKey,
+    #[serde(skip_serializing)]
+    sk_c: Secret<secp256k1::SecretKey>,
     pub cust_balance: i64,
     //
     pub merch_balance: i64,
     pub wpk: secp256k1::PublicKey,
     // keypair bound to the wallet
-    wsk: secp256k1::SecretKey,
+    #[serde(skip_serializing)]
+    wsk: Secret<secp256k1::SecretKey>,
+    #[serde(skip_serializing)]
     old_kp: Option<WalletKeyPair>,
     // old wallet key pair
-    t: E::Fr,
+    #[serde(skip_serializing)]
+    t: SecretScalar<E::Fr>,
     // randomness used to form the commitment
     wallet: Wallet<E>,
     // vector of field elements that represent wallet
@@ -204,6 +332,35 @@ pub struct CustomerState<E: Engine> {
     pay_tokens: HashMap<i32, Signature<E>>,
 }
 
+///
+/// Exported customer state, including the secrets (see CustomerState::export)
+///
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, \
+<E as pairing::Engine>::G1: serde::Serialize, \
+<E as pairing::Engine>::G2: serde::Serialize"
+))]
+#[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>, \
+<E as pairing::Engine>::G1: serde::Deserialize<'de>, \
+<E as pairing::Engine>::G2: serde::Deserialize<'de>"
+))]
+pub struct CustomerStateExport<E: Engine> {
+    name: String,
+    pk_c: secp256k1::PublicKey,
+    sk_c: Secret<secp256k1::SecretKey>,
+    cust_balance: i64,
+    merch_balance: i64,
+    wpk: secp256k1::PublicKey,
+    wsk: Secret<secp256k1::SecretKey>,
+    old_kp: Option<WalletKeyPair>,
+    t: SecretScalar<E::Fr>,
+    wallet: Wallet<E>,
+    w_com: Commitment<E>,
+    index: i32,
+    close_tokens: HashMap<i32, Signature<E>>,
+    pay_tokens: HashMap<i32, Signature<E>>,
+}
+
 impl<E: Engine> CustomerState<E> {
     pub fn new<R: Rng>(csprng: &mut R, channel_token: &mut ChannelToken<E>, cust_bal: i64, merch_bal: i64, name: String) -> Self
         where <E as pairing::Engine>::G1: serde::Serialize,
@@ -212,17 +369,17 @@ impl<E: Engine> CustomerState<E> {
     {
         let secp = secp256k1::Secp256k1::new();
 
-        let mut seckey = [0u8; 32];
-        csprng.fill_bytes(&mut seckey);
+        let mut seckey = Zeroizing::new([0u8; 32]);
+        csprng.fill_bytes(&mut seckey[..]);
 
         // generate the signing keypair for the channel        
-        let sk_c = secp256k1::SecretKey::from_slice(&seckey).unwrap();
+        let sk_c = Secret::new(secp256k1::SecretKey::from_slice(&seckey[..]).unwrap());
         let pk_c = secp256k1::PublicKey::from_secret_key(&secp, &sk_c);
         
-        let mut seckey1 = [0u8; 32];
-        csprng.fill_bytes(&mut seckey1);
+        let mut seckey1 = Zeroizing::new([0u8; 32]);
+        csprng.fill_bytes(&mut seckey1[..]);
         // generate the keypair for the initial wallet
-        let wsk = secp256k1::SecretKey::from_slice(&seckey1).unwrap();
+        let wsk = Secret::new(secp256k1::SecretKey::from_slice(&seckey1[..]).unwrap());
         let wpk = secp256k1::PublicKey::from_secret_key(&secp, &wsk);
         // hash the wallet pub key
         let wpk_h = hash_pubkey_to_fr::<E>(&wpk);
@@ -230,7 +387,7 @@ impl<E: Engine> CustomerState<E> {
         // compute the channel ID
         let channelId = channel_token.compute_channel_id();
         // randomness for commitment
-        let t = E::Fr::rand(csprng);
+        let t = SecretScalar::new(E::Fr::rand(csprng));
         // initialize wallet vector
         let wallet = Wallet { channelId: channelId, wpk: wpk_h, bc: cust_bal, bm: merch_bal, close: None };
 
@@ -337,15 +494,15 @@ impl<E: Engine> CustomerState<E> {
         return is_ct && is_pt;
     }
 
-    // for channel pay
-    pub fn generate_payment<R: Rng>(&self, csprng: &mut R, channel: &ChannelState<E>, amount: i64) -> (NIZKProof<E>, Commitment<E>, secp256k1::PublicKey, CustomerState<E>) {
+    // chooses a new wpk/wsk pair and commits to the wallet with the updated balances under comParams
+    fn next_state<R: Rng>(&self, csprng: &mut R, comParams: &CSMultiParams<E>, amount: i64) -> CustomerState<E> {
         // 1 - chooose new wpk/wsk pair
         let secp = secp256k1::Secp256k1::new();
 
-        let mut seckey = [0u8; 32];
-        csprng.fill_bytes(&mut seckey);
+        let mut seckey = Zeroizing::new([0u8; 32]);
+        csprng.fill_bytes(&mut seckey[..]);
         
-        let new_wsk = secp256k1::SecretKey::from_slice(&seckey).unwrap();
+        let new_wsk = Secret::new(secp256k1::SecretKey::from_slice(&seckey[..]).unwrap());
         let new_wpk = secp256k1::PublicKey::from_secret_key(&secp, &new_wsk);
         // compute hash of the pubkey
         let wpk_h = hash_pubkey_to_fr::<E>(&new_wpk);
@@ -353,23 +510,13 @@ impl<E: Engine> CustomerState<E> {
         // 2 - form new wallet and commitment
         let new_cust_bal = self.cust_balance - amount;
         let new_merch_bal = self.merch_balance + amount;
-        let new_t = E::Fr::rand(csprng);
+        let new_t = SecretScalar::new(E::Fr::rand(csprng));
 
-        let cp = channel.cp.as_ref().unwrap();
-        let old_wallet = Wallet { channelId: self.wallet.channelId.clone(), wpk: self.wallet.wpk.clone(), bc: self.cust_balance, bm: self.merch_balance, close: None };
         let new_wallet = Wallet { channelId: self.wallet.channelId.clone(), wpk: wpk_h, bc: new_cust_bal, bm: new_merch_bal, close: Some(self.wallet.close.unwrap()) };
-        let new_wcom = cp.pub_params.comParams.commit(&new_wallet.as_fr_vec(), &new_t);
-
-        // 3 - generate new blinded and randomized pay token
-        let i = self.index;
-        let prev_pay_token = self.pay_tokens.get(&i).unwrap();
-        //println!("Found prev pay token: {}", prev_pay_token);
-
-        let pay_proof = cp.pub_params.prove(csprng, old_wallet, new_wallet.clone(),
-                                            new_wcom.clone(), new_t, &prev_pay_token);
+        let new_wcom = comParams.commit(&new_wallet.as_fr_vec(), &new_t);
 
         // update internal state after proof has been verified by remote
-        let new_cw = CustomerState {
+        CustomerState {
             name: self.name.clone(),
             pk_c: self.pk_c.clone(),
             sk_c: self.sk_c.clone(),
@@ -379,14 +526,49 @@ impl<E: Engine> CustomerState<E> {
             wsk: new_wsk,
             old_kp: Some(WalletKeyPair { wpk: self.wpk.clone(), wsk: self.wsk.clone() }),
             t: new_t,
-            w_com: new_wcom.clone(),
-            wallet: new_wallet.clone(),
+            w_com: new_wcom,
+            wallet: new_wallet,
             index: self.index, // increment index here
             close_tokens: self.close_tokens.clone(),
             pay_tokens: self.pay_tokens.clone(),
-        };
+        }
+    }
 
-        return (pay_proof, new_wcom, self.wpk, new_cw);
+    // for channel pay
+    pub fn generate_payment<R: Rng>(&self, csprng: &mut R, channel: &ChannelState<E>, amount: i64) -> (NIZKProof<E>, Commitment<E>, secp256k1::PublicKey, CustomerState<E>) {
+        let cp = channel.cp.as_ref().unwrap();
+        let old_wallet = Wallet { channelId: self.wallet.channelId.clone(), wpk: self.wallet.wpk.clone(), bc: self.cust_balance, bm: self.merch_balance, close: None };
+        let new_cw = self.next_state(csprng, &cp.pub_params.comParams, amount);
+
+        // 3 - generate new blinded and randomized pay token
+        let i = self.index;
+        let prev_pay_token = self.pay_tokens.get(&i).unwrap();
+        //println!("Found prev pay token: {}", prev_pay_token);
+
+        let pay_proof = cp.pub_params.prove(csprng, old_wallet, new_cw.wallet.clone(),
+                                            new_cw.w_com.clone(), *new_cw.t, &prev_pay_token);
+
+        return (pay_proof, new_cw.w_com.clone(), self.wpk, new_cw);
+    }
+
+    ///
+    /// generate_migration - proves possession of a valid pay token under the merchant key of
+    /// old_channel and commits to the same balances under the commitment bases of new_channel,
+    /// after the merchant rotated its keys (see MerchantState::rotate_keys). The channel id is
+    /// revealed to the merchant. Returns the proof, the new wallet commitment, the old wpk and
+    /// the new customer state, to be completed as for a payment (see generate_revoke_token).
+    ///
+    pub fn generate_migration<R: Rng>(&self, csprng: &mut R, old_channel: &ChannelState<E>, new_channel: &ChannelState<E>) -> (NIZKProof<E>, Commitment<E>, secp256k1::PublicKey, CustomerState<E>) {
+        let old_cp = old_channel.cp.as_ref().unwrap();
+        let new_cp = new_channel.cp.as_ref().unwrap();
+        let old_wallet = Wallet { channelId: self.wallet.channelId.clone(), wpk: self.wallet.wpk.clone(), bc: self.cust_balance, bm: self.merch_balance, close: None };
+        let new_cw = self.next_state(csprng, &new_cp.pub_params.comParams, 0);
+
+        let prev_pay_token = self.pay_tokens.get(&self.index).unwrap();
+        let migration_proof = new_cp.pub_params.prove_migration(csprng, &old_cp.pub_params.pk, old_wallet, new_cw.wallet.clone(),
+                                                                 new_cw.w_com.clone(), *new_cw.t, &prev_pay_token);
+
+        return (migration_proof, new_cw.w_com.clone(), self.wpk, new_cw);
     }
 
     // update the internal state of the customer wallet
@@ -408,9 +590,68 @@ impl<E: Engine> CustomerState<E> {
         return true;
     }
 
+    ///
+    /// export_secrets - returns the secret keys and the commitment randomness of the customer.
+    /// These are redacted from Display and the serialized state and only leave the state through
+    /// this function (or export), e.g. to back them up or to hand them to a signing device.
+    ///
+    pub fn export_secrets(&self) -> CustomerSecrets<E> {
+        CustomerSecrets {
+            sk_c: self.sk_c.clone(),
+            wsk: self.wsk.clone(),
+            old_wsk: self.old_kp.as_ref().map(|kp| kp.wsk.clone()),
+            t: self.t.clone(),
+        }
+    }
+
+    ///
+    /// export - returns the full customer state, including the secrets, to persist it
+    /// (it should be stored encrypted). The state is restored with import.
+    ///
+    pub fn export(&self) -> CustomerStateExport<E> {
+        CustomerStateExport {
+            name: self.name.clone(),
+            pk_c: self.pk_c,
+            sk_c: self.sk_c.clone(),
+            cust_balance: self.cust_balance,
+            merch_balance: self.merch_balance,
+            wpk: self.wpk,
+            wsk: self.wsk.clone(),
+            old_kp: self.old_kp.clone(),
+            t: self.t.clone(),
+            wallet: self.wallet.clone(),
+            w_com: self.w_com.clone(),
+            index: self.index,
+            close_tokens: self.close_tokens.clone(),
+            pay_tokens: self.pay_tokens.clone(),
+        }
+    }
+
+    ///
+    /// import - restores a customer state from export
+    ///
+    pub fn import(state: CustomerStateExport<E>) -> Self {
+        CustomerState {
+            name: state.name,
+            pk_c: state.pk_c,
+            sk_c: state.sk_c,
+            cust_balance: state.cust_balance,
+            merch_balance: state.merch_balance,
+            wpk: state.wpk,
+            wsk: state.wsk,
+            old_kp: state.old_kp,
+            t: state.t,
+            wallet: state.wallet,
+            w_com: state.w_com,
+            index: state.index,
+            close_tokens: state.close_tokens,
+            pay_tokens: state.pay_tokens,
+        }
+    }
+
     pub fn generate_revoke_token(&mut self, channel: &ChannelState<E>, close_token: &Signature<E>) -> ResultBoltType<(RevokedMessage, secp256k1::Signature)> {
         if self.verify_close_token(channel, close_token) {
-            let old_wallet = self.old_kp.unwrap();
+            let old_wallet = self.old_kp.as_ref().unwrap();
             // proceed with generating the close token
             let secp = secp256k1::Secp256k1::new();
             let rm = RevokedMessage::new(String::from("revoked"), old_wallet.wpk);
@@ -433,8 +674,7 @@ impl<E: Engine> fmt::Display for CustomerState<E> {
         content = format!("{}cust-bal = {}\n", content, &self.cust_balance);
         content = format!("{}merch-bal = {}\n", content, &self.merch_balance);
         content = format!("{}wpk = {}\nwsk = {}\n", content, &self.wpk, &self.wsk);
-        if (!self.old_kp.is_none()) {
-            let old_kp = self.old_kp.unwrap();
+        if let Some(ref old_kp) = self.old_kp {
             content = format!("{}revoked: wpk = {}\nrevoked: wsk = {}\n", content, &old_kp.wpk, &old_kp.wsk);
         }
         content = format!("{}t = {}\n", content, &self.t);
@@ -460,7 +700,9 @@ pub struct ChannelcloseM {
 }
 
 ///
-/// Merchant State
+/// Secrets of the merchant state, as returned by MerchantState::export_secrets. These are the
+/// long-lived signing keys of the merchant and should be stored encrypted. Together with the
+/// channel state, they restore the merchant state (see MerchantState::from_secrets).
 ///
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, \
@@ -471,17 +713,75 @@ pub struct ChannelcloseM {
 <E as pairing::Engine>::G1: serde::Deserialize<'de>, \
 <E as pairing::Engine>::G2: serde::Deserialize<'de>"
 ))]
+pub struct MerchantSecrets<E: Engine> {
+    pub id: String,
+    pub sk: Secret<secp256k1::SecretKey>,
+    // signing key for the close and pay tokens
+    pub cl_sk: cl::SecretKey<E>,
+    // signing key for the digit signatures of the range proofs
+    pub rp_sk: cl::SecretKey<E>,
+    pub epoch: u32,
+    // signing keys replaced by rotate_keys, until retire_keys
+    pub old_cl_sk: Option<cl::SecretKey<E>>,
+    pub old_rp_sk: Option<cl::SecretKey<E>>,
+    // public params of the replaced keys, which the channel state no longer holds
+    pub old_pub_params: Option<NIZKPublicParams<E>>,
+}
+
+///
+/// Merchant State - the signing keys and public params of the merchant, whereas the per-channel
+/// state lives in the MerchantLedger. Serializes without the signing keys; the state is persisted
+/// with export_secrets and restored with from_secrets.
+///
+#[derive(Clone, Serialize)]
+#[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, \
+<E as pairing::Engine>::G1: serde::Serialize, \
+<E as pairing::Engine>::G2: serde::Serialize"
+))]
 pub struct MerchantState<E: Engine> {
     id: String,
+    #[serde(skip_serializing)]
     keypair: cl::BlindKeyPair<E>,
+    #[serde(skip_serializing)]
     nizkParams: NIZKSecretParams<E>,
     pk: secp256k1::PublicKey,
     // pk_m
-    sk: secp256k1::SecretKey,
+    #[serde(skip_serializing)]
+    sk: Secret<secp256k1::SecretKey>,
     // sk_m
     comParams: CSMultiParams<E>,
+    // incremented by rotate_keys
+    epoch: u32,
+    // keys replaced by rotate_keys, until retire_keys
+    #[serde(skip_serializing)]
+    oldNizkParams: Option<NIZKSecretParams<E>>,
+}
+
+///
+/// Channel Record - the initial balances the merchant agreed to for an established channel
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelRecord {
+    pub init_cust_balance: i64,
+    pub init_merch_balance: i64,
+    // epoch of the merchant keys the channel's tokens are issued under
+    #[serde(default)]
+    pub key_epoch: u32,
+    #[serde(default)]
+    pub closed: bool,
+}
+
+///
+/// Merchant Ledger - the revoked wallet keys, pay tokens and channel records of the merchant.
+/// Contains no secret keys and can be persisted and replicated independently of the MerchantState.
+///
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "<E as pairing::Engine>::G1: serde::Serialize"))]
+#[serde(bound(deserialize = "<E as pairing::Engine>::G1: serde::Deserialize<'de>"))]
+pub struct MerchantLedger<E: Engine> {
     pub keys: HashMap<String, PubKeyMap>,
     pub pay_tokens: HashMap<String, cl::Signature<E>>,
+    pub channels: HashMap<String, ChannelRecord>,
 }
 
 impl<E: Engine> MerchantState<E> {
@@ -491,9 +791,9 @@ impl<E: Engine> MerchantState<E> {
         let secp = secp256k1::Secp256k1::new();
         // tx_kp.randomize(csprng);
         // let (wsk, wpk) = tx_kp.generate_keypair(csprng);
-        let mut seckey = [0u8; 32];
-        csprng.fill_bytes(&mut seckey);
-        let wsk = secp256k1::SecretKey::from_slice(&seckey).unwrap();
+        let mut seckey = Zeroizing::new([0u8; 32]);
+        csprng.fill_bytes(&mut seckey[..]);
+        let wsk = Secret::new(secp256k1::SecretKey::from_slice(&seckey[..]).unwrap());
         let wpk = secp256k1::PublicKey::from_secret_key(&secp, &wsk);
 
         let mut ch = channel.clone();
@@ -507,11 +807,105 @@ impl<E: Engine> MerchantState<E> {
             pk: wpk,
             sk: wsk,
             comParams: nizkParams.pubParams.comParams.clone(),
-            keys: HashMap::new(), // store wpks/revoke_tokens
-            pay_tokens: HashMap::new(),
+            epoch: 0,
+            oldNizkParams: None,
         }, ch)
     }
 
+    ///
+    /// rotate_keys - generates a new CL key pair (and the commitment bases derived from it) for the merchant
+    /// and returns the channel state with the new public params. The new channel token is obtained with init.
+    /// The pk_m and the replaced keys are kept, so that the customers of the existing channels can migrate
+    /// their wallets (see verify_migration), until retire_keys. Only one rotation can be in progress.
+    ///
+    pub fn rotate_keys<R: Rng>(&mut self, csprng: &mut R, channel: &ChannelState<E>) -> ResultBoltType<ChannelState<E>> {
+        if self.oldNizkParams.is_some() {
+            return Err(BoltError::new("rotate_keys - the previous keys are not retired yet"));
+        }
+        let l = match channel.cp {
+            Some(ref cp) => cp.l,
+            None => return Err(BoltError::new("rotate_keys - channel params are not initialized"))
+        };
+
+        let mut ch = channel.clone();
+        let nizkParams = NIZKSecretParams::<E>::setup(csprng, l);
+        ch.cp = Some(ChannelParams::<E> { pub_params: nizkParams.pubParams.clone(), l, extra_verify: true });
+
+        self.keypair = nizkParams.keypair.clone();
+        self.comParams = nizkParams.pubParams.comParams.clone();
+        self.oldNizkParams = Some(core::mem::replace(&mut self.nizkParams, nizkParams));
+        self.epoch += 1;
+        Ok(ch)
+    }
+
+    ///
+    /// retire_keys - drops the keys replaced by rotate_keys, once every channel of the ledger has
+    /// migrated to the current keys or is closed. Pay tokens under the retired keys are no longer accepted.
+    ///
+    pub fn retire_keys(&mut self, ledger: &MerchantLedger<E>) -> ResultBoltType<()> {
+        if self.oldNizkParams.is_none() {
+            return Err(BoltError::new("retire_keys - no key rotation in progress"));
+        }
+        let pending = ledger.channels.values().filter(|record| record.key_epoch < self.epoch && !record.closed).count();
+        if pending > 0 {
+            return Err(BoltError::new(&format!("retire_keys - {} channels have not migrated or closed", pending)));
+        }
+        self.oldNizkParams = None;
+        Ok(())
+    }
+
+    ///
+    /// export_secrets - returns the secret keys of the merchant (see CustomerState::export_secrets),
+    /// from which the state is restored with from_secrets
+    ///
+    pub fn export_secrets(&self) -> MerchantSecrets<E> {
+        MerchantSecrets {
+            id: self.id.clone(),
+            sk: self.sk.clone(),
+            cl_sk: self.keypair.secret.clone(),
+            rp_sk: self.nizkParams.rpParams.kp.secret.clone(),
+            epoch: self.epoch,
+            old_cl_sk: self.oldNizkParams.as_ref().map(|params| params.keypair.secret.clone()),
+            old_rp_sk: self.oldNizkParams.as_ref().map(|params| params.rpParams.kp.secret.clone()),
+            old_pub_params: self.oldNizkParams.as_ref().map(|params| params.pubParams.clone()),
+        }
+    }
+
+    ///
+    /// from_secrets - restores a merchant state from its secrets (see export_secrets) and the public
+    /// params of the channel state. Fails if the keys do not match the public params.
+    ///
+    pub fn from_secrets(secrets: MerchantSecrets<E>, channel: &ChannelState<E>) -> ResultBoltType<Self> {
+        let cp = match channel.cp {
+            Some(ref cp) => cp,
+            None => return Err(BoltError::new("from_secrets - channel params are not initialized"))
+        };
+        let nizkParams = match NIZKSecretParams::from_secrets(cp.pub_params.clone(), secrets.cl_sk, secrets.rp_sk) {
+            Some(params) => params,
+            None => return Err(BoltError::new("from_secrets - keys do not match the channel params"))
+        };
+        let oldNizkParams = match (secrets.old_pub_params, secrets.old_cl_sk, secrets.old_rp_sk) {
+            (Some(pub_params), Some(cl_sk), Some(rp_sk)) => match NIZKSecretParams::from_secrets(pub_params, cl_sk, rp_sk) {
+                Some(params) => Some(params),
+                None => return Err(BoltError::new("from_secrets - replaced keys do not match their params"))
+            },
+            (None, None, None) => None,
+            _ => return Err(BoltError::new("from_secrets - replaced keys are incomplete"))
+        };
+
+        let secp = secp256k1::Secp256k1::new();
+        Ok(MerchantState {
+            id: secrets.id,
+            keypair: nizkParams.keypair.clone(),
+            pk: secp256k1::PublicKey::from_secret_key(&secp, &secrets.sk),
+            sk: secrets.sk,
+            comParams: nizkParams.pubParams.comParams.clone(),
+            nizkParams,
+            epoch: secrets.epoch,
+            oldNizkParams,
+        })
+    }
+
     pub fn init(&mut self, channel: &mut ChannelState<E>) -> ChannelToken<E> {
         let cp = channel.cp.as_ref().unwrap(); // if not set, then panic!
         let mpk = cp.pub_params.mpk.clone();
@@ -548,53 +942,119 @@ impl<E: Engine> MerchantState<E> {
         return self.keypair.sign_blind(csprng, &cp.pub_params.mpk, pay_com);
     }
 
-    pub fn verify_proof<R: Rng>(&self, csprng: &mut R, channel: &ChannelState<E>, com: &Commitment<E>, com_proof: &CommitmentProof<E>, channelId: &E::Fr, cust_balance: i64, merch_balance: i64) -> ResultBoltType<(Signature<E>, Signature<E>)> {
+    pub fn verify_proof<R: Rng>(&self, csprng: &mut R, channel: &ChannelState<E>, ledger: &mut MerchantLedger<E>, com: &Commitment<E>, com_proof: &CommitmentProof<E>, channelId: &E::Fr, cust_balance: i64, merch_balance: i64) -> ResultBoltType<(Signature<E>, Signature<E>)> {
         let is_valid = nizk::verify_opening(&self.comParams, &com.c, &com_proof, &channelId, cust_balance, merch_balance);
         let cp = channel.cp.as_ref().unwrap();
         if is_valid {
             let close_token = self.issue_close_token(csprng, cp, com, true);
             let pay_token = self.issue_pay_token(csprng, cp, com, false);
+            ledger.store_channel(channelId, cust_balance, merch_balance, self.epoch);
             return Ok((close_token, pay_token));
         }
         Err(BoltError::new("verify_proof - Failed to verify PoK of commitment opening"))
     }
 
-    fn store_wpk_with_token(&mut self, wpk: &secp256k1::PublicKey, pay_token: Signature<E>) {
-        // compute fingerprint on wpk
-        let wpk_str = util::compute_pub_key_fingerprint(&wpk);
-        self.pay_tokens.insert(wpk_str, pay_token);
-    }
-
-    fn get_pay_token(&self, wpk: &secp256k1::PublicKey) -> Signature<E> {
-        let wpk_str = util::compute_pub_key_fingerprint(&wpk);
-        return self.pay_tokens.get(&wpk_str).unwrap().clone();
-    }
-
-    pub fn verify_payment<R: Rng>(&mut self, csprng: &mut R, channel: &ChannelState<E>, proof: &NIZKProof<E>, com: &Commitment<E>, wpk: &secp256k1::PublicKey, amount: i64) -> ResultBoltType<Signature<E>> {
+    pub fn verify_payment<R: Rng>(&self, csprng: &mut R, channel: &ChannelState<E>, ledger: &mut MerchantLedger<E>, proof: &NIZKProof<E>, com: &Commitment<E>, wpk: &secp256k1::PublicKey, amount: i64) -> ResultBoltType<Signature<E>> {
         let cp = channel.cp.as_ref().unwrap();
         let pay_proof = proof.clone();
         let prev_wpk = hash_pubkey_to_fr::<E>(&wpk);
         let epsilon = util::convert_int_to_fr::<E>(amount);
 
         if self.nizkParams.verify(pay_proof, epsilon, com, prev_wpk) {
-            // 1 - proceed with generating close and pay token
-            let close_token = self.issue_close_token(csprng, cp, com, false);
-            let pay_token = self.issue_pay_token(csprng, cp, com, true);
-            // let's store the pay token with the wpk for now
-            self.store_wpk_with_token(wpk, pay_token);
-            return Ok(close_token);
+            return Ok(self.issue_payment_tokens(csprng, cp, ledger, com, wpk));
         }
         Err(BoltError::new("verify_payment - Failed to validate NIZK PoK for payment."))
     }
 
-    pub fn verify_revoke_token(&self, revoke_token: &secp256k1::Signature, revoke_msg: &RevokedMessage, wpk: &secp256k1::PublicKey) -> ResultBoltType<Signature<E>> {
-        let secp = secp256k1::Secp256k1::new();
-        let msg = secp256k1::Message::from_slice(&revoke_msg.hash_to_slice()).unwrap();
-        // verify that the revocation token is valid
-        if secp.verify(&msg, revoke_token, wpk).is_ok() {
-            return Ok(self.get_pay_token(wpk));
+    /// Verifies many independent payments, given as (proof, com, wpk, amount). The NIZK proofs are
+    /// checked concurrently if the `parallel` feature is enabled, then the tokens for the valid payments
+    /// are issued in order. Returns a close token or an error for each payment.
+    pub fn verify_payments<R: Rng>(&self, csprng: &mut R, channel: &ChannelState<E>, ledger: &mut MerchantLedger<E>, payments: &Vec<(NIZKProof<E>, Commitment<E>, secp256k1::PublicKey, i64)>) -> Vec<ResultBoltType<Signature<E>>> {
+        let cp = channel.cp.as_ref().unwrap();
+        let valid = {
+            let nizkParams = &self.nizkParams;
+            util::par_map(payments, |(proof, com, wpk, amount)| {
+                let prev_wpk = hash_pubkey_to_fr::<E>(&wpk);
+                let epsilon = util::convert_int_to_fr::<E>(*amount);
+                nizkParams.verify(proof.clone(), epsilon, com, prev_wpk)
+            })
+        };
+
+        let mut close_tokens = Vec::with_capacity(payments.len());
+        for ((_, com, wpk, _), is_valid) in payments.iter().zip(valid.into_iter()) {
+            if is_valid {
+                close_tokens.push(Ok(self.issue_payment_tokens(csprng, cp, ledger, com, wpk)));
+            } else {
+                close_tokens.push(Err(BoltError::new("verify_payments - Failed to validate NIZK PoK for payment.")));
+            }
         }
-        Err(BoltError::new("verify_revoke_token - Failed to verify the revoke token for wpk!"))
+        close_tokens
+    }
+
+    ///
+    /// verify_migration - verifies the proof of a customer that migrates the wallet of an established
+    /// channel from the keys replaced by rotate_keys (see CustomerState::generate_migration). The channel
+    /// must be recorded in the ledger under a previous epoch and not closed, and the wpk not revoked.
+    /// If valid, the channel is moved to the current epoch and a close token is returned, after which
+    /// the migration completes as a payment (with the revoke token of wpk).
+    ///
+    pub fn verify_migration<R: Rng>(&self, csprng: &mut R, channel: &ChannelState<E>, ledger: &mut MerchantLedger<E>, proof: &NIZKProof<E>, com: &Commitment<E>, wpk: &secp256k1::PublicKey, channelId: &E::Fr) -> ResultBoltType<Signature<E>> {
+        let oldNizkParams = match self.oldNizkParams {
+            Some(ref params) => params,
+            None => return Err(BoltError::new("verify_migration - no key rotation in progress"))
+        };
+        match ledger.get_channel(channelId) {
+            Some(record) if record.closed => return Err(BoltError::new("verify_migration - channel is closed")),
+            Some(record) if record.key_epoch == self.epoch => return Err(BoltError::new("verify_migration - channel already migrated")),
+            Some(_) => {},
+            None => return Err(BoltError::new("verify_migration - unknown channel"))
+        }
+        if ledger.keys.contains_key(&util::compute_pub_key_fingerprint(wpk)) {
+            return Err(BoltError::new("verify_migration - wpk has been revoked"));
+        }
+
+        let cp = channel.cp.as_ref().unwrap();
+        let prev_wpk = hash_pubkey_to_fr::<E>(&wpk);
+        if self.nizkParams.verify_migration(oldNizkParams, proof.clone(), com, prev_wpk, channelId.clone()) {
+            ledger.set_channel_epoch(channelId, self.epoch);
+            return Ok(self.issue_payment_tokens(csprng, cp, ledger, com, wpk));
+        }
+        Err(BoltError::new("verify_migration - Failed to validate NIZK PoK for migration."))
+    }
+
+    // generates the close and pay token for a verified payment and stores the pay token with the wpk
+    fn issue_payment_tokens<R: Rng>(&self, csprng: &mut R, cp: &ChannelParams<E>, ledger: &mut MerchantLedger<E>, com: &Commitment<E>, wpk: &secp256k1::PublicKey) -> Signature<E> {
+        let close_token = self.issue_close_token(csprng, cp, com, false);
+        let pay_token = self.issue_pay_token(csprng, cp, com, true);
+        // let's store the pay token with the wpk for now
+        ledger.store_wpk_with_token(wpk, pay_token);
+        close_token
+    }
+
+    ///
+    /// sign_channel_token_update - signs the channel token of a migrated channel under pk_m, so that the close
+    /// tokens under the current keys can be verified against the channel token the channel was established with
+    /// (see ChannelTokenUpdate::verify). The new channel token must be that of the current keys.
+    ///
+    pub fn sign_channel_token_update(&self, channel: &ChannelState<E>, ledger: &MerchantLedger<E>, channel_token: &ChannelToken<E>, new_channel_token: &ChannelToken<E>) -> ResultBoltType<ChannelTokenUpdate<E>> {
+        if !channel_token.is_init() || new_channel_token.pk_c != channel_token.pk_c {
+            return Err(BoltError::new("sign_channel_token_update - customer public key does not match"));
+        }
+        if new_channel_token.validate(channel).is_err() || new_channel_token.pk_m != self.pk ||
+            new_channel_token.cl_pk_m != self.keypair.get_public_key(&new_channel_token.mpk) {
+            return Err(BoltError::new("sign_channel_token_update - new channel token is not that of the current keys"));
+        }
+        match ledger.get_channel(&channel_token.compute_channel_id()) {
+            Some(record) if record.closed => return Err(BoltError::new("sign_channel_token_update - channel is closed")),
+            Some(record) if record.key_epoch == self.epoch => {},
+            Some(_) => return Err(BoltError::new("sign_channel_token_update - channel has not migrated")),
+            None => return Err(BoltError::new("sign_channel_token_update - unknown channel"))
+        }
+
+        let secp = secp256k1::Secp256k1::signing_only();
+        let msg = ChannelTokenUpdate::message(channel_token, new_channel_token);
+        let signature = secp.sign(&msg, &self.sk);
+        Ok(ChannelTokenUpdate { channel_token: new_channel_token.clone(), signature })
     }
 
     pub fn sign_revoke_message(&self, address: String, revoke_token: &Option<secp256k1::Signature>) -> ChannelcloseM {
@@ -611,6 +1071,65 @@ impl<E: Engine> MerchantState<E> {
     }
 }
 
+impl<E: Engine> MerchantLedger<E> {
+    pub fn new() -> Self {
+        MerchantLedger {
+            keys: HashMap::new(), // store wpks/revoke_tokens
+            pay_tokens: HashMap::new(),
+            channels: HashMap::new(),
+        }
+    }
+
+    fn store_channel(&mut self, channelId: &E::Fr, init_cust_balance: i64, init_merch_balance: i64, key_epoch: u32) {
+        self.channels.insert(format!("{}", channelId), ChannelRecord { init_cust_balance, init_merch_balance, key_epoch, closed: false });
+    }
+
+    pub fn get_channel(&self, channelId: &E::Fr) -> Option<&ChannelRecord> {
+        self.channels.get(&format!("{}", channelId))
+    }
+
+    fn set_channel_epoch(&mut self, channelId: &E::Fr, key_epoch: u32) {
+        if let Some(record) = self.channels.get_mut(&format!("{}", channelId)) {
+            record.key_epoch = key_epoch;
+        }
+    }
+
+    ///
+    /// close_channel - marks the channel as closed, so that it does not prevent retiring the keys
+    /// it was established with (see MerchantState::retire_keys). Returns false for an unknown channel.
+    ///
+    pub fn close_channel(&mut self, channelId: &E::Fr) -> bool {
+        match self.channels.get_mut(&format!("{}", channelId)) {
+            Some(record) => {
+                record.closed = true;
+                true
+            },
+            None => false
+        }
+    }
+
+    fn store_wpk_with_token(&mut self, wpk: &secp256k1::PublicKey, pay_token: Signature<E>) {
+        // compute fingerprint on wpk
+        let wpk_str = util::compute_pub_key_fingerprint(&wpk);
+        self.pay_tokens.insert(wpk_str, pay_token);
+    }
+
+    fn get_pay_token(&self, wpk: &secp256k1::PublicKey) -> Signature<E> {
+        let wpk_str = util::compute_pub_key_fingerprint(&wpk);
+        return self.pay_tokens.get(&wpk_str).unwrap().clone();
+    }
+
+    pub fn verify_revoke_token(&self, revoke_token: &secp256k1::Signature, revoke_msg: &RevokedMessage, wpk: &secp256k1::PublicKey) -> ResultBoltType<Signature<E>> {
+        let secp = secp256k1::Secp256k1::new();
+        let msg = secp256k1::Message::from_slice(&revoke_msg.hash_to_slice()).unwrap();
+        // verify that the revocation token is valid
+        if secp.verify(&msg, revoke_token, wpk).is_ok() {
+            return Ok(self.get_pay_token(wpk));
+        }
+        Err(BoltError::new("verify_revoke_token - Failed to verify the revoke token for wpk!"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -628,6 +1147,7 @@ mod tests {
         // in order to derive the channel tokens
         // initialize on the merchant side with balance: b0_merch
         let (mut merch_state, mut channel) = MerchantState::<Bls12>::new(rng, &mut channel, String::from("Merchant B"));
+        let mut merch_ledger = MerchantLedger::<Bls12>::new();
 
         // initialize the merchant wallet with the balance
         let mut channel_token = merch_state.init(&mut channel);
@@ -643,7 +1163,7 @@ mod tests {
         // then send the pay-token after confirmation
         let channelId = channel_token.compute_channel_id();
         assert_eq!(channelId, cust_state.get_wallet().channelId);
-        let (close_token, pay_token) = merch_state.verify_proof(rng, &channel, &cust_state.w_com, &cust_com_proof, &channelId, b0_cust, b0_merch).unwrap();
+        let (close_token, pay_token) = merch_state.verify_proof(rng, &channel, &mut merch_ledger, &cust_state.w_com, &cust_com_proof, &channelId, b0_cust, b0_merch).unwrap();
         // unblind tokens and verify signatures
         assert!(cust_state.verify_close_token(&channel, &close_token));
 
@@ -656,7 +1176,7 @@ mod tests {
         let (pay_proof, new_com, old_wpk, new_cw) = cust_state.generate_payment(rng, &channel, amount);
 
         // new pay_token is not sent until revoke_token is obtained from the customer
-        let new_close_token = merch_state.verify_payment(rng, &channel, &pay_proof, &new_com, &old_wpk, amount).unwrap();
+        let new_close_token = merch_state.verify_payment(rng, &channel, &mut merch_ledger, &pay_proof, &new_com, &old_wpk, amount).unwrap();
 
         //println!("1 -  Updated close Token : {}", new_close_token);
         // unblind tokens and verify signatures
@@ -674,9 +1194,16 @@ mod tests {
 
         //println!("5 - Revoke token => {}", revoke_token);
 
-        let new_pay_token = merch_state.verify_revoke_token(&revoke_sig, &revoke_msg, &old_wpk).unwrap();
+        let new_pay_token = merch_ledger.verify_revoke_token(&revoke_sig, &revoke_msg, &old_wpk).unwrap();
         assert!(cust_state.verify_pay_token(&channel, &new_pay_token));
 
+        // the ledger records the channel and holds no merchant secrets
+        assert_eq!(merch_ledger.get_channel(&channelId), Some(&ChannelRecord { init_cust_balance: b0_cust, init_merch_balance: b0_merch, key_epoch: 0, closed: false }));
+        let ser_ledger = serde_json::to_string(&merch_ledger).unwrap();
+        assert!(!ser_ledger.contains(&serde_json::to_string(&*merch_state.sk).unwrap()));
+        let merch_ledger2: MerchantLedger<Bls12> = serde_json::from_str(&ser_ledger).unwrap();
+        assert_eq!(merch_ledger2.pay_tokens.len(), 1);
+
         //println!("Validated revoke token!");
     }
 
@@ -695,6 +1222,131 @@ mod tests {
         let _channelId = channel_token.compute_channel_id();
     }
 
+    #[test]
+    fn channel_token_validate_works() {
+        let mut channel = ChannelState::<Bls12>::new(String::from("Channel A <-> B"), false);
+        let rng = &mut rand::thread_rng();
+        let (mut merch_state, mut channel) = MerchantState::<Bls12>::new(rng, &mut channel, String::from("Merchant B"));
+        let channel_token = merch_state.init(&mut channel);
+        assert!(channel_token.validate(&channel).is_ok());
+
+        let uninit_channel = ChannelState::<Bls12>::new(String::from("Channel A <-> B"), false);
+        assert_eq!(channel_token.validate(&uninit_channel).err().unwrap(), "channel params are not initialized");
+
+        let mut token = channel_token.clone();
+        token.mpk = cl::setup(rng);
+        assert_eq!(token.validate(&channel).err().unwrap(), "mpk does not match the channel params");
+
+        let mut token = channel_token.clone();
+        token.cl_pk_m.Y.pop();
+        assert_eq!(token.validate(&channel).err().unwrap(), "cl_pk_m has 4 messages, expected 5");
+
+        let mut token = channel_token.clone();
+        token.cl_pk_m.X = token.cl_pk_m.Y[0];
+        assert_eq!(token.validate(&channel).err().unwrap(), "cl_pk_m does not match the merchant public key in the channel params");
+
+        let mut token = channel_token.clone();
+        token.comParams.pub_bases.push(token.mpk.g1);
+        assert_eq!(token.validate(&channel).err().unwrap(), "comParams has 7 bases, expected 6");
+
+        let mut token = channel_token.clone();
+        token.comParams.pub_bases.swap(1, 2);
+        assert_eq!(token.validate(&channel).err().unwrap(), "comParams are not derived from the merchant public key");
+
+        let mut bad_channel = channel.clone();
+        bad_channel.cp.as_mut().unwrap().pub_params.pk.Y1[0] = channel_token.mpk.g1;
+        assert_eq!(channel_token.validate(&bad_channel).err().unwrap(), "merchant public key is not well formed");
+
+        let mut bad_channel = channel.clone();
+        bad_channel.cp.as_mut().unwrap().pub_params.mpk = cl::setup(rng);
+        assert_eq!(channel_token.validate(&bad_channel).err().unwrap(), "public params are not derived from the public seeds");
+    }
+
+    #[test]
+    fn secrets_are_redacted_and_exported() {
+        let mut channel = ChannelState::<Bls12>::new(String::from("Channel A <-> B"), false);
+        let rng = &mut rand::thread_rng();
+        let (mut merch_state, mut channel) = MerchantState::<Bls12>::new(rng, &mut channel, String::from("Merchant B"));
+        let mut channel_token = merch_state.init(&mut channel);
+        let cust_state = CustomerState::<Bls12>::new(rng, &mut channel_token, 100, 20, String::from("Alice"));
+
+        let secrets = cust_state.export_secrets();
+        let secp = secp256k1::Secp256k1::new();
+        assert_eq!(secp256k1::PublicKey::from_secret_key(&secp, &secrets.sk_c), cust_state.pk_c);
+        assert_eq!(secp256k1::PublicKey::from_secret_key(&secp, &secrets.wsk), cust_state.wpk);
+        assert!(secrets.old_wsk.is_none());
+
+        let display = format!("{}", cust_state);
+        assert!(display.contains("sk = <redacted>"));
+        assert!(display.contains("wsk = <redacted>"));
+        assert!(display.contains("t = <redacted>"));
+        assert!(!display.contains(&format!("{}", *secrets.sk_c)));
+        assert!(!display.contains(&format!("{}", *secrets.wsk)));
+
+        // the serialized state omits the secrets, which only leave the state through an export
+        let ser_secrets = serde_json::to_string(&secrets).unwrap();
+        let ser_state = serde_json::to_string(&cust_state).unwrap();
+        assert!(!ser_state.contains(&serde_json::to_string(&*secrets.sk_c).unwrap()));
+        assert!(!ser_state.contains(&serde_json::to_string(&*secrets.wsk).unwrap()));
+        assert!(!ser_state.contains("\"t\""));
+        let secrets2: CustomerSecrets<Bls12> = serde_json::from_str(&ser_secrets).unwrap();
+        assert!(secrets2.t == secrets.t);
+
+        let ser_export = serde_json::to_string(&cust_state.export()).unwrap();
+        assert!(ser_export.contains(&serde_json::to_string(&*secrets.wsk).unwrap()));
+        let cust_state2 = CustomerState::<Bls12>::import(serde_json::from_str(&ser_export).unwrap());
+        assert!(cust_state2.export_secrets().t == secrets.t);
+        assert_eq!(cust_state2.wpk, cust_state.wpk);
+        // a serialized state cannot be restored without its secrets
+        assert!(serde_json::from_str::<CustomerStateExport<Bls12>>(&ser_state).is_err());
+
+        let merch_secrets = merch_state.export_secrets();
+        let mpk = &channel.cp.as_ref().unwrap().pub_params.mpk;
+        assert!(cl::BlindPublicKey::from_secret(mpk, &merch_secrets.cl_sk) == channel.cp.as_ref().unwrap().pub_params.pk);
+        assert_eq!(secp256k1::PublicKey::from_secret_key(&secp, &merch_secrets.sk), channel_token.pk_m);
+        assert!(merch_secrets.old_cl_sk.is_none());
+
+        assert!(merch_secrets.old_rp_sk.is_none());
+
+        let ser_state = serde_json::to_string(&merch_state).unwrap();
+        assert!(!ser_state.contains(&serde_json::to_string(&*merch_secrets.sk).unwrap()));
+        assert!(!ser_state.contains(&serde_json::to_string(&merch_secrets.cl_sk).unwrap()));
+        assert!(!ser_state.contains(&serde_json::to_string(&merch_secrets.rp_sk).unwrap()));
+
+        // the secrets and the channel state restore the merchant state
+        let ser_secrets = serde_json::to_string(&merch_secrets).unwrap();
+        let merch_state2 = MerchantState::<Bls12>::from_secrets(serde_json::from_str(&ser_secrets).unwrap(), &channel).unwrap();
+        assert_eq!(merch_state2.pk, merch_state.pk);
+        assert!(merch_state2.nizkParams.rpParams.kp.public == merch_state.nizkParams.rpParams.kp.public);
+        assert_eq!(serde_json::to_string(&merch_state2).unwrap(), ser_state);
+
+        // the replaced signing keys are exported until they are retired
+        let new_channel = merch_state.rotate_keys(rng, &channel).unwrap();
+        let merch_secrets = merch_state.export_secrets();
+        let new_mpk = &new_channel.cp.as_ref().unwrap().pub_params.mpk;
+        assert!(cl::BlindPublicKey::from_secret(new_mpk, &merch_secrets.cl_sk) == new_channel.cp.as_ref().unwrap().pub_params.pk);
+        assert!(cl::BlindPublicKey::from_secret(mpk, merch_secrets.old_cl_sk.as_ref().unwrap()) == channel.cp.as_ref().unwrap().pub_params.pk);
+        assert!(merch_secrets.old_rp_sk.is_some());
+        let merch_state3 = MerchantState::<Bls12>::from_secrets(merch_secrets.clone(), &new_channel).unwrap();
+        assert_eq!(merch_state3.epoch, 1);
+        assert!(merch_state3.oldNizkParams.unwrap().pubParams.pk == channel.cp.as_ref().unwrap().pub_params.pk);
+
+        // incomplete replaced keys, or the params of another channel, do not restore a merchant state
+        let mut other_merch_secrets = merch_secrets.clone();
+        other_merch_secrets.old_pub_params = None;
+        assert!(MerchantState::<Bls12>::from_secrets(other_merch_secrets, &new_channel).is_err());
+        assert!(MerchantState::<Bls12>::from_secrets(merch_secrets, &channel).is_err());
+    }
+
+    #[test]
+    fn ledger_without_key_epochs_deserializes() {
+        // channel records persisted before key rotation have neither a key epoch nor a closed flag
+        let ser_ledger = "{\"keys\":{},\"pay_tokens\":{},\"channels\":{\"42\":{\"init_cust_balance\":100,\"init_merch_balance\":20}}}";
+        let ledger: MerchantLedger<Bls12> = serde_json::from_str(ser_ledger).unwrap();
+        let record = ledger.channels.get("42").unwrap();
+        assert_eq!(*record, ChannelRecord { init_cust_balance: 100, init_merch_balance: 20, key_epoch: 0, closed: false });
+    }
+
     #[test]
     fn channel_util_works_with_Bn256() {
         let mut channel = ChannelState::<Bn256>::new(String::from("Channel A <-> B"), false);
@@ -706,6 +1358,7 @@ mod tests {
         // in order to derive the channel tokens
         // initialize on the merchant side with balance: b0_merch
         let (mut merch_state, mut channel) = MerchantState::<Bn256>::new(rng, &mut channel, String::from("Merchant B"));
+        let mut merch_ledger = MerchantLedger::<Bn256>::new();
 
         // initialize the merchant wallet with the balance
         let mut channel_token = merch_state.init(&mut channel);
@@ -721,7 +1374,7 @@ mod tests {
         // then send the pay-token after confirmation
         let channelId = channel_token.compute_channel_id();
         assert_eq!(channelId, cust_state.get_wallet().channelId);
-        let (close_token, pay_token) = merch_state.verify_proof(rng, &channel, &cust_state.w_com, &cust_com_proof, &channelId, b0_cust, b0_merch).unwrap();
+        let (close_token, pay_token) = merch_state.verify_proof(rng, &channel, &mut merch_ledger, &cust_state.w_com, &cust_com_proof, &channelId, b0_cust, b0_merch).unwrap();
         // unblind tokens and verify signatures
         assert!(cust_state.verify_close_token(&channel, &close_token));
 
@@ -732,7 +1385,7 @@ mod tests {
         let (pay_proof, new_com, old_wpk, new_cw) = cust_state.generate_payment(rng, &channel, amount);
 
         // new pay_token is not sent until revoke_token is obtained from the customer
-        let new_close_token = merch_state.verify_payment(rng, &channel, &pay_proof, &new_com, &old_wpk, amount).unwrap();
+        let new_close_token = merch_state.verify_payment(rng, &channel, &mut merch_ledger, &pay_proof, &new_com, &old_wpk, amount).unwrap();
 
         //println!("1 -  Updated close Token : {}", new_close_token);
         // unblind tokens and verify signatures
@@ -750,7 +1403,7 @@ mod tests {
 
         //println!("5 - Revoke token => {}", revoke_token);
 
-        let new_pay_token = merch_state.verify_revoke_token(&revoke_sig, &revoke_msg, &old_wpk).unwrap();
+        let new_pay_token = merch_ledger.verify_revoke_token(&revoke_sig, &revoke_msg, &old_wpk).unwrap();
         assert!(cust_state.verify_pay_token(&channel, &new_pay_token));
 
         //println!("Validated revoke token!");
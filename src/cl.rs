@@ -1,12 +1,13 @@
 // cl.rs
 // CL Sigs - Pointcheval Sanders ('06)
 use super::*;
-use pairing::{CurveProjective, Engine};
+use pairing::{CurveAffine, CurveProjective, Engine};
 use ff::PrimeField;
 use rand::Rng;
 use ped92::{Commitment, CSMultiParams};
 use serde::{Serialize, Deserialize};
 use util;
+use util::{Validate, validate_point};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(bound(deserialize = "<E as pairing::Engine>::G1: serde::Deserialize<'de>, <E as pairing::Engine>::G2: serde::Deserialize<'de>"))]
@@ -32,24 +33,39 @@ impl<E: Engine> PublicParams<E> {
 
         PublicParams { g1, g2 }
     }
+
+    /// verify_seed - recomputes the public params from a public seed (see setup_from_seed) and
+    /// checks that they match, so anyone can check that nobody knows the discrete logs of g1, g2
+    pub fn verify_seed(&self, seed: &[u8]) -> bool {
+        *self == setup_from_seed::<E>(seed)
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+/// SecretKey is zeroized when dropped and redacted by Display and Debug
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SecretKey<E: Engine> {
     pub x: E::Fr,
     pub y: Vec<E::Fr>,
 }
 
+impl<E: Engine> Drop for SecretKey<E> {
+    fn drop(&mut self) {
+        util::zeroize_scalar(&mut self.x);
+        for y in self.y.iter_mut() {
+            util::zeroize_scalar(y);
+        }
+    }
+}
+
 impl<E: Engine> fmt::Display for SecretKey<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut y_str = String::new();
-        let mut i = 0;
-        for y in self.y.iter() {
-            y_str = format!("{}\n{} => {}", y_str, i, y);
-            i += 1;
-        }
+        write!(f, "SK : (x=<redacted>, y=<{} redacted>)", self.y.len())
+    }
+}
 
-        write!(f, "SK : \nx={},\ny=[{}\n]", self.x, y_str)
+impl<E: Engine> fmt::Debug for SecretKey<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKey {{ x: <redacted>, y: <{} redacted> }}", self.y.len())
     }
 }
 
@@ -160,6 +176,46 @@ impl<E: Engine> Signature<E> {
     }
 }
 
+impl<E: Engine> Validate for Signature<E> {
+    fn validate(&self) -> Result<(), String> {
+        validate_point(&self.h, "signature h")?;
+        validate_point(&self.H, "signature H")
+    }
+}
+
+impl<E: Engine> Validate for PublicKey<E> {
+    fn validate(&self) -> Result<(), String> {
+        validate_point(&self.X, "public key X")?;
+        for (i, y) in self.Y.iter().enumerate() {
+            validate_point(y, &format!("public key Y[{}]", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Engine> Validate for BlindPublicKey<E> {
+    fn validate(&self) -> Result<(), String> {
+        validate_point(&self.X1, "public key X1")?;
+        validate_point(&self.X2, "public key X2")?;
+        for (i, y) in self.Y1.iter().enumerate() {
+            validate_point(y, &format!("public key Y1[{}]", i))?;
+        }
+        for (i, y) in self.Y2.iter().enumerate() {
+            validate_point(y, &format!("public key Y2[{}]", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Engine> Validate for SignatureProof<E> {
+    fn validate(&self) -> Result<(), String> {
+        match util::is_valid_gt::<E>(&self.a) {
+            true => Ok(()),
+            false => Err(String::from("signature proof a is zero or not in the order r subgroup"))
+        }
+    }
+}
+
 
 #[derive(Clone)]
 pub struct KeyPair<E: Engine> {
@@ -263,31 +319,46 @@ impl<E: Engine> PublicKey<E> {
         }
     }
 
-    pub fn verify(&self, mpk: &PublicParams<E>, message: &Vec<E::Fr>, signature: &Signature<E>) -> bool {
+    /// computes X * prod Y_i ^ m_i, the G2 element the signature is checked against
+    fn message_point(&self, message: &Vec<E::Fr>) -> E::G2 {
         let mut L = E::G2::zero();
-        let mut l = self.Y.len();
-        let diff = (l - message.len());
-
-        l = match diff > 0 {
+        let l = match self.Y.len() > message.len() {
             true => message.len(),
-            false => l
+            false => self.Y.len()
         };
 
         for i in 0..l {
-            if (i < message.len()) { // bounds check on message vector
-                // L = L + self.Y[i].mul(message[i]);
-                let mut Y = self.Y[i];
-                Y.mul_assign(message[i]); // Y_i ^ m_i
-                L.add_assign(&Y); // L += Y_i ^m_i
-            }
+            // L = L + self.Y[i].mul(message[i]);
+            let mut Y = self.Y[i];
+            Y.mul_assign(message[i]); // Y_i ^ m_i
+            L.add_assign(&Y); // L += Y_i ^m_i
         }
 
         let mut X2 = self.X;
         X2.add_assign(&L); // X2 = X + L
+        X2
+    }
+
+    pub fn verify(&self, mpk: &PublicParams<E>, message: &Vec<E::Fr>, signature: &Signature<E>) -> bool {
+        if signature.validate().is_err() {
+            return false;
+        }
+        let X2 = self.message_point(message);
         let lhs = E::pairing(signature.h, X2);
         let rhs = E::pairing(signature.H, mpk.g2);
         signature.h != E::G1::one() && lhs == rhs
     }
+
+    /// Verify many (message, signature) pairs at once. Each pairing equation is raised
+    /// to a random exponent and the product is checked with a single multi-pairing,
+    /// so an invalid signature makes the batch fail except with negligible probability.
+    pub fn verify_batch<R: Rng>(&self, csprng: &mut R, mpk: &PublicParams<E>, messages: &Vec<Vec<E::Fr>>, signatures: &Vec<Signature<E>>) -> bool {
+        if messages.len() != signatures.len() {
+            return false;
+        }
+        let points = messages.iter().map(|m| self.message_point(m)).collect();
+        verify_batch_helper(csprng, mpk, points, signatures)
+    }
 }
 
 ///
@@ -324,7 +395,22 @@ impl<E: Engine> BlindPublicKey<E> {
         PublicKey { X: self.X2.clone(), Y: self.Y2.clone() }
     }
 
-    pub fn verify(&self, mpk: &PublicParams<E>, message: &Vec<E::Fr>, signature: &Signature<E>) -> bool {
+    /// checks that the G1 and G2 halves of the key have the same exponents, i.e.
+    /// e(X1, g2) = e(g1, X2) and e(Y1_i, g2) = e(g1, Y2_i), and that all points are valid
+    pub fn is_well_formed(&self, mpk: &PublicParams<E>) -> bool {
+        if self.Y1.len() != self.Y2.len() {
+            return false;
+        }
+        let g1 = self.Y1.iter().chain(Some(&self.X1));
+        let g2 = self.Y2.iter().chain(Some(&self.X2));
+        g1.zip(g2).all(|(p1, p2)| {
+            util::is_valid_point(p1) && util::is_valid_point(p2) &&
+                E::pairing(*p1, mpk.g2) == E::pairing(mpk.g1, *p2)
+        })
+    }
+
+    /// computes X2 * prod Y2_i ^ m_i, where the last message element is taken on g2
+    fn message_point(&self, mpk: &PublicParams<E>, message: &Vec<E::Fr>) -> E::G2 {
         let mut L = E::G2::zero();
         let l = self.Y2.len();
         //println!("verify - m.len = {}, l = {}", message.len(), l);
@@ -354,12 +440,30 @@ impl<E: Engine> BlindPublicKey<E> {
 
         let mut X2 = self.X2.clone();
         X2.add_assign(&L); // X2 = X + L
+        X2
+    }
+
+    pub fn verify(&self, mpk: &PublicParams<E>, message: &Vec<E::Fr>, signature: &Signature<E>) -> bool {
+        if signature.validate().is_err() {
+            return false;
+        }
+        let X2 = self.message_point(mpk, message);
         let lhs = E::pairing(signature.h, X2);
         let rhs = E::pairing(signature.H, mpk.g2);
 
         signature.h != E::G1::one() && lhs == rhs
     }
 
+    /// Verify many (message, signature) pairs at once using a random linear combination
+    /// and a single multi-pairing (see PublicKey::verify_batch)
+    pub fn verify_batch<R: Rng>(&self, csprng: &mut R, mpk: &PublicParams<E>, messages: &Vec<Vec<E::Fr>>, signatures: &Vec<Signature<E>>) -> bool {
+        if messages.len() != signatures.len() {
+            return false;
+        }
+        let points = messages.iter().map(|m| self.message_point(mpk, m)).collect();
+        verify_batch_helper(csprng, mpk, points, signatures)
+    }
+
     /// verify a blinded signature without unblinding it first
     pub fn verify_blind(&self, mpk: &PublicParams<E>, message: &Vec<E::Fr>, bf: &E::Fr, signature: &Signature<E>) -> bool {
         let mut m = message.clone();
@@ -372,20 +476,24 @@ impl<E: Engine> BlindPublicKey<E> {
     /// Takes in a proof generated by prove_response(), a blind signature, and a challenge
     /// outputs: boolean
     pub fn verify_proof(&self, mpk: &PublicParams<E>, blindSig: Signature<E>, p: SignatureProof<E>, challenge: E::Fr) -> bool {
-        let mut gx = E::pairing(blindSig.h, self.X2);
-        gx = gx.pow(challenge.into_repr());
-        for j in 0..self.Y2.len() {
-            let mut gy = E::pairing(blindSig.h, self.Y2[j]);
-            gy = gy.pow(p.zsig[j].into_repr());
-            gx.mul_assign(&gy);
+        if p.zsig.len() != self.Y2.len() {
+            return false;
+        }
+        let points = proof_g1_points(&blindSig, &p, &challenge, self.Y2.len());
+        let mut g2 = vec! {self.X2};
+        g2.extend(self.Y2.iter());
+        g2.push(mpk.g2);
+        let pairs: Vec<(E::G1, E::G2)> = points.into_iter().zip(g2.into_iter()).collect();
+        multi_pairing::<E>(&pairs) == p.a
+    }
+
+    /// prepares the fixed G2 elements of the public key for repeated proof verification
+    pub fn prepare(&self, mpk: &PublicParams<E>) -> PreparedBlindPublicKey<E> {
+        PreparedBlindPublicKey {
+            X2: self.X2.into_affine().prepare(),
+            Y2: self.Y2.iter().map(|y| y.into_affine().prepare()).collect(),
+            g2: mpk.g2.into_affine().prepare(),
         }
-        let mut h = E::pairing(blindSig.h, mpk.g2);
-        h = h.pow(p.zv.into_repr());
-        gx.mul_assign(&h);
-        let mut g = E::pairing(blindSig.H, mpk.g2);
-        g = g.pow(challenge.into_repr());
-        g.mul_assign(&p.a);
-        gx == g
     }
 
     pub fn blind<R: Rng>(&self, csprng: &mut R, bf: &E::Fr, signature: &Signature<E>) -> Signature<E> {
@@ -425,19 +533,20 @@ impl<E: Engine> BlindPublicKey<E> {
         let blindSig = self.blind(rng, &v, signature);
         let mut t = tOptional.unwrap_or(Vec::<E::Fr>::with_capacity(self.Y2.len()));
         let tt = ttOptional.unwrap_or(E::Fr::rand(rng));
-        let mut a = E::Fqk::one();
-        // TODO: consider optimizations to pairing in loop
+        // a = prod e(h, Y2_j)^t_j * e(h, g2)^tt = prod e(h^t_j, Y2_j) * e(h^tt, g2)
+        let mut pairs: Vec<(E::G1, E::G2)> = Vec::with_capacity(self.Y2.len() + 1);
         for j in 0..self.Y2.len() {
             if t.len() == j {
                 t.push(E::Fr::rand(rng));
             }
-            let mut gy = E::pairing(blindSig.h, self.Y2[j]);
-            gy = gy.pow(t[j].into_repr());
-            a.mul_assign(&gy);
+            let mut ht = blindSig.h;
+            ht.mul_assign(t[j].into_repr());
+            pairs.push((ht, self.Y2[j]));
         }
-        let mut h = E::pairing(blindSig.h, mpk.g2);
-        h = h.pow(tt.into_repr());
-        a.mul_assign(&h);
+        let mut htt = blindSig.h;
+        htt.mul_assign(tt.into_repr());
+        pairs.push((htt, mpk.g2));
+        let a = multi_pairing::<E>(&pairs);
         ProofState { v, t, tt, a, blindSig }
     }
 
@@ -464,6 +573,101 @@ impl<E: Engine> BlindPublicKey<E> {
 }
 
 
+/// computes prod e(P_i, Q_i) with one multi-Miller loop and a single final exponentiation
+pub fn multi_pairing<E: Engine>(pairs: &Vec<(E::G1, E::G2)>) -> E::Fqk {
+    let prepared: Vec<<E::G2Affine as CurveAffine>::Prepared> = pairs.iter()
+        .map(|(_, q)| q.into_affine().prepare())
+        .collect();
+    let pairs: Vec<(E::G1, &<E::G2Affine as CurveAffine>::Prepared)> = pairs.iter()
+        .zip(prepared.iter())
+        .map(|((p, _), q)| (*p, q))
+        .collect();
+    multi_pairing_prepared::<E>(&pairs)
+}
+
+/// checks prod e(h_i ^ r_i, M_i) * e(prod H_i ^ -r_i, g2) == 1 for random r_i
+fn verify_batch_helper<R: Rng, E: Engine>(csprng: &mut R, mpk: &PublicParams<E>, points: Vec<E::G2>, signatures: &Vec<Signature<E>>) -> bool {
+    let mut pairs: Vec<(E::G1, E::G2)> = Vec::with_capacity(signatures.len() + 1);
+    let mut H = E::G1::zero();
+    for (M, signature) in points.into_iter().zip(signatures.iter()) {
+        if signature.h == E::G1::one() || signature.validate().is_err() {
+            return false;
+        }
+        let r = E::Fr::rand(csprng);
+        let mut h = signature.h;
+        h.mul_assign(r); // h_i ^ r_i
+        let mut Hr = signature.H;
+        Hr.mul_assign(r); // H_i ^ r_i
+        H.add_assign(&Hr);
+        pairs.push((h, M));
+    }
+    H.negate();
+    pairs.push((H, mpk.g2));
+
+    multi_pairing::<E>(&pairs) == E::Fqk::one()
+}
+
+/// computes the G1 side of the proof of knowledge check
+/// e(h, X2)^c * prod e(h, Y2_j)^zsig_j * e(h, g2)^zv == e(H, g2)^c * a
+/// with the exponents moved into G1, so it becomes a single multi-pairing:
+/// e(h^c, X2) * prod e(h^zsig_j, Y2_j) * e(h^zv * H^-c, g2) == a
+fn proof_g1_points<E: Engine>(blindSig: &Signature<E>, p: &SignatureProof<E>, challenge: &E::Fr, l: usize) -> Vec<E::G1> {
+    let mut points: Vec<E::G1> = Vec::with_capacity(l + 2);
+    let mut hc = blindSig.h;
+    hc.mul_assign(challenge.into_repr());
+    points.push(hc);
+    for j in 0..l {
+        let mut hz = blindSig.h;
+        hz.mul_assign(p.zsig[j].into_repr());
+        points.push(hz);
+    }
+    let mut hzv = blindSig.h;
+    hzv.mul_assign(p.zv.into_repr());
+    let mut Hc = blindSig.H;
+    Hc.mul_assign(challenge.into_repr());
+    hzv.sub_assign(&Hc);
+    points.push(hzv);
+    points
+}
+
+///
+/// BlindPublicKey with X2, Y2 and g2 prepared for the Miller loop. These elements are
+/// fixed for the life of a merchant, so verifiers compute the G2 line functions once.
+///
+#[derive(Clone)]
+pub struct PreparedBlindPublicKey<E: Engine> {
+    pub X2: <E::G2Affine as CurveAffine>::Prepared,
+    pub Y2: Vec<<E::G2Affine as CurveAffine>::Prepared>,
+    pub g2: <E::G2Affine as CurveAffine>::Prepared,
+}
+
+impl<E: Engine> PreparedBlindPublicKey<E> {
+    /// Verify a proof of knowledge of a signature (see BlindPublicKey::verify_proof)
+    pub fn verify_proof(&self, blindSig: &Signature<E>, p: &SignatureProof<E>, challenge: E::Fr) -> bool {
+        if p.zsig.len() != self.Y2.len() {
+            return false;
+        }
+        let points = proof_g1_points(blindSig, p, &challenge, self.Y2.len());
+        let mut g2 = vec! {&self.X2};
+        g2.extend(self.Y2.iter());
+        g2.push(&self.g2);
+        let pairs: Vec<(E::G1, &<E::G2Affine as CurveAffine>::Prepared)> = points.into_iter().zip(g2.into_iter()).collect();
+        multi_pairing_prepared::<E>(&pairs) == p.a
+    }
+}
+
+/// computes prod e(P_i, Q_i) for G2 elements that have already been prepared
+pub fn multi_pairing_prepared<E: Engine>(pairs: &Vec<(E::G1, &<E::G2Affine as CurveAffine>::Prepared)>) -> E::Fqk {
+    let prepared: Vec<<E::G1Affine as CurveAffine>::Prepared> = pairs.iter()
+        .map(|(p, _)| p.into_affine().prepare())
+        .collect();
+    let terms: Vec<(&<E::G1Affine as CurveAffine>::Prepared, &<E::G2Affine as CurveAffine>::Prepared)> = prepared.iter()
+        .zip(pairs.iter())
+        .map(|(p, (_, q))| (p, *q))
+        .collect();
+    E::final_exponentiation(&E::miller_loop(terms.iter())).unwrap()
+}
+
 pub fn setup<R: Rng, E: Engine>(csprng: &mut R) -> PublicParams<E> {
     let g1 = E::G1::rand(csprng);
     let g2 = E::G2::rand(csprng);
@@ -471,6 +675,15 @@ pub fn setup<R: Rng, E: Engine>(csprng: &mut R) -> PublicParams<E> {
     return mpk;
 }
 
+/// setup_from_seed - derives the public params deterministically from a public seed by hashing it
+/// to G1 and G2 (nothing up my sleeve), so that no trusted setup is needed. The map is specified
+/// in util::hash_to_curve, so other implementations can recompute the params.
+pub fn setup_from_seed<E: Engine>(seed: &[u8]) -> PublicParams<E> {
+    let g1 = util::hash_to_g1::<E>(seed, util::DST_NUMS_PUBLIC_PARAMS_G1);
+    let g2 = util::hash_to_g2::<E>(seed, util::DST_NUMS_PUBLIC_PARAMS_G2);
+    PublicParams { g1, g2 }
+}
+
 ///
 /// KeyPair - implements the standard CL signature variant by PS - Section 3.1
 ///
@@ -568,7 +781,8 @@ mod tests {
     use super::*;
 
     use ff::Rand;
-    use pairing::bls12_381::{Bls12, Fr};
+    use pairing::bls12_381::{Bls12, Fr, G1, G2};
+    use pairing::bn256::Bn256;
 
     #[test]
     fn sign_and_verify() {
@@ -634,6 +848,102 @@ mod tests {
         assert_eq!(public_key.verify(&mpk, &message1, &rand_sig), true);
     }
 
+    #[test]
+    fn validate_rejects_invalid_points() {
+        let mut rng = &mut rand::thread_rng();
+        let mpk = setup(&mut rng);
+        let keypair = BlindKeyPair::<Bls12>::generate(&mut rng, &mpk, 3);
+        let public_key = keypair.get_public_key(&mpk);
+        let message = vec![Fr::rand(rng), Fr::rand(rng), Fr::rand(rng)];
+        let sig = keypair.sign(&mut rng, &message);
+        assert!(sig.validate().is_ok());
+        assert!(public_key.validate().is_ok());
+        assert!(keypair.public.validate().is_ok());
+
+        // (0, 0) satisfies the pairing equation for every message
+        let zero_sig = Signature::<Bls12> { h: G1::zero(), H: G1::zero() };
+        assert_eq!(zero_sig.validate().err().unwrap(), "signature h is the identity or not in the prime order subgroup");
+        assert!(!public_key.verify(&mpk, &message, &zero_sig));
+        assert!(!keypair.public.verify(&mpk, &message, &zero_sig));
+        assert!(!public_key.verify_batch(&mut rng, &mpk, &vec![message.clone()], &vec![zero_sig]));
+
+        let mut bad_sig = sig.clone();
+        bad_sig.H = util::non_subgroup_g1();
+        assert_eq!(bad_sig.validate().err().unwrap(), "signature H is the identity or not in the prime order subgroup");
+        assert!(!public_key.verify(&mpk, &message, &bad_sig));
+
+        let mut bad_pk = public_key.clone();
+        bad_pk.Y[2] = G2::zero();
+        assert_eq!(bad_pk.validate().err().unwrap(), "public key Y[2] is the identity or not in the prime order subgroup");
+
+        let mut bad_pk = keypair.public.clone();
+        bad_pk.Y1[1] = util::non_subgroup_g1();
+        assert_eq!(bad_pk.validate().err().unwrap(), "public key Y1[1] is the identity or not in the prime order subgroup");
+        assert!(!bad_pk.is_well_formed(&mpk));
+
+        let proof_state = keypair.public.prove_commitment(rng, &mpk, &sig, None, None);
+        let challenge = Fr::rand(rng);
+        let mut proof = keypair.public.prove_response(&proof_state, challenge, &mut message.clone());
+        assert!(proof.validate().is_ok());
+        proof.a = <Bls12 as Engine>::Fqk::zero();
+        assert_eq!(proof.validate().err().unwrap(), "signature proof a is zero or not in the order r subgroup");
+    }
+
+    #[test]
+    fn secret_key_is_redacted() {
+        let mut rng = &mut rand::thread_rng();
+        let mpk = setup(&mut rng);
+        let keypair = BlindKeyPair::<Bls12>::generate(&mut rng, &mpk, 3);
+        let x = format!("{}", keypair.secret.x);
+        assert_eq!(format!("{}", keypair.secret), "SK : (x=<redacted>, y=<3 redacted>)");
+        assert_eq!(format!("{:?}", keypair.secret), "SecretKey { x: <redacted>, y: <3 redacted> }");
+        assert!(!format!("{:?}", keypair.secret).contains(&x));
+    }
+
+    #[test]
+    fn setup_from_seed_works() {
+        let mpk = setup_from_seed::<Bls12>(b"seed");
+        assert!(!mpk.g1.is_zero() && !mpk.g2.is_zero());
+        assert!(mpk == setup_from_seed::<Bls12>(b"seed"));
+        assert!(mpk.verify_seed(b"seed"));
+        assert!(!mpk.verify_seed(b"other seed"));
+        assert!(!setup::<_, Bls12>(&mut rand::thread_rng()).verify_seed(b"seed"));
+        assert!(setup_from_seed::<Bn256>(b"seed").verify_seed(b"seed"));
+    }
+
+    #[test]
+    fn setup_from_seed_matches_known_answers() {
+        // a change of the hash to the curve would silently change the public params, and so
+        // the channel tokens of all existing channels
+        let mpk = setup_from_seed::<Bls12>(b"seed");
+        assert_eq!(format!("{}", mpk.g1), "G1(x=Fq(0x0d634ee2ca656afbda08e29b4ca0785b75e5961a38b3324e356d3d91e0190097fd3b73ff52f04b7939fa15d7c1d20ca9), y=Fq(0x00670e85377cdd0b7123875c790564ded6e5d6872de031652459902107a97f77c57d9c95a6f912a7b85b49a2326583e6))");
+        assert_eq!(format!("{}", mpk.g2), "G2(x=Fq2(Fq(0x0584126eb3c2870e99695fca5245ced412046c6d50212152d9da2e3bd1f255beb30ad546adb455e1b988e0c6d03ae2c8) + Fq(0x13ce7447b9e98552344148e94a180ae39c722e55168ae6e2e09b2f218b8c75c04e31f375e4f0c4d580544360225dea00) * u), y=Fq2(Fq(0x04ab40b383f95d8ad6efc661619c061a22e7f9cd7689de4b3a2d3b5b091941e8ed8c52c68412c03babb4ccfc74beee59) + Fq(0x0981a865ac6699cba9c92549887df2f2f9eb3c70d77f3dd983ee96ca0ba12cfe1cacf4fbca8a0acab1519751d49fc426) * u))");
+
+        let mpk = setup_from_seed::<Bn256>(b"seed");
+        assert_eq!(format!("{}", mpk.g1), "G1(x=Fq(0x0ee4411720b953fe2e0cd378658b9e914c0aa93debece20474c81e656b61df2d), y=Fq(0x254c93b3e1f88e18d7c42fc6fd517487075b3a33e047518f27f2335265f8e398))");
+        assert_eq!(format!("{}", mpk.g2), "G2(x=Fq2(Fq(0x22a56f8cdc195d176b85a2ec248323b9e8c1acc83c449c1582ee451b3295ea97) + Fq(0x10a9ab7e280041fe299dc1595ec316005878e70311575dd6c5fbdede7d0bd23a) * u), y=Fq2(Fq(0x27defcb9e0673dc63dcb3b719c6e4f477ed8f7ee7f90c241a4a3f3c31ece48b0) + Fq(0x2d807503c02cb1223fea7eccc0c96b5f3a9845ebb6e3d5e261e36774f3ea055b) * u))");
+    }
+
+    #[test]
+    fn blind_public_key_is_well_formed_works() {
+        let mut rng = &mut rand::thread_rng();
+        let mpk = setup(&mut rng);
+        let keypair = BlindKeyPair::<Bls12>::generate(&mut rng, &mpk, 3);
+        assert!(keypair.public.is_well_formed(&mpk));
+
+        let mut pk = keypair.public.clone();
+        pk.Y1[1] = mpk.g1;
+        assert!(!pk.is_well_formed(&mpk));
+
+        let mut pk = keypair.public.clone();
+        pk.X2 = <Bls12 as Engine>::G2::zero();
+        assert!(!pk.is_well_formed(&mpk));
+
+        let mut pk = keypair.public.clone();
+        pk.Y2.pop();
+        assert!(!pk.is_well_formed(&mpk));
+    }
+
     #[test]
     fn blind_unblind_works() {
         let mut rng = &mut rand::thread_rng();
@@ -713,6 +1023,146 @@ mod tests {
         assert_eq!(keypair.public.verify_proof(&mpk, proof_state.blindSig, proof, challenge), true);
     }
 
+    #[test]
+    fn proof_of_knowledge_of_signature_bn256() {
+        let mut rng = &mut rand::thread_rng();
+
+        let l = 5;
+        let mpk = setup(&mut rng);
+        let keypair = BlindKeyPair::<Bn256>::generate(&mut rng, &mpk, l);
+
+        let mut message1 = Vec::new();
+        for _i in 0..l {
+            message1.push(<Bn256 as ff::ScalarEngine>::Fr::rand(&mut rng));
+        }
+
+        let sig = keypair.sign(&mut rng, &message1);
+        let proof_state = keypair.public.prove_commitment(rng, &mpk, &sig, None, None);
+        let challenge = <Bn256 as ff::ScalarEngine>::Fr::rand(&mut rng);
+        let proof = keypair.public.prove_response(&proof_state.clone(), challenge, &mut message1);
+
+        assert_eq!(keypair.public.verify_proof(&mpk, proof_state.blindSig.clone(), proof.clone(), challenge), true);
+        let challenge1 = <Bn256 as ff::ScalarEngine>::Fr::rand(&mut rng);
+        assert_eq!(keypair.public.verify_proof(&mpk, proof_state.blindSig, proof, challenge1), false);
+    }
+
+    #[test]
+    fn prepared_public_key_verifies_proof() {
+        let mut rng = &mut rand::thread_rng();
+
+        let l = 5;
+        let mpk = setup(&mut rng);
+        let keypair = BlindKeyPair::<Bls12>::generate(&mut rng, &mpk, l);
+        let prepared_pk = keypair.public.prepare(&mpk);
+
+        let mut message1: Vec<Fr> = Vec::new();
+        for _i in 0..l {
+            message1.push(Fr::rand(&mut rng));
+        }
+
+        let sig = keypair.sign(&mut rng, &message1);
+        let proof_state = keypair.public.prove_commitment(rng, &mpk, &sig, None, None);
+        let challenge = Fr::rand(&mut rng);
+        let proof = keypair.public.prove_response(&proof_state.clone(), challenge, &mut message1);
+
+        assert_eq!(prepared_pk.verify_proof(&proof_state.blindSig, &proof, challenge), true);
+        assert_eq!(prepared_pk.verify_proof(&proof_state.blindSig, &proof, Fr::rand(&mut rng)), false);
+    }
+
+    #[test]
+    fn prove_commitment_matches_pairing_product() {
+        let mut rng = &mut rand::thread_rng();
+
+        let l = 5;
+        let mpk = setup(&mut rng);
+        let keypair = BlindKeyPair::<Bls12>::generate(&mut rng, &mpk, l);
+
+        let mut message1: Vec<Fr> = Vec::new();
+        let mut t: Vec<Fr> = Vec::new();
+        for _i in 0..l {
+            message1.push(Fr::rand(&mut rng));
+            t.push(Fr::rand(&mut rng));
+        }
+        let tt = Fr::rand(&mut rng);
+
+        let sig = keypair.sign(&mut rng, &message1);
+        let proof_state = keypair.public.prove_commitment(rng, &mpk, &sig, Some(t.clone()), Some(tt));
+
+        // a computed as a product of individual pairings in the target group
+        let mut a = <Bls12 as Engine>::Fqk::one();
+        for j in 0..l {
+            let gy = Bls12::pairing(proof_state.blindSig.h, keypair.public.Y2[j]).pow(t[j].into_repr());
+            a.mul_assign(&gy);
+        }
+        a.mul_assign(&Bls12::pairing(proof_state.blindSig.h, mpk.g2).pow(tt.into_repr()));
+
+        assert_eq!(proof_state.a, a);
+    }
+
+    #[test]
+    fn batch_verify_works() {
+        let mut rng = &mut rand::thread_rng();
+
+        let l = 5;
+        let mpk = setup(&mut rng);
+        let keypair = KeyPair::<Bls12>::generate(&mut rng, &mpk, l);
+
+        let mut messages: Vec<Vec<Fr>> = Vec::new();
+        let mut signatures: Vec<Signature<Bls12>> = Vec::new();
+        for _i in 0..10 {
+            let mut message: Vec<Fr> = Vec::new();
+            for _j in 0..l {
+                message.push(Fr::rand(&mut rng));
+            }
+            signatures.push(keypair.sign(&mut rng, &message));
+            messages.push(message);
+        }
+
+        assert_eq!(keypair.public.verify_batch(&mut rng, &mpk, &messages, &signatures), true);
+
+        // a single invalid signature invalidates the whole batch
+        let mut bad_signatures = signatures.clone();
+        bad_signatures[3] = signatures[4].clone();
+        assert_eq!(keypair.public.verify_batch(&mut rng, &mpk, &messages, &bad_signatures), false);
+
+        let mut bad_messages = messages.clone();
+        bad_messages[7][0] = Fr::rand(&mut rng);
+        assert_eq!(keypair.public.verify_batch(&mut rng, &mpk, &bad_messages, &signatures), false);
+
+        messages.pop();
+        assert_eq!(keypair.public.verify_batch(&mut rng, &mpk, &messages, &signatures), false);
+    }
+
+    #[test]
+    fn blind_batch_verify_works() {
+        let mut rng = &mut rand::thread_rng();
+
+        let l = 5;
+        let mpk = setup(&mut rng);
+        let keypair = BlindKeyPair::<Bn256>::generate(&mut rng, &mpk, l);
+
+        let mut messages: Vec<Vec<<Bn256 as ff::ScalarEngine>::Fr>> = Vec::new();
+        let mut signatures: Vec<Signature<Bn256>> = Vec::new();
+        for _i in 0..10 {
+            let mut message = Vec::new();
+            for _j in 0..l {
+                message.push(<Bn256 as ff::ScalarEngine>::Fr::rand(&mut rng));
+            }
+            let signature = keypair.sign(&mut rng, &message);
+            // blinded signatures are checked with the blinding factor as the last element
+            let t = <Bn256 as ff::ScalarEngine>::Fr::rand(&mut rng);
+            signatures.push(keypair.blind(&mut rng, &t, &signature));
+            message.push(t);
+            messages.push(message);
+        }
+
+        assert_eq!(keypair.public.verify_batch(&mut rng, &mpk, &messages, &signatures), true);
+
+        let mut bad_messages = messages.clone();
+        bad_messages[2][4] = <Bn256 as ff::ScalarEngine>::Fr::rand(&mut rng);
+        assert_eq!(keypair.public.verify_batch(&mut rng, &mpk, &bad_messages, &signatures), false);
+    }
+
     #[test]
     fn test_cl_basic_serialize() {
         let mut rng = &mut rand::thread_rng();
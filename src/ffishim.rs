@@ -5,515 +5,1612 @@ pub mod ffishim {
     use bidirectional;
     use ff::ScalarEngine;
     use pairing::bls12_381::Bls12;
+    use pairing::bn256::Bn256;
 
-    use serde::Deserialize;
+    use serde::{Serialize, Deserialize};
+    use serde_json::Value;
 
     use libc::c_char;
+    use std::any::Any;
     use std::ffi::{CStr, CString};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::ptr;
     use std::str;
 
-    fn error_message(s: String) -> *mut c_char {
-        let ser = ["{\'error\':\'", &s, "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
+    /// Numeric codes carried by the `{"error": {"code": .., "message": ..}}` response of a failed call
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum ErrorCode {
+        /// an argument could not be parsed into the expected type
+        InvalidInput = 1,
+        /// the inputs were well-formed but rejected by the protocol
+        ProtocolFailure = 2,
+        /// the call panicked, e.g. on a precondition of the protocol API that does not hold
+        Panic = 3,
+    }
+
+    fn json_response(response: Value) -> *mut c_char {
+        let cser = CString::new(response.to_string()).unwrap();
         cser.into_raw()
     }
 
+    fn error_message(code: ErrorCode, s: String) -> *mut c_char {
+        json_response(json!({"error": {"code": code as i32, "message": s}}))
+    }
+
+    fn panic_message(err: Box<dyn Any + Send>) -> String {
+        match err.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => match err.downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => String::from("unknown panic"),
+            }
+        }
+    }
+
+    // Unwinding across the C ABI is undefined behaviour, so every entry point runs its body
+    // through one of these guards. A panic becomes an error response (or `on_panic` for entry
+    // points that do not return a response). The handles passed to a call that panicked may
+    // be left partially updated.
+    fn catch_panic<F: FnOnce() -> *mut c_char>(f: F) -> *mut c_char {
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(response) => response,
+            Err(err) => error_message(ErrorCode::Panic, panic_message(err)),
+        }
+    }
+
+    fn catch_panic_or<T, F: FnOnce() -> T>(on_panic: T, f: F) -> T {
+        panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+    }
+
     macro_rules! bolt_try {
         ($e:expr) => (match $e {
             Ok(val) => val.unwrap(),
-            Err(err) => return error_message(err),
+            Err(err) => return error_message(ErrorCode::ProtocolFailure, err),
         });
     }
 
     macro_rules! handle_errors {
         ($e:expr) => (match $e {
             Ok(val) => val,
-            Err(err) => return error_message(err.to_string()),
+            Err(err) => return error_message(ErrorCode::InvalidInput, err.to_string()),
+        });
+    }
+
+    macro_rules! handle_mut {
+        ($handle:expr) => (match unsafe { $handle.as_mut() } {
+            Some(handle) => handle,
+            None => return error_message(ErrorCode::InvalidInput, String::from("null handle")),
+        });
+    }
+
+    macro_rules! handle_ref {
+        ($handle:expr) => (match unsafe { $handle.as_ref() } {
+            Some(handle) => handle,
+            None => return error_message(ErrorCode::InvalidInput, String::from("null handle")),
         });
     }
 
     pub type ResultSerdeType<T> = Result<T, serde_json::error::Error>;
-    type CURVE = Bls12;
 
-    fn deserialize_result_object<'a, T>(serialized: *mut c_char) -> ResultSerdeType<T>
+    // C string arguments must be UTF-8, otherwise they are reported as invalid input
+    fn str_arg<'a>(ptr: *const c_char) -> Result<&'a str, str::Utf8Error> {
+        let bytes = unsafe { CStr::from_ptr(ptr).to_bytes() };
+        str::from_utf8(bytes)
+    }
+
+    fn deserialize_result_object<'a, T>(serialized: *const c_char) -> ResultSerdeType<T>
         where
             T: Deserialize<'a>,
     {
-        let bytes = unsafe { CStr::from_ptr(serialized).to_bytes() };
-        let string: &str = str::from_utf8(bytes).unwrap(); // make sure the bytes are UTF-8
+        let string: &str = str_arg(serialized).map_err(<serde_json::Error as serde::de::Error>::custom)?;
         serde_json::from_str(&string)
     }
 
+    // Protocol objects are embedded in responses as their serialized JSON string
+    fn serialize_object<T: Serialize>(object: &T) -> String {
+        serde_json::to_string(object).unwrap()
+    }
+
     #[no_mangle]
-    pub extern fn ffishim_free_string(pointer: *mut c_char) {
+    pub extern "C" fn ffishim_free_string(pointer: *mut c_char) {
         unsafe {
             if pointer.is_null() { return; }
             CString::from_raw(pointer)
         };
     }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_wtp_check_wpk(ser_wpk: *mut c_char) -> *mut c_char {
-        let wpk_result: ResultSerdeType<secp256k1::PublicKey> = deserialize_result_object(ser_wpk);
-        let _wpk = handle_errors!(wpk_result);
-
-        let res = true;
-        let ser = ["{\'result\':\'", serde_json::to_string(&res).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
+    // Emits the full set of `ffishim_<curve>_*` entry points for a pairing engine, so that
+    // each protocol function is defined once and bound for every supported curve.
+    macro_rules! ffishim_curve {
+        ($curve:ident, $CURVE:ident) => { paste! {
+            /// Merchant state loaded from its secrets, see `ffishim_<curve>_load_merchant`
+            pub type [<MerchantState $CURVE>] = bidirectional::MerchantState<$CURVE>;
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _wtp_check_wpk>](ser_wpk: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    let wpk_result: ResultSerdeType<secp256k1::PublicKey> = deserialize_result_object(ser_wpk);
+                    let _wpk = handle_errors!(wpk_result);
+
+                    let res = true;
+                    json_response(json!({"result": res}))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _channel_setup>](channel_name: *const c_char, third_party_support: u32) -> *mut c_char {
+                catch_panic(|| {
+                    let name: &str = handle_errors!(str_arg(channel_name));
+
+                    let mut tps = false;
+                    if third_party_support > 1 {
+                        tps = true;
+                    }
+                    let channel_state = bidirectional::ChannelState::<$CURVE>::new(name.to_string(), tps);
+
+                    json_response(json!({"channel_state": serialize_object(&channel_state)}))
+                })
+            }
+
+            // INIT
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _init_merchant>](ser_channel_state: *const c_char, name_ptr: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let mut channel_state = handle_errors!(channel_state_result);
+
+                    let name: &str = handle_errors!(str_arg(name_ptr));
+
+                    let (channel_token, merch_state, merch_ledger, channel_state) = bidirectional::init_merchant(rng, &mut channel_state, name);
+
+                    json_response(json!({
+                        "channel_token": serialize_object(&channel_token),
+                        "merch_state": serialize_object(&merch_state),
+                        "merch_secrets": serialize_object(&merch_state.export_secrets()),
+                        "merch_ledger": serialize_object(&merch_ledger),
+                        "channel_state": serialize_object(&channel_state)
+                    }))
+                })
+            }
+
+            // The merchant secrets are loaded once into a merchant state that stays in Rust, and the
+            // merchant entry points take it along with the public channel state and the ledger.
+            // Returns null if the secrets do not match the public params of the channel state.
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _load_merchant>](ser_merch_secrets: *const c_char, ser_channel_state: *const c_char) -> *mut [<MerchantState $CURVE>] {
+                catch_panic_or(ptr::null_mut(), || {
+                    let merch_secrets_result: ResultSerdeType<bidirectional::MerchantSecrets<$CURVE>> = deserialize_result_object(ser_merch_secrets);
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    match (merch_secrets_result, channel_state_result) {
+                        (Ok(merch_secrets), Ok(channel_state)) => match bidirectional::MerchantState::from_secrets(merch_secrets, &channel_state) {
+                            Ok(merch_state) => Box::into_raw(Box::new(merch_state)),
+                            Err(_) => ptr::null_mut(),
+                        },
+                        _ => ptr::null_mut(),
+                    }
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _free_merchant>](merch_state: *mut [<MerchantState $CURVE>]) {
+                catch_panic_or((), || {
+                    if merch_state.is_null() { return; }
+                    drop(unsafe { Box::from_raw(merch_state) });
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _init_customer>](ser_channel_state: *const c_char, ser_channel_token: *const c_char, balance_customer: i64, balance_merchant: i64, name_ptr: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the channel token
+                    let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<$CURVE>> = deserialize_result_object(ser_channel_token);
+                    let mut channel_token = handle_errors!(channel_token_result);
+
+                    // Deserialize the name
+                    let name: &str = handle_errors!(str_arg(name_ptr));
+
+                    // We change the channel state
+                    let cust_state = bolt_try!(bidirectional::init_customer(rng, &channel_state, &mut channel_token, balance_customer, balance_merchant, name));
+                    json_response(json!({
+                        "cust_state": serialize_object(&cust_state.export()),
+                        "channel_token": serialize_object(&channel_token)
+                    }))
+                })
+            }
+
+            // ESTABLISH
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _establish_customer_generate_proof>](ser_channel_token: *const c_char, ser_customer_state: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    // Deserialize the channel token
+                    let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<$CURVE>> = deserialize_result_object(ser_channel_token);
+                    let mut channel_token = handle_errors!(channel_token_result);
+
+                    // Deserialize the cust state
+                    let cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_customer_state);
+                    let mut cust_state = bidirectional::CustomerState::import(handle_errors!(cust_state_result));
+
+                    let (com, com_proof) = bidirectional::establish_customer_generate_proof(rng, &mut channel_token, &mut cust_state);
+
+                    json_response(json!({
+                        "cust_state": serialize_object(&cust_state.export()),
+                        "channel_token": serialize_object(&channel_token),
+                        "com": serialize_object(&com),
+                        "com_proof": serialize_object(&com_proof)
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _generate_channel_id>](ser_channel_token: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel token
+                    let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<$CURVE>> = deserialize_result_object(ser_channel_token);
+                    let channel_token = handle_errors!(channel_token_result);
+
+                    let id = channel_token.compute_channel_id();
+                    json_response(json!({"channel_id": serialize_object(&id)}))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _establish_merchant_issue_close_token>](ser_channel_state: *const c_char, ser_com: *const c_char, ser_com_proof: *const c_char, ser_channel_id: *const c_char, init_cust_bal: i64, init_merch_bal: i64, merch_state: *const [<MerchantState $CURVE>], ser_merch_ledger: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the com proof
+                    let com_result: ResultSerdeType<bidirectional::Commitment<$CURVE>> = deserialize_result_object(ser_com);
+                    let com = handle_errors!(com_result);
+
+                    // Deserialize the com proof
+                    let com_proof_result: ResultSerdeType<bidirectional::CommitmentProof<$CURVE>> = deserialize_result_object(ser_com_proof);
+                    let com_proof = handle_errors!(com_proof_result);
+
+                    // The merchant state loaded from its secrets
+                    let merch_state = handle_ref!(merch_state);
+
+                    // Deserialize the merchant ledger
+                    let merch_ledger_result: ResultSerdeType<bidirectional::MerchantLedger<$CURVE>> = deserialize_result_object(ser_merch_ledger);
+                    let mut merch_ledger = handle_errors!(merch_ledger_result);
+
+                    // Deserialize the pk_c
+                    let channel_id_result: ResultSerdeType<<$CURVE as ScalarEngine>::Fr> = deserialize_result_object(ser_channel_id);
+                    let channel_id_fr = handle_errors!(channel_id_result);
+
+                    let close_token = bolt_try!(bidirectional::establish_merchant_issue_close_token(rng, &channel_state, &com, &com_proof, &channel_id_fr, init_cust_bal, init_merch_bal, merch_state, &mut merch_ledger));
+
+                    json_response(json!({
+                        "close_token": serialize_object(&close_token),
+                        "merch_ledger": serialize_object(&merch_ledger)
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _establish_merchant_issue_pay_token>](ser_channel_state: *const c_char, ser_com: *const c_char, merch_state: *const [<MerchantState $CURVE>]) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the commitment
+                    let com_result: ResultSerdeType<bidirectional::Commitment<$CURVE>> = deserialize_result_object(ser_com);
+                    let com = handle_errors!(com_result);
+
+                    // The merchant state loaded from its secrets
+                    let merch_state = handle_ref!(merch_state);
+
+                    let pay_token = bidirectional::establish_merchant_issue_pay_token(rng, &channel_state, &com, merch_state);
+
+                    json_response(json!({"pay_token": serialize_object(&pay_token)}))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _verify_close_token>](ser_channel_state: *const c_char, ser_customer_state: *const c_char, ser_close_token: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let mut channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the cust state
+                    let cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_customer_state);
+                    let mut cust_state = bidirectional::CustomerState::import(handle_errors!(cust_state_result));
+
+                    // Deserialize the close token
+                    let close_result: ResultSerdeType<bidirectional::Signature<$CURVE>> = deserialize_result_object(ser_close_token);
+                    let close_token = handle_errors!(close_result);
+
+                    let is_close_token_valid = cust_state.verify_close_token(&mut channel_state, &close_token);
+
+                    json_response(json!({
+                        "cust_state": serialize_object(&cust_state.export()),
+                        "is_token_valid": is_close_token_valid,
+                        "channel_state": serialize_object(&channel_state)
+                    }))
+                })
+            }
+
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _establish_customer_final>](ser_channel_state: *const c_char, ser_customer_state: *const c_char, ser_pay_token: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let mut channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the cust state
+                    let cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_customer_state);
+                    let mut cust_state = bidirectional::CustomerState::import(handle_errors!(cust_state_result));
+
+                    // Deserialize the custdata
+                    let pay_token_result: ResultSerdeType<bidirectional::Signature<$CURVE>> = deserialize_result_object(ser_pay_token);
+                    let pay_token = handle_errors!(pay_token_result);
+
+                    let is_channel_established = bidirectional::establish_customer_final(&mut channel_state, &mut cust_state, &pay_token);
+
+                    json_response(json!({
+                        "cust_state": serialize_object(&cust_state.export()),
+                        "is_established": is_channel_established,
+                        "channel_state": serialize_object(&channel_state)
+                    }))
+                })
+            }
+
+            // PAY
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _pay_generate_payment_proof>](ser_channel_state: *const c_char, ser_customer_state: *const c_char, amount: i64) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the cust state
+                    let cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_customer_state);
+                    let cust_state = bidirectional::CustomerState::import(handle_errors!(cust_state_result));
+
+                    // Generate the payment proof
+                    let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &channel_state, &cust_state, amount);
+                    // Serialize the results and return to caller
+                    json_response(json!({
+                        "payment": serialize_object(&payment),
+                        "cust_state": serialize_object(&new_cust_state.export())
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _pay_verify_payment_proof>](ser_channel_state: *const c_char, ser_pay_proof: *const c_char, merch_state: *const [<MerchantState $CURVE>], ser_merch_ledger: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the payment proof
+                    let payment_result: ResultSerdeType<bidirectional::Payment<$CURVE>> = deserialize_result_object(ser_pay_proof);
+                    let payment = handle_errors!(payment_result);
+
+                    // The merchant state loaded from its secrets
+                    let merch_state = handle_ref!(merch_state);
+
+                    // Deserialize the merchant ledger
+                    let merch_ledger_result: ResultSerdeType<bidirectional::MerchantLedger<$CURVE>> = deserialize_result_object(ser_merch_ledger);
+                    let mut merch_ledger = handle_errors!(merch_ledger_result);
+
+                    let close_token = bidirectional::verify_payment_proof(rng, &channel_state, &payment, merch_state, &mut merch_ledger);
+                    json_response(json!({
+                        "close_token": serialize_object(&close_token),
+                        "merch_ledger": serialize_object(&merch_ledger)
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _pay_verify_multiple_payment_proofs>](ser_channel_state: *const c_char, ser_sender_pay_proof: *const c_char, ser_receiver_pay_proof: *const c_char, merch_state: *const [<MerchantState $CURVE>], ser_merch_ledger: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    let rng = &mut rand::thread_rng();
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the payment proofs
+                    let sender_payment_result: ResultSerdeType<bidirectional::Payment<$CURVE>> = deserialize_result_object(ser_sender_pay_proof);
+                    let sender_payment = handle_errors!(sender_payment_result);
+
+                    let receiver_payment_result: ResultSerdeType<bidirectional::Payment<$CURVE>> = deserialize_result_object(ser_receiver_pay_proof);
+                    let receiver_payment = handle_errors!(receiver_payment_result);
+
+                    // The merchant state loaded from its secrets
+                    let merch_state = handle_ref!(merch_state);
+
+                    // Deserialize the merchant ledger
+                    let merch_ledger_result: ResultSerdeType<bidirectional::MerchantLedger<$CURVE>> = deserialize_result_object(ser_merch_ledger);
+                    let mut merch_ledger = handle_errors!(merch_ledger_result);
+
+                    let close_token_result = bidirectional::verify_multiple_payment_proofs(rng, &channel_state, &sender_payment, &receiver_payment, merch_state, &mut merch_ledger);
+                    let (sender_close_token, receiver_cond_close_token) = bolt_try!(close_token_result);
+                    json_response(json!({
+                        "sender_close_token": serialize_object(&sender_close_token),
+                        "receiver_cond_close_token": serialize_object(&receiver_cond_close_token),
+                        "merch_ledger": serialize_object(&merch_ledger)
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _pay_generate_revoke_token>](ser_channel_state: *const c_char, ser_cust_state: *const c_char, ser_new_cust_state: *const c_char, ser_close_token: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the cust state
+                    let cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_cust_state);
+                    let mut cust_state = bidirectional::CustomerState::import(handle_errors!(cust_state_result));
+
+                    // Deserialize the cust state
+                    let new_cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_new_cust_state);
+                    let new_cust_state = bidirectional::CustomerState::import(handle_errors!(new_cust_state_result));
+
+                    // Deserialize the close token
+                    let close_token_result: ResultSerdeType<bidirectional::Signature<$CURVE>> = deserialize_result_object(ser_close_token);
+                    let close_token = handle_errors!(close_token_result);
+
+                    let revoke_token = bidirectional::generate_revoke_token(&channel_state, &mut cust_state, new_cust_state, &close_token);
+                    json_response(json!({
+                        "revoke_token": serialize_object(&revoke_token),
+                        "cust_state": serialize_object(&cust_state.export())
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _pay_verify_revoke_token>](ser_revoke_token: *const c_char, ser_merch_ledger: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the revoke token
+                    let revoke_token_result: ResultSerdeType<bidirectional::RevokeToken> = deserialize_result_object(ser_revoke_token);
+                    let revoke_token = handle_errors!(revoke_token_result);
+
+                    // Deserialize the merchant ledger
+                    let merch_ledger_result: ResultSerdeType<bidirectional::MerchantLedger<$CURVE>> = deserialize_result_object(ser_merch_ledger);
+                    let mut merch_ledger = handle_errors!(merch_ledger_result);
+
+                    // send revoke token and get pay-token in response
+                    let pay_token_result = bidirectional::verify_revoke_token(&revoke_token, &mut merch_ledger);
+                    let pay_token = bolt_try!(pay_token_result);
+
+                    json_response(json!({
+                        "pay_token": serialize_object(&pay_token),
+                        "merch_ledger": serialize_object(&merch_ledger)
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _pay_verify_multiple_revoke_tokens>](ser_sender_revoke_token: *const c_char, ser_receiver_revoke_token: *const c_char, ser_merch_ledger: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the revoke tokens
+                    let sender_revoke_token_result: ResultSerdeType<bidirectional::RevokeToken> = deserialize_result_object(ser_sender_revoke_token);
+                    let sender_revoke_token = handle_errors!(sender_revoke_token_result);
+
+                    let receiver_revoke_token_result: ResultSerdeType<bidirectional::RevokeToken> = deserialize_result_object(ser_receiver_revoke_token);
+                    let receiver_revoke_token = handle_errors!(receiver_revoke_token_result);
+
+                    // Deserialize the merchant ledger
+                    let merch_ledger_result: ResultSerdeType<bidirectional::MerchantLedger<$CURVE>> = deserialize_result_object(ser_merch_ledger);
+                    let mut merch_ledger = handle_errors!(merch_ledger_result);
+
+                    // send revoke token and get pay-token in response
+                    let pay_token_result = bidirectional::verify_multiple_revoke_tokens(&sender_revoke_token, &receiver_revoke_token, &mut merch_ledger);
+                    let (sender_pay_token, receiver_pay_token) = bolt_try!(pay_token_result);
+
+                    json_response(json!({
+                        "sender_pay_token": serialize_object(&sender_pay_token),
+                        "receiver_pay_token": serialize_object(&receiver_pay_token),
+                        "merch_ledger": serialize_object(&merch_ledger)
+                    }))
+                })
+            }
+
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _pay_verify_payment_token>](ser_channel_state: *const c_char, ser_cust_state: *const c_char, ser_pay_token: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the cust state
+                    let cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_cust_state);
+                    let mut cust_state = bidirectional::CustomerState::import(handle_errors!(cust_state_result));
+
+                    // Deserialize the pay token
+                    let pay_token_result: ResultSerdeType<bidirectional::Signature<$CURVE>> = deserialize_result_object(ser_pay_token);
+                    let pay_token = handle_errors!(pay_token_result);
+
+                    // verify the pay token and update internal state
+                    let is_pay_valid = cust_state.verify_pay_token(&channel_state, &pay_token);
+                    json_response(json!({
+                        "cust_state": serialize_object(&cust_state.export()),
+                        "is_pay_valid": is_pay_valid
+                    }))
+                })
+            }
+
+            // CLOSE
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _customer_close>](ser_channel_state: *const c_char, ser_cust_state: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the cust state
+                    let cust_state_result: ResultSerdeType<bidirectional::CustomerStateExport<$CURVE>> = deserialize_result_object(ser_cust_state);
+                    let cust_state = bidirectional::CustomerState::import(handle_errors!(cust_state_result));
+
+                    let cust_close = bidirectional::customer_close(&channel_state, &cust_state);
+                    json_response(json!({"cust_close": serialize_object(&cust_close)}))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _merchant_close>](ser_channel_state: *const c_char, ser_channel_token: *const c_char, ser_address: *const c_char, ser_cust_close: *const c_char, merch_state: *const [<MerchantState $CURVE>], ser_merch_ledger: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel state
+                    let channel_state_result: ResultSerdeType<bidirectional::ChannelState<$CURVE>> = deserialize_result_object(ser_channel_state);
+                    let channel_state = handle_errors!(channel_state_result);
+
+                    // Deserialize the channel token
+                    let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<$CURVE>> = deserialize_result_object(ser_channel_token);
+                    let channel_token = handle_errors!(channel_token_result);
+
+                    // Deserialize the customer close structure
+                    let cust_close_result: ResultSerdeType<bidirectional::ChannelcloseC<$CURVE>> = deserialize_result_object(ser_cust_close);
+                    let cust_close = handle_errors!(cust_close_result);
+
+                    // The merchant state loaded from its secrets
+                    let merch_state = handle_ref!(merch_state);
+
+                    // Deserialize the merchant ledger
+                    let merch_ledger_result: ResultSerdeType<bidirectional::MerchantLedger<$CURVE>> = deserialize_result_object(ser_merch_ledger);
+                    let mut merch_ledger = handle_errors!(merch_ledger_result);
+
+                    // Deserialize the destination address as a string
+                    let address: &str = handle_errors!(str_arg(ser_address));
+
+                    // the ledger is returned in both outcomes, since a valid close marks the channel as closed
+                    let option = bidirectional::merchant_close(&channel_state, &channel_token, &cust_close, &mut merch_ledger);
+                    let keys = match option {
+                        Ok(n) => n.unwrap(),
+                        Err(err) => return json_response(json!({
+                            "error": {"code": ErrorCode::ProtocolFailure as i32, "message": err},
+                            "merch_ledger": serialize_object(&merch_ledger)
+                        })),
+                    };
+
+                    let merch_close: bidirectional::ChannelcloseM = merch_state.sign_revoke_message(address.to_string(), &keys.revoke_token);
+
+                    json_response(json!({
+                        "wpk": serialize_object(&keys.wpk),
+                        "merch_close": serialize_object(&merch_close),
+                        "merch_ledger": serialize_object(&merch_ledger)
+                    }))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _wtp_verify_cust_close_message>](ser_channel_token: *const c_char, ser_wpk: *const c_char, ser_close_msg: *const c_char, ser_close_token: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel token
+                    let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<$CURVE>> = deserialize_result_object(ser_channel_token);
+                    let channel_token = handle_errors!(channel_token_result);
+
+                    // Deserialize the wpk
+                    let wpk_result: ResultSerdeType<secp256k1::PublicKey> = deserialize_result_object(ser_wpk);
+                    let wpk = handle_errors!(wpk_result);
+
+                    // Deserialize the close wallet
+                    let close_msg_result: ResultSerdeType<bidirectional::Wallet<$CURVE>> = deserialize_result_object(ser_close_msg);
+                    let close_msg = handle_errors!(close_msg_result);
+
+                    // Deserialize the close token
+                    let close_token_result: ResultSerdeType<bidirectional::Signature<$CURVE>> = deserialize_result_object(ser_close_token);
+                    let close_token = handle_errors!(close_token_result);
+
+                    // check the signatures
+                    let token_valid = bidirectional::wtp_verify_cust_close_message(&channel_token, &wpk, &close_msg, &close_token);
+                    json_response(json!({"result": token_valid}))
+                })
+            }
+
+            #[no_mangle]
+            pub extern "C" fn [<ffishim_ $curve _wtp_verify_merch_close_message>](ser_channel_token: *const c_char, ser_wpk: *const c_char, ser_merch_close: *const c_char) -> *mut c_char {
+                catch_panic(|| {
+                    // Deserialize the channel token
+                    let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<$CURVE>> = deserialize_result_object(ser_channel_token);
+                    let channel_token = handle_errors!(channel_token_result);
+
+                    // Deserialize the wpk
+                    let wpk_result: ResultSerdeType<secp256k1::PublicKey> = deserialize_result_object(ser_wpk);
+                    let wpk = handle_errors!(wpk_result);
+
+                    // Deserialize the merch close
+                    //let revoke_token: secp256k1::Signature = deserialize_object(ser_revoke_token);
+                    let merch_close_result: ResultSerdeType<bidirectional::ChannelcloseM> = deserialize_result_object(ser_merch_close);
+                    let merch_close = handle_errors!(merch_close_result);
+
+                    // a close message without a revoke token does not prove a dispute
+                    let revoke_token_valid = match merch_close.revoke {
+                        Some(ref revoke_token) => bidirectional::wtp_verify_revoke_message(&wpk, revoke_token),
+                        None => false
+                    };
+                    let merch_close_valid = bidirectional::wtp_verify_merch_close_message(&channel_token, &merch_close);
+                    let token_valid = revoke_token_valid && merch_close_valid;
+
+                    json_response(json!({"result": token_valid}))
+                })
+            }
+        }};
     }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_channel_setup(channel_name: *const c_char, third_party_support: u32) -> *mut c_char {
-        let bytes = unsafe { CStr::from_ptr(channel_name).to_bytes() };
-        let name: &str = str::from_utf8(bytes).unwrap(); // make sure the bytes are UTF-8
+    ffishim_curve!(bls12, Bls12);
+    ffishim_curve!(bn256, Bn256);
+
+    /// Handle-based API on BLS12-381. Channel, merchant and customer states live in Rust
+    /// behind opaque pointers, so only the protocol messages exchanged between the parties
+    /// are (de)serialized on each call. A handle is persisted with its `_export` function,
+    /// restored with `_import` and released with `_free`; a merchant is exported as its secrets
+    /// and restored against its channel. Returned strings are released with `ffishim_free_string`.
+    pub mod handle {
+        use super::*;
+        use bidirectional::{ChannelState, ChannelToken, CustomerState, CustomerStateExport, MerchantState, MerchantSecrets, MerchantLedger};
+        use serde::de::DeserializeOwned;
+        use serde::{Serializer, Deserializer};
+
+        type CURVE = Bls12;
+
+        /// Channel state held by either party
+        #[derive(Serialize, Deserialize)]
+        pub struct BoltChannel {
+            state: ChannelState<CURVE>,
+        }
 
-        let mut tps = false;
-        if third_party_support > 1 {
-            tps = true;
+        /// Merchant state and ledger, and the channel token handed out to customers
+        pub struct BoltMerchant {
+            state: MerchantState<CURVE>,
+            ledger: MerchantLedger<CURVE>,
+            channel_token: ChannelToken<CURVE>,
         }
-        let channel_state = bidirectional::ChannelState::<CURVE>::new(name.to_string(), tps);
 
-        let ser = ["{\'channel_state\':\'", serde_json::to_string(&channel_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        // BoltMerchant as exported, with the secrets from which the state is restored (see MerchantState::from_secrets)
+        #[derive(Serialize, Deserialize)]
+        struct BoltMerchantExport {
+            secrets: MerchantSecrets<CURVE>,
+            ledger: MerchantLedger<CURVE>,
+            channel_token: ChannelToken<CURVE>,
+        }
 
-    // INIT
+        /// Customer state, its channel token and the next state of a payment in progress
+        #[derive(Serialize, Deserialize)]
+        pub struct BoltCustomer {
+            #[serde(serialize_with = "export_customer_state", deserialize_with = "import_customer_state")]
+            state: CustomerState<CURVE>,
+            channel_token: ChannelToken<CURVE>,
+            #[serde(serialize_with = "export_pending_state", deserialize_with = "import_pending_state")]
+            pending_state: Option<CustomerState<CURVE>>,
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_init_merchant(ser_channel_state: *mut c_char, name_ptr: *const c_char) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let mut channel_state = handle_errors!(channel_state_result);
+        // the states of an exported handle include their secrets (see CustomerState::export)
+        fn export_customer_state<S: Serializer>(state: &CustomerState<CURVE>, serializer: S) -> Result<S::Ok, S::Error> {
+            state.export().serialize(serializer)
+        }
 
-        let bytes = unsafe { CStr::from_ptr(name_ptr).to_bytes() };
-        let name: &str = str::from_utf8(bytes).unwrap(); // make sure the bytes are UTF-8
+        fn import_customer_state<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CustomerState<CURVE>, D::Error> {
+            CustomerStateExport::deserialize(deserializer).map(CustomerState::import)
+        }
 
-        let (channel_token, merch_state, channel_state) = bidirectional::init_merchant(rng, &mut channel_state, name);
+        fn export_pending_state<S: Serializer>(state: &Option<CustomerState<CURVE>>, serializer: S) -> Result<S::Ok, S::Error> {
+            state.as_ref().map(CustomerState::export).serialize(serializer)
+        }
 
-        let ser = ["{\'channel_token\':\'", serde_json::to_string(&channel_token).unwrap().as_str(), "\', \'merch_state\':\'", serde_json::to_string(&merch_state).unwrap().as_str(), "\', \'channel_state\':\'", serde_json::to_string(&channel_state).unwrap().as_str(), "\'}"].concat();
+        fn import_pending_state<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<CustomerState<CURVE>>, D::Error> {
+            Option::<CustomerStateExport<CURVE>>::deserialize(deserializer).map(|state| state.map(CustomerState::import))
+        }
 
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        fn into_handle<T>(object: T) -> *mut T {
+            Box::into_raw(Box::new(object))
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_init_customer(ser_channel_token: *mut c_char, balance_customer: i64, balance_merchant: i64, name_ptr: *const c_char) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        // Deserialize the channel token
-        let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
-        let mut channel_token = handle_errors!(channel_token_result);
-
-        // Deserialize the name
-        let bytes = unsafe { CStr::from_ptr(name_ptr).to_bytes() };
-        let name: &str = str::from_utf8(bytes).unwrap(); // make sure the bytes are UTF-8
-
-        // We change the channel state
-        let cust_state = bidirectional::init_customer(rng, &mut channel_token, balance_customer, balance_merchant, name);
-        let ser = ["{\'cust_state\':\'", serde_json::to_string(&cust_state).unwrap().as_str(), "\', \'channel_token\':\'", serde_json::to_string(&channel_token).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        fn free_handle<T>(handle: *mut T) {
+            if handle.is_null() { return; }
+            drop(unsafe { Box::from_raw(handle) });
+        }
 
-    // ESTABLISH
+        fn export_handle<T: Serialize>(handle: *const T) -> *mut c_char {
+            match unsafe { handle.as_ref() } {
+                Some(object) => CString::new(serialize_object(object)).unwrap().into_raw(),
+                None => ptr::null_mut(),
+            }
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_establish_customer_generate_proof(ser_channel_token: *mut c_char, ser_customer_state: *mut c_char) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        // Deserialize the channel token
-        let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
-        let mut channel_token = handle_errors!(channel_token_result);
-
-        // Deserialize the cust state
-        let cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_customer_state);
-        let mut cust_state = handle_errors!(cust_state_result);
-
-        let (com, com_proof) = bidirectional::establish_customer_generate_proof(rng, &mut channel_token, &mut cust_state);
-
-        let ser = ["{\'cust_state\':\'", serde_json::to_string(&cust_state).unwrap().as_str(),
-            "\', \'channel_token\':\'", serde_json::to_string(&channel_token).unwrap().as_str(),
-            "\', \'com\':\'", serde_json::to_string(&com).unwrap().as_str(),
-            "\', \'com_proof\':\'", serde_json::to_string(&com_proof).unwrap().as_str(),
-            "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        fn import_handle<T: DeserializeOwned>(ser: *const c_char) -> *mut T {
+            let result: ResultSerdeType<T> = deserialize_result_object(ser);
+            match result {
+                Ok(object) => into_handle(object),
+                Err(_) => ptr::null_mut(),
+            }
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_generate_channel_id(ser_channel_token: *mut c_char) -> *mut c_char {
-        // Deserialize the channel token
-        let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
-        let channel_token = handle_errors!(channel_token_result);
-
-        let id = channel_token.compute_channel_id();
-        let ser = ["{\'channel_id\':\'", serde_json::to_string(&id).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        fn name_arg(name_ptr: *const c_char) -> Option<String> {
+            str_arg(name_ptr).ok().map(|name| name.to_string())
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_establish_merchant_issue_close_token(ser_channel_state: *mut c_char, ser_com: *mut c_char, ser_com_proof: *mut c_char, ser_channel_id: *mut c_char, init_cust_bal: i64, init_merch_bal: i64, ser_merch_state: *mut c_char) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
+        // CHANNEL
+
+        #[no_mangle]
+        pub extern "C" fn bolt_channel_new(channel_name: *const c_char, third_party_support: u32) -> *mut BoltChannel {
+            catch_panic_or(ptr::null_mut(), || {
+                let name = match name_arg(channel_name) {
+                    Some(name) => name,
+                    None => return ptr::null_mut(),
+                };
+                into_handle(BoltChannel { state: ChannelState::<CURVE>::new(name, third_party_support > 1) })
+            })
+        }
 
-        // Deserialize the com proof
-        let com_result: ResultSerdeType<bidirectional::Commitment<CURVE>> = deserialize_result_object(ser_com);
-        let com = handle_errors!(com_result);
+        #[no_mangle]
+        pub extern "C" fn bolt_channel_free(channel: *mut BoltChannel) {
+            catch_panic_or((), || {
+                free_handle(channel)
+            })
+        }
 
-        // Deserialize the com proof
-        let com_proof_result: ResultSerdeType<bidirectional::CommitmentProof<CURVE>> = deserialize_result_object(ser_com_proof);
-        let com_proof = handle_errors!(com_proof_result);
+        #[no_mangle]
+        pub extern "C" fn bolt_channel_export(channel: *const BoltChannel) -> *mut c_char {
+            catch_panic(|| {
+                export_handle(channel)
+            })
+        }
 
-        // Deserialize the merchant state
-        let merch_state_result: ResultSerdeType<bidirectional::MerchantState<CURVE>> = deserialize_result_object(ser_merch_state);
-        let merch_state = handle_errors!(merch_state_result);
+        #[no_mangle]
+        pub extern "C" fn bolt_channel_import(ser_channel: *const c_char) -> *mut BoltChannel {
+            catch_panic_or(ptr::null_mut(), || {
+                import_handle(ser_channel)
+            })
+        }
 
-        // Deserialize the pk_c
-        let channel_id_result: ResultSerdeType<<CURVE as ScalarEngine>::Fr> = deserialize_result_object(ser_channel_id);
-        let channel_id_fr = handle_errors!(channel_id_result);
+        // MERCHANT
+
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_new(channel: *mut BoltChannel, name_ptr: *const c_char) -> *mut BoltMerchant {
+            catch_panic_or(ptr::null_mut(), || {
+                let rng = &mut rand::thread_rng();
+                let channel = match unsafe { channel.as_mut() } {
+                    Some(channel) => channel,
+                    None => return ptr::null_mut(),
+                };
+                let name = match name_arg(name_ptr) {
+                    Some(name) => name,
+                    None => return ptr::null_mut(),
+                };
+
+                let (channel_token, merch_state, merch_ledger, channel_state) = bidirectional::init_merchant(rng, &mut channel.state, &name);
+                channel.state = channel_state;
+                into_handle(BoltMerchant { state: merch_state, ledger: merch_ledger, channel_token })
+            })
+        }
 
-        let close_token = bolt_try!(bidirectional::establish_merchant_issue_close_token(rng, &channel_state, &com, &com_proof, &channel_id_fr, init_cust_bal, init_merch_bal, &merch_state));
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_free(merchant: *mut BoltMerchant) {
+            catch_panic_or((), || {
+                free_handle(merchant)
+            })
+        }
 
-        let ser = ["{\'close_token\':\'", serde_json::to_string(&close_token).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_export(merchant: *const BoltMerchant) -> *mut c_char {
+            catch_panic(|| {
+                let merchant = match unsafe { merchant.as_ref() } {
+                    Some(merchant) => merchant,
+                    None => return ptr::null_mut(),
+                };
+                let export = BoltMerchantExport {
+                    secrets: merchant.state.export_secrets(),
+                    ledger: merchant.ledger.clone(),
+                    channel_token: merchant.channel_token.clone(),
+                };
+                CString::new(serialize_object(&export)).unwrap().into_raw()
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_establish_merchant_issue_pay_token(ser_channel_state: *mut c_char, ser_com: *mut c_char, ser_merch_state: *mut c_char) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
+        /// Restores a merchant exported with `bolt_merchant_export`, whose secrets must match the
+        /// public params of the channel. Returns null otherwise.
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_import(channel: *const BoltChannel, ser_merchant: *const c_char) -> *mut BoltMerchant {
+            catch_panic_or(ptr::null_mut(), || {
+                let channel = match unsafe { channel.as_ref() } {
+                    Some(channel) => channel,
+                    None => return ptr::null_mut(),
+                };
+                let export_result: ResultSerdeType<BoltMerchantExport> = deserialize_result_object(ser_merchant);
+                let export = match export_result {
+                    Ok(export) => export,
+                    Err(_) => return ptr::null_mut(),
+                };
+                match MerchantState::from_secrets(export.secrets, &channel.state) {
+                    Ok(state) => into_handle(BoltMerchant { state, ledger: export.ledger, channel_token: export.channel_token }),
+                    Err(_) => ptr::null_mut(),
+                }
+            })
+        }
 
-        // Deserialize the commitment
-        let com_result: ResultSerdeType<bidirectional::Commitment<CURVE>> = deserialize_result_object(ser_com);
-        let com = handle_errors!(com_result);
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_channel_token(merchant: *const BoltMerchant) -> *mut c_char {
+            catch_panic(|| {
+                let merchant = handle_ref!(merchant);
+                json_response(json!({"channel_token": serialize_object(&merchant.channel_token)}))
+            })
+        }
 
-        // Deserialize the merchant state
-        let merch_state_result: ResultSerdeType<bidirectional::MerchantState<CURVE>> = deserialize_result_object(ser_merch_state);
-        let merch_state = handle_errors!(merch_state_result);
+        /// Returns the merchant ledger (revoked wallet keys, pay tokens and channel records),
+        /// which holds no secret keys and can be persisted apart from the merchant handle.
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_ledger(merchant: *const BoltMerchant) -> *mut c_char {
+            catch_panic(|| {
+                let merchant = handle_ref!(merchant);
+                json_response(json!({"merch_ledger": serialize_object(&merchant.ledger)}))
+            })
+        }
 
-        let pay_token = bidirectional::establish_merchant_issue_pay_token(rng, &channel_state, &com, &merch_state);
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_establish_issue_close_token(merchant: *mut BoltMerchant, channel: *const BoltChannel, ser_com: *const c_char, ser_com_proof: *const c_char, ser_channel_id: *const c_char, init_cust_bal: i64, init_merch_bal: i64) -> *mut c_char {
+            catch_panic(|| {
+                let rng = &mut rand::thread_rng();
+                let merchant = handle_mut!(merchant);
+                let channel = handle_ref!(channel);
 
-        let ser = ["{\'pay_token\':\'", serde_json::to_string(&pay_token).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+                let com_result: ResultSerdeType<bidirectional::Commitment<CURVE>> = deserialize_result_object(ser_com);
+                let com = handle_errors!(com_result);
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_verify_close_token(ser_channel_state: *mut c_char, ser_customer_state: *mut c_char, ser_close_token: *mut c_char) -> *mut c_char {
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let mut channel_state = handle_errors!(channel_state_result);
+                let com_proof_result: ResultSerdeType<bidirectional::CommitmentProof<CURVE>> = deserialize_result_object(ser_com_proof);
+                let com_proof = handle_errors!(com_proof_result);
 
-        // Deserialize the cust state
-        let cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_customer_state);
-        let mut cust_state = handle_errors!(cust_state_result);
+                let channel_id_result: ResultSerdeType<<CURVE as ScalarEngine>::Fr> = deserialize_result_object(ser_channel_id);
+                let channel_id_fr = handle_errors!(channel_id_result);
 
-        // Deserialize the close token
-        let close_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_close_token);
-        let close_token = handle_errors!(close_result);
+                let close_token = bolt_try!(bidirectional::establish_merchant_issue_close_token(rng, &channel.state, &com, &com_proof, &channel_id_fr, init_cust_bal, init_merch_bal, &merchant.state, &mut merchant.ledger));
+                json_response(json!({"close_token": serialize_object(&close_token)}))
+            })
+        }
 
-        let is_close_token_valid = cust_state.verify_close_token(&mut channel_state, &close_token);
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_establish_issue_pay_token(merchant: *const BoltMerchant, channel: *const BoltChannel, ser_com: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let rng = &mut rand::thread_rng();
+                let merchant = handle_ref!(merchant);
+                let channel = handle_ref!(channel);
 
-        let ser = ["{\'cust_state\':\'", serde_json::to_string(&cust_state).unwrap().as_str(),
-            "\', \'is_token_valid\':\'", serde_json::to_string(&is_close_token_valid).unwrap().as_str(),
-            "\', \'channel_state\':\'", serde_json::to_string(&channel_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+                let com_result: ResultSerdeType<bidirectional::Commitment<CURVE>> = deserialize_result_object(ser_com);
+                let com = handle_errors!(com_result);
 
+                let pay_token = bidirectional::establish_merchant_issue_pay_token(rng, &channel.state, &com, &merchant.state);
+                json_response(json!({"pay_token": serialize_object(&pay_token)}))
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_establish_customer_final(ser_channel_state: *mut c_char, ser_customer_state: *mut c_char, ser_pay_token: *mut c_char) -> *mut c_char {
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let mut channel_state = handle_errors!(channel_state_result);
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_pay_verify_payment_proof(merchant: *mut BoltMerchant, channel: *const BoltChannel, ser_pay_proof: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let rng = &mut rand::thread_rng();
+                let merchant = handle_mut!(merchant);
+                let channel = handle_ref!(channel);
 
-        // Deserialize the cust state
-        let cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_customer_state);
-        let mut cust_state = handle_errors!(cust_state_result);
+                let payment_result: ResultSerdeType<bidirectional::Payment<CURVE>> = deserialize_result_object(ser_pay_proof);
+                let payment = handle_errors!(payment_result);
 
-        // Deserialize the custdata
-        let pay_token_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_pay_token);
-        let pay_token = handle_errors!(pay_token_result);
+                let close_token = bidirectional::verify_payment_proof(rng, &channel.state, &payment, &merchant.state, &mut merchant.ledger);
+                json_response(json!({"close_token": serialize_object(&close_token)}))
+            })
+        }
 
-        let is_channel_established = bidirectional::establish_customer_final(&mut channel_state, &mut cust_state, &pay_token);
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_pay_verify_revoke_token(merchant: *mut BoltMerchant, ser_revoke_token: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let merchant = handle_mut!(merchant);
 
-        let ser = ["{\'cust_state\':\'", serde_json::to_string(&cust_state).unwrap().as_str(),
-            "\', \'is_established\':\'", serde_json::to_string(&is_channel_established).unwrap().as_str(),
-            "\', \'channel_state\':\'", serde_json::to_string(&channel_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+                let revoke_token_result: ResultSerdeType<bidirectional::RevokeToken> = deserialize_result_object(ser_revoke_token);
+                let revoke_token = handle_errors!(revoke_token_result);
 
-    // PAY
+                let pay_token = bolt_try!(bidirectional::verify_revoke_token(&revoke_token, &mut merchant.ledger));
+                json_response(json!({"pay_token": serialize_object(&pay_token)}))
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_pay_generate_payment_proof(ser_channel_state: *mut c_char, ser_customer_state: *mut c_char, amount: i64) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
-
-        // Deserialize the cust state
-        let cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_customer_state);
-        let cust_state = handle_errors!(cust_state_result);
-
-        // Generate the payment proof
-        let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &channel_state, &cust_state, amount);
-        // Serialize the results and return to caller
-        let ser = ["{\'payment\':\'", serde_json::to_string(&payment).unwrap().as_str(),
-            "\', \'cust_state\':\'", serde_json::to_string(&new_cust_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_merchant_close(merchant: *mut BoltMerchant, channel: *const BoltChannel, ser_channel_token: *const c_char, ser_cust_close: *const c_char, ser_address: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let merchant = handle_mut!(merchant);
+                let channel = handle_ref!(channel);
+
+                // the customer's channel token, which includes its public key
+                let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
+                let channel_token = handle_errors!(channel_token_result);
+
+                let cust_close_result: ResultSerdeType<bidirectional::ChannelcloseC<CURVE>> = deserialize_result_object(ser_cust_close);
+                let cust_close = handle_errors!(cust_close_result);
+
+                let address = match name_arg(ser_address) {
+                    Some(address) => address,
+                    None => return error_message(ErrorCode::InvalidInput, String::from("address is not valid UTF-8")),
+                };
+
+                let keys = bolt_try!(bidirectional::merchant_close(&channel.state, &channel_token, &cust_close, &mut merchant.ledger));
+                let merch_close = merchant.state.sign_revoke_message(address, &keys.revoke_token);
+                json_response(json!({
+                    "wpk": serialize_object(&keys.wpk),
+                    "merch_close": serialize_object(&merch_close)
+                }))
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_pay_verify_payment_proof(ser_channel_state: *mut c_char, ser_pay_proof: *mut c_char, ser_merch_state: *mut c_char) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
-
-        // Deserialize the payment proof
-        let payment_result: ResultSerdeType<bidirectional::Payment<CURVE>> = deserialize_result_object(ser_pay_proof);
-        let payment = handle_errors!(payment_result);
-
-        // Deserialize the merch state
-        let merch_state_result: ResultSerdeType<bidirectional::MerchantState<CURVE>> = deserialize_result_object(ser_merch_state);
-        let mut merch_state = handle_errors!(merch_state_result);
-
-        let close_token = bidirectional::verify_payment_proof(rng, &channel_state, &payment, &mut merch_state);
-        let ser = ["{\'close_token\':\'", serde_json::to_string(&close_token).unwrap().as_str(),
-            "\', \'merch_state\':\'", serde_json::to_string(&merch_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        // CUSTOMER
+
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_new(channel: *const BoltChannel, ser_channel_token: *const c_char, balance_customer: i64, balance_merchant: i64, name_ptr: *const c_char) -> *mut BoltCustomer {
+            catch_panic_or(ptr::null_mut(), || {
+                let rng = &mut rand::thread_rng();
+                let channel = match unsafe { channel.as_ref() } {
+                    Some(channel) => channel,
+                    None => return ptr::null_mut(),
+                };
+                let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
+                let mut channel_token = match channel_token_result {
+                    Ok(channel_token) => channel_token,
+                    Err(_) => return ptr::null_mut(),
+                };
+                let name = match name_arg(name_ptr) {
+                    Some(name) => name,
+                    None => return ptr::null_mut(),
+                };
+
+                // rejects channels with invalid range proof parameters
+                let cust_state = match bidirectional::init_customer(rng, &channel.state, &mut channel_token, balance_customer, balance_merchant, &name) {
+                    Ok(Some(cust_state)) => cust_state,
+                    _ => return ptr::null_mut(),
+                };
+                into_handle(BoltCustomer { state: cust_state, channel_token, pending_state: None })
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_pay_verify_multiple_payment_proofs(ser_channel_state: *mut c_char, ser_sender_pay_proof: *mut c_char, ser_receiver_pay_proof: *mut c_char, ser_merch_state: *mut c_char) -> *mut c_char {
-        let rng = &mut rand::thread_rng();
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
-
-        // Deserialize the payment proofs
-        let sender_payment_result: ResultSerdeType<bidirectional::Payment<CURVE>> = deserialize_result_object(ser_sender_pay_proof);
-        let sender_payment = handle_errors!(sender_payment_result);
-
-        let receiver_payment_result: ResultSerdeType<bidirectional::Payment<CURVE>> = deserialize_result_object(ser_receiver_pay_proof);
-        let receiver_payment = handle_errors!(receiver_payment_result);
-
-        // Deserialize the merch state
-        let merch_state_result: ResultSerdeType<bidirectional::MerchantState<CURVE>> = deserialize_result_object(ser_merch_state);
-        let mut merch_state = handle_errors!(merch_state_result);
-
-        let close_token_result = bidirectional::verify_multiple_payment_proofs(rng, &channel_state, &sender_payment, &receiver_payment, &mut merch_state);
-        let (sender_close_token, receiver_cond_close_token) = handle_errors!(close_token_result).unwrap();
-        let ser = ["{\'sender_close_token\':\'", serde_json::to_string(&sender_close_token).unwrap().as_str(),
-            "\', \'receiver_cond_close_token\':\'", serde_json::to_string(&receiver_cond_close_token).unwrap().as_str(),
-            "\', \'merch_state\':\'", serde_json::to_string(&merch_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_free(customer: *mut BoltCustomer) {
+            catch_panic_or((), || {
+                free_handle(customer)
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_pay_generate_revoke_token(ser_channel_state: *mut c_char, ser_cust_state: *mut c_char, ser_new_cust_state: *mut c_char, ser_close_token: *mut c_char) -> *mut c_char {
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
-
-        // Deserialize the cust state
-        let cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_cust_state);
-        let mut cust_state = handle_errors!(cust_state_result);
-
-        // Deserialize the cust state
-        let new_cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_new_cust_state);
-        let new_cust_state = handle_errors!(new_cust_state_result);
-
-        // Deserialize the close token
-        let close_token_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_close_token);
-        let close_token = handle_errors!(close_token_result);
-
-        let revoke_token = bidirectional::generate_revoke_token(&channel_state, &mut cust_state, new_cust_state, &close_token);
-        let ser = ["{\'revoke_token\':\'", serde_json::to_string(&revoke_token).unwrap().as_str(),
-            "\', \'cust_state\':\'", serde_json::to_string(&cust_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_export(customer: *const BoltCustomer) -> *mut c_char {
+            catch_panic(|| {
+                export_handle(customer)
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_pay_verify_revoke_token(ser_revoke_token: *mut c_char, ser_merch_state: *mut c_char) -> *mut c_char {
-        // Deserialize the revoke token
-        let revoke_token_result: ResultSerdeType<bidirectional::RevokeToken> = deserialize_result_object(ser_revoke_token);
-        let revoke_token = handle_errors!(revoke_token_result);
-
-        // Deserialize the cust state
-        let merch_state_result: ResultSerdeType<bidirectional::MerchantState<CURVE>> = deserialize_result_object(ser_merch_state);
-        let mut merch_state = handle_errors!(merch_state_result);
-
-        // send revoke token and get pay-token in response
-        let pay_token_result = bidirectional::verify_revoke_token(&revoke_token, &mut merch_state);
-        let pay_token = handle_errors!(pay_token_result);
-
-        let ser = ["{\'pay_token\':\'", serde_json::to_string(&pay_token.unwrap()).unwrap().as_str(),
-            "\', \'merch_state\':\'", serde_json::to_string(&merch_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_import(ser_customer: *const c_char) -> *mut BoltCustomer {
+            catch_panic_or(ptr::null_mut(), || {
+                import_handle(ser_customer)
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_pay_verify_multiple_revoke_tokens(ser_sender_revoke_token: *mut c_char, ser_receiver_revoke_token: *mut c_char, ser_merch_state: *mut c_char) -> *mut c_char {
-        // Deserialize the revoke tokens
-        let sender_revoke_token_result: ResultSerdeType<bidirectional::RevokeToken> = deserialize_result_object(ser_sender_revoke_token);
-        let sender_revoke_token = handle_errors!(sender_revoke_token_result);
-
-        let receiver_revoke_token_result: ResultSerdeType<bidirectional::RevokeToken> = deserialize_result_object(ser_receiver_revoke_token);
-        let receiver_revoke_token = handle_errors!(receiver_revoke_token_result);
-
-        // Deserialize the cust state
-        let merch_state_result: ResultSerdeType<bidirectional::MerchantState<CURVE>> = deserialize_result_object(ser_merch_state);
-        let mut merch_state = handle_errors!(merch_state_result);
-
-        // send revoke token and get pay-token in response
-        let pay_token_result = bidirectional::verify_multiple_revoke_tokens(&sender_revoke_token, &receiver_revoke_token, &mut merch_state);
-        let (sender_pay_token, receiver_pay_token) = handle_errors!(pay_token_result).unwrap();
-
-        let ser = ["{\'sender_pay_token\':\'", serde_json::to_string(&sender_pay_token).unwrap().as_str(),
-            "\', \'receiver_pay_token\':\'", serde_json::to_string(&receiver_pay_token).unwrap().as_str(),
-            "\', \'merch_state\':\'", serde_json::to_string(&merch_state).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_establish_generate_proof(customer: *const BoltCustomer) -> *mut c_char {
+            catch_panic(|| {
+                let rng = &mut rand::thread_rng();
+                let customer = handle_ref!(customer);
+
+                let (com, com_proof) = bidirectional::establish_customer_generate_proof(rng, &customer.channel_token, &customer.state);
+                json_response(json!({
+                    "channel_token": serialize_object(&customer.channel_token),
+                    "channel_id": serialize_object(&customer.state.get_wallet().channelId),
+                    "com": serialize_object(&com),
+                    "com_proof": serialize_object(&com_proof)
+                }))
+            })
+        }
 
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_establish_verify_close_token(customer: *mut BoltCustomer, channel: *const BoltChannel, ser_close_token: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let customer = handle_mut!(customer);
+                let channel = handle_ref!(channel);
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_pay_verify_payment_token(ser_channel_state: *mut c_char, ser_cust_state: *mut c_char, ser_pay_token: *mut c_char) -> *mut c_char {
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
-
-        // Deserialize the cust state
-        let cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_cust_state);
-        let mut cust_state = handle_errors!(cust_state_result);
-
-        // Deserialize the pay token
-        let pay_token_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_pay_token);
-        let pay_token = handle_errors!(pay_token_result);
-
-        // verify the pay token and update internal state
-        let is_pay_valid = cust_state.verify_pay_token(&channel_state, &pay_token);
-        let ser = ["{\'cust_state\':\'", serde_json::to_string(&cust_state).unwrap().as_str(),
-            "\', \'is_pay_valid\':\'", serde_json::to_string(&is_pay_valid).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+                let close_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_close_token);
+                let close_token = handle_errors!(close_result);
 
-    // CLOSE
+                let is_token_valid = customer.state.verify_close_token(&channel.state, &close_token);
+                json_response(json!({"is_token_valid": is_token_valid}))
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_customer_close(ser_channel_state: *mut c_char, ser_cust_state: *mut c_char) -> *mut c_char {
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
-
-        // Deserialize the cust state
-        let cust_state_result: ResultSerdeType<bidirectional::CustomerState<CURVE>> = deserialize_result_object(ser_cust_state);
-        let cust_state = handle_errors!(cust_state_result);
-
-        let cust_close = bidirectional::customer_close(&channel_state, &cust_state);
-        let ser = ["{\'cust_close\':\'", serde_json::to_string(&cust_close).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_establish_final(customer: *mut BoltCustomer, channel: *mut BoltChannel, ser_pay_token: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let customer = handle_mut!(customer);
+                let channel = handle_mut!(channel);
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_merchant_close(ser_channel_state: *mut c_char, ser_channel_token: *mut c_char, ser_address: *const c_char, ser_cust_close: *mut c_char, ser_merch_state: *mut c_char) -> *mut c_char {
-        // Deserialize the channel state
-        let channel_state_result: ResultSerdeType<bidirectional::ChannelState<CURVE>> = deserialize_result_object(ser_channel_state);
-        let channel_state = handle_errors!(channel_state_result);
-
-        // Deserialize the channel token
-        let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
-        let channel_token = handle_errors!(channel_token_result);
-
-        // Deserialize the customer close structure
-        let cust_close_result: ResultSerdeType<bidirectional::ChannelcloseC<CURVE>> = deserialize_result_object(ser_cust_close);
-        let cust_close = handle_errors!(cust_close_result);
-
-        // Deserialize the merch state
-        let merch_state_result: ResultSerdeType<bidirectional::MerchantState<CURVE>> = deserialize_result_object(ser_merch_state);
-        let merch_state = handle_errors!(merch_state_result);
-
-        // Deserialize the destination address as a string
-        let ser_addr_bytes = unsafe { CStr::from_ptr(ser_address).to_bytes() };
-        let address: &str = str::from_utf8(ser_addr_bytes).unwrap(); // make sure the bytes are UTF-8
-
-        let option = bidirectional::merchant_close(&channel_state, &channel_token, &cust_close, &merch_state);
-        let keys = match option {
-            Ok(n) => n.unwrap(),
-            Err(err) => return error_message(err),
-        };
+                let pay_token_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_pay_token);
+                let pay_token = handle_errors!(pay_token_result);
 
-        let merch_close: bidirectional::ChannelcloseM = merch_state.sign_revoke_message(address.to_string(), &keys.revoke_token);
+                let is_established = bidirectional::establish_customer_final(&mut channel.state, &mut customer.state, &pay_token);
+                json_response(json!({"is_established": is_established}))
+            })
+        }
 
-        let ser = ["{\'wpk\':\'", serde_json::to_string(&keys.wpk).unwrap().as_str(),
-            "\', \'merch_close\':\'", serde_json::to_string(&merch_close).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
-    }
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_pay_generate_payment_proof(customer: *mut BoltCustomer, channel: *const BoltChannel, amount: i64) -> *mut c_char {
+            catch_panic(|| {
+                let rng = &mut rand::thread_rng();
+                let customer = handle_mut!(customer);
+                let channel = handle_ref!(channel);
+
+                // the new state is kept until the merchant's close token is received
+                let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &channel.state, &customer.state, amount);
+                customer.pending_state = Some(new_cust_state);
+                json_response(json!({"payment": serialize_object(&payment)}))
+            })
+        }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_wtp_verify_cust_close_message(ser_channel_token: *mut c_char, ser_wpk: *mut c_char, ser_close_msg: *mut c_char, ser_close_token: *mut c_char) -> *mut c_char {
-        // Deserialize the channel token
-        let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
-        let channel_token = handle_errors!(channel_token_result);
-
-        // Deserialize the wpk
-        let wpk_result: ResultSerdeType<secp256k1::PublicKey> = deserialize_result_object(ser_wpk);
-        let wpk = handle_errors!(wpk_result);
-
-        // Deserialize the close wallet
-        let close_msg_result: ResultSerdeType<bidirectional::Wallet<CURVE>> = deserialize_result_object(ser_close_msg);
-        let close_msg = handle_errors!(close_msg_result);
-
-        // Deserialize the close token
-        let close_token_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_close_token);
-        let close_token = handle_errors!(close_token_result);
-
-        // check the signatures
-        let token_valid = bidirectional::wtp_verify_cust_close_message(&channel_token, &wpk, &close_msg, &close_token);
-        let ser = ["{\"result\":\"", serde_json::to_string(&token_valid).unwrap().as_str(), "\"}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_pay_generate_revoke_token(customer: *mut BoltCustomer, channel: *const BoltChannel, ser_close_token: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let customer = handle_mut!(customer);
+                let channel = handle_ref!(channel);
+
+                let close_token_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_close_token);
+                let close_token = handle_errors!(close_token_result);
+
+                let new_cust_state = match customer.pending_state {
+                    Some(ref new_cust_state) => new_cust_state.clone(),
+                    None => return error_message(ErrorCode::ProtocolFailure, String::from("no payment in progress")),
+                };
+                // keep the pending state until the close token checks out, so the customer can retry
+                let mut updated_state = customer.state.clone();
+                if !updated_state.update(new_cust_state.clone()) || !updated_state.verify_close_token(&channel.state, &close_token) {
+                    return error_message(ErrorCode::ProtocolFailure, String::from("could not verify the close token"));
+                }
+                customer.pending_state = None;
+                let revoke_token = bidirectional::generate_revoke_token(&channel.state, &mut customer.state, new_cust_state, &close_token);
+                json_response(json!({"revoke_token": serialize_object(&revoke_token)}))
+            })
+        }
+
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_pay_verify_payment_token(customer: *mut BoltCustomer, channel: *const BoltChannel, ser_pay_token: *const c_char) -> *mut c_char {
+            catch_panic(|| {
+                let customer = handle_mut!(customer);
+                let channel = handle_ref!(channel);
+
+                let pay_token_result: ResultSerdeType<bidirectional::Signature<CURVE>> = deserialize_result_object(ser_pay_token);
+                let pay_token = handle_errors!(pay_token_result);
+
+                let is_pay_valid = customer.state.verify_pay_token(&channel.state, &pay_token);
+                json_response(json!({"is_pay_valid": is_pay_valid}))
+            })
+        }
+
+        #[no_mangle]
+        pub extern "C" fn bolt_customer_close(customer: *const BoltCustomer, channel: *const BoltChannel) -> *mut c_char {
+            catch_panic(|| {
+                let customer = handle_ref!(customer);
+                let channel = handle_ref!(channel);
+
+                let cust_close = bidirectional::customer_close(&channel.state, &customer.state);
+                json_response(json!({"cust_close": serialize_object(&cust_close)}))
+            })
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+
+            fn parse_response(response: *mut c_char) -> Value {
+                let ser = unsafe { CStr::from_ptr(response) }.to_str().unwrap().to_string();
+                ffishim_free_string(response);
+                serde_json::from_str(&ser).unwrap()
+            }
+
+            fn field(response: &Value, key: &str) -> CString {
+                assert!(response.get("error").is_none(), "unexpected error: {}", response);
+                CString::new(response[key].as_str().unwrap()).unwrap()
+            }
+
+            // export a handle, release it and import a fresh copy
+            fn reload<T: Serialize + DeserializeOwned>(handle: *mut T) -> *mut T {
+                let ser = export_handle(handle);
+                free_handle(handle);
+                let imported = import_handle(ser);
+                ffishim_free_string(ser);
+                assert!(!imported.is_null());
+                imported
+            }
+
+            // export a merchant, release it and restore it against its channel
+            fn reload_merchant(merchant: *mut BoltMerchant, channel: *const BoltChannel) -> *mut BoltMerchant {
+                let ser = bolt_merchant_export(merchant);
+                bolt_merchant_free(merchant);
+                let imported = bolt_merchant_import(channel, ser);
+                ffishim_free_string(ser);
+                assert!(!imported.is_null());
+                imported
+            }
+
+            #[test]
+            fn handle_api_establish_pay_and_close_works() {
+                let b0_cust = 100;
+                let b0_merch = 10;
+
+                let channel_name = CString::new("Channel A -> B").unwrap();
+                let merch_channel = bolt_channel_new(channel_name.as_ptr(), 0);
+                let merch_name = CString::new("Bob").unwrap();
+                let merchant = bolt_merchant_new(merch_channel, merch_name.as_ptr());
+                assert!(!merchant.is_null());
+
+                // the customer starts from a copy of the merchant's channel state
+                let ser_channel = bolt_channel_export(merch_channel);
+                let cust_channel = bolt_channel_import(ser_channel);
+                ffishim_free_string(ser_channel);
+
+                let r = parse_response(bolt_merchant_channel_token(merchant));
+                let channel_token = field(&r, "channel_token");
+                let cust_name = CString::new("Alice").unwrap();
+                let customer = bolt_customer_new(cust_channel, channel_token.as_ptr(), b0_cust, b0_merch, cust_name.as_ptr());
+                assert!(!customer.is_null());
+
+                let r = parse_response(bolt_customer_establish_generate_proof(customer));
+                let channel_token = field(&r, "channel_token");
+                let (channel_id, com, com_proof) = (field(&r, "channel_id"), field(&r, "com"), field(&r, "com_proof"));
+
+                let r = parse_response(bolt_merchant_establish_issue_close_token(merchant, merch_channel, com.as_ptr(), com_proof.as_ptr(), channel_id.as_ptr(), b0_cust, b0_merch));
+                let close_token = field(&r, "close_token");
+                let r = parse_response(bolt_customer_establish_verify_close_token(customer, cust_channel, close_token.as_ptr()));
+                assert_eq!(r["is_token_valid"], true);
+
+                let r = parse_response(bolt_merchant_establish_issue_pay_token(merchant, merch_channel, com.as_ptr()));
+                let pay_token = field(&r, "pay_token");
+                let r = parse_response(bolt_customer_establish_final(customer, cust_channel, pay_token.as_ptr()));
+                assert_eq!(r["is_established"], true);
+
+                // both parties persist and restore their state between protocol runs
+                let merchant = reload_merchant(merchant, merch_channel);
+                let customer = reload(customer);
+
+                let r = parse_response(bolt_customer_pay_generate_payment_proof(customer, cust_channel, 10));
+                let payment = field(&r, "payment");
+                let stale_close_token = close_token;
+                let r = parse_response(bolt_merchant_pay_verify_payment_proof(merchant, merch_channel, payment.as_ptr()));
+                let close_token = field(&r, "close_token");
+
+                // a close token for the old state is rejected and the payment can still be completed
+                let r = parse_response(bolt_customer_pay_generate_revoke_token(customer, cust_channel, stale_close_token.as_ptr()));
+                assert_eq!(r["error"]["code"], ErrorCode::ProtocolFailure as i32);
+
+                let r = parse_response(bolt_customer_pay_generate_revoke_token(customer, cust_channel, close_token.as_ptr()));
+                let revoke_token = field(&r, "revoke_token");
+                let r = parse_response(bolt_merchant_pay_verify_revoke_token(merchant, revoke_token.as_ptr()));
+                let pay_token = field(&r, "pay_token");
+                let r = parse_response(bolt_customer_pay_verify_payment_token(customer, cust_channel, pay_token.as_ptr()));
+                assert_eq!(r["is_pay_valid"], true);
+
+                // the ledger records the channel and the revoked wallet key
+                let r = parse_response(bolt_merchant_ledger(merchant));
+                let merch_ledger: Value = serde_json::from_str(field(&r, "merch_ledger").to_str().unwrap()).unwrap();
+                assert_eq!(merch_ledger["channels"].as_object().unwrap().len(), 1);
+                assert_eq!(merch_ledger["keys"].as_object().unwrap().len(), 1);
+
+                // a revoke token cannot be generated without a payment in progress
+                let r = parse_response(bolt_customer_pay_generate_revoke_token(customer, cust_channel, close_token.as_ptr()));
+                assert_eq!(r["error"]["code"], ErrorCode::ProtocolFailure as i32);
+
+                let r = parse_response(bolt_customer_close(customer, cust_channel));
+                let cust_close = field(&r, "cust_close");
+                let address = CString::new("11".repeat(32)).unwrap();
+                let r = parse_response(bolt_merchant_close(merchant, merch_channel, channel_token.as_ptr(), cust_close.as_ptr(), address.as_ptr()));
+                assert_eq!(r["error"]["code"], ErrorCode::ProtocolFailure as i32);
+
+                // a valid close marks the channel as closed in the ledger
+                let r = parse_response(bolt_merchant_ledger(merchant));
+                let merch_ledger: Value = serde_json::from_str(field(&r, "merch_ledger").to_str().unwrap()).unwrap();
+                assert!(merch_ledger["channels"].as_object().unwrap().values().all(|channel| channel["closed"] == true));
+
+                bolt_customer_free(customer);
+                bolt_merchant_free(merchant);
+                bolt_channel_free(cust_channel);
+                bolt_channel_free(merch_channel);
+            }
+
+            #[test]
+            fn handle_api_rejects_null_and_malformed_input() {
+                let r = parse_response(bolt_customer_close(ptr::null(), ptr::null()));
+                assert_eq!(r["error"]["code"], ErrorCode::InvalidInput as i32);
+
+                let bad_state = CString::new("{}").unwrap();
+                let channel_name = CString::new("Channel A -> B").unwrap();
+                let channel = bolt_channel_new(channel_name.as_ptr(), 0);
+                assert!(bolt_merchant_import(channel, bad_state.as_ptr()).is_null());
+                assert!(bolt_channel_export(ptr::null()).is_null());
+                bolt_channel_free(ptr::null_mut());
+
+                // a merchant is not restored against a channel with other public params
+                let merch_name = CString::new("Bob").unwrap();
+                let merchant = bolt_merchant_new(channel, merch_name.as_ptr());
+                let ser_merchant = bolt_merchant_export(merchant);
+                let other_channel = bolt_channel_new(channel_name.as_ptr(), 0);
+                let other_merchant = bolt_merchant_new(other_channel, merch_name.as_ptr());
+                assert!(bolt_merchant_import(other_channel, ser_merchant).is_null());
+                assert!(bolt_merchant_import(ptr::null(), ser_merchant).is_null());
+                ffishim_free_string(ser_merchant);
+
+                bolt_merchant_free(other_merchant);
+                bolt_merchant_free(merchant);
+                bolt_channel_free(other_channel);
+                bolt_channel_free(channel);
+            }
+
+            #[test]
+            fn handle_api_returns_error_on_panic() {
+                let channel_name = CString::new("Channel A -> B").unwrap();
+                let channel = bolt_channel_new(channel_name.as_ptr(), 0);
+                let merch_name = CString::new("Bob").unwrap();
+                let merchant = bolt_merchant_new(channel, merch_name.as_ptr());
+
+                let r = parse_response(bolt_merchant_channel_token(merchant));
+                let channel_token = field(&r, "channel_token");
+                let cust_name = CString::new("Alice").unwrap();
+                let customer = bolt_customer_new(channel, channel_token.as_ptr(), 100, 10, cust_name.as_ptr());
+
+                // customer_close panics if the channel has not been established
+                let r = parse_response(bolt_customer_close(customer, channel));
+                assert_eq!(r["error"]["code"], ErrorCode::Panic as i32);
+
+                bolt_customer_free(customer);
+                bolt_merchant_free(merchant);
+                bolt_channel_free(channel);
+            }
+        }
     }
 
-    #[no_mangle]
-    pub extern fn ffishim_bls12_wtp_verify_merch_close_message(ser_channel_token: *mut c_char, ser_wpk: *mut c_char, ser_merch_close: *mut c_char) -> *mut c_char {
-        // Deserialize the channel token
-        let channel_token_result: ResultSerdeType<bidirectional::ChannelToken<CURVE>> = deserialize_result_object(ser_channel_token);
-        let channel_token = handle_errors!(channel_token_result);
-
-        // Deserialize the wpk
-        let wpk_result: ResultSerdeType<secp256k1::PublicKey> = deserialize_result_object(ser_wpk);
-        let wpk = handle_errors!(wpk_result);
-
-        // Deserialize the merch close
-        //let revoke_token: secp256k1::Signature = deserialize_object(ser_revoke_token);
-        let merch_close_result: ResultSerdeType<bidirectional::ChannelcloseM> = deserialize_result_object(ser_merch_close);
-        let merch_close = handle_errors!(merch_close_result);
-
-        let revoke_token_valid = bidirectional::wtp_verify_revoke_message(&wpk, &merch_close.revoke.unwrap());
-        let merch_close_valid = bidirectional::wtp_verify_merch_close_message(&channel_token, &merch_close);
-        let token_valid = revoke_token_valid && merch_close_valid;
-
-        let ser = ["{\'result\':\'", serde_json::to_string(&token_valid).unwrap().as_str(), "\'}"].concat();
-        let cser = CString::new(ser).unwrap();
-        cser.into_raw()
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // Parses an FFI response with a strict JSON parser and releases the string
+        fn parse_response(response: *mut c_char) -> Value {
+            let ser = unsafe { CStr::from_ptr(response) }.to_str().unwrap().to_string();
+            ffishim_free_string(response);
+            serde_json::from_str(&ser).expect("FFI response is not valid JSON")
+        }
+
+        fn field(response: &Value, key: &str) -> CString {
+            assert!(response.get("error").is_none(), "unexpected error: {}", response);
+            CString::new(response[key].as_str().unwrap()).unwrap()
+        }
+
+        macro_rules! ffishim_curve_tests {
+            ($curve:ident) => { paste! {
+                #[test]
+                fn [<ffishim_ $curve _responses_are_valid_json>]() {
+                    let b0_cust = 100;
+                    let b0_merch = 10;
+
+                    let channel_name = CString::new("Channel A -> B").unwrap();
+                    let r = parse_response([<ffishim_ $curve _channel_setup>](channel_name.as_ptr(), 0));
+                    let channel_state = field(&r, "channel_state");
+
+                    let merch_name = CString::new("Bob").unwrap();
+                    let r = parse_response([<ffishim_ $curve _init_merchant>](channel_state.as_ptr(), merch_name.as_ptr()));
+                    let (channel_token, merch_secrets, merch_ledger, channel_state) = (field(&r, "channel_token"), field(&r, "merch_secrets"), field(&r, "merch_ledger"), field(&r, "channel_state"));
+                    // the serialized merchant state omits the secrets, which are loaded once
+                    let secrets: Value = serde_json::from_str(merch_secrets.to_str().unwrap()).unwrap();
+                    assert!(!field(&r, "merch_state").to_str().unwrap().contains(&secrets["sk"].to_string()));
+                    let merch_state = [<ffishim_ $curve _load_merchant>](merch_secrets.as_ptr(), channel_state.as_ptr());
+                    assert!(!merch_state.is_null());
+
+                    let cust_name = CString::new("Alice").unwrap();
+                    let r = parse_response([<ffishim_ $curve _init_customer>](channel_state.as_ptr(), channel_token.as_ptr(), b0_cust, b0_merch, cust_name.as_ptr()));
+                    let (channel_token, cust_state) = (field(&r, "channel_token"), field(&r, "cust_state"));
+
+                    // establish
+                    let r = parse_response([<ffishim_ $curve _establish_customer_generate_proof>](channel_token.as_ptr(), cust_state.as_ptr()));
+                    let (channel_token, cust_state) = (field(&r, "channel_token"), field(&r, "cust_state"));
+                    let (com, com_proof) = (field(&r, "com"), field(&r, "com_proof"));
+
+                    let r = parse_response([<ffishim_ $curve _generate_channel_id>](channel_token.as_ptr()));
+                    let channel_id = field(&r, "channel_id");
+
+                    let r = parse_response([<ffishim_ $curve _establish_merchant_issue_close_token>](channel_state.as_ptr(), com.as_ptr(), com_proof.as_ptr(), channel_id.as_ptr(), b0_cust, b0_merch, merch_state, merch_ledger.as_ptr()));
+                    let (close_token, merch_ledger) = (field(&r, "close_token"), field(&r, "merch_ledger"));
+
+                    let r = parse_response([<ffishim_ $curve _verify_close_token>](channel_state.as_ptr(), cust_state.as_ptr(), close_token.as_ptr()));
+                    assert_eq!(r["is_token_valid"], true);
+                    let (channel_state, cust_state) = (field(&r, "channel_state"), field(&r, "cust_state"));
+
+                    let r = parse_response([<ffishim_ $curve _establish_merchant_issue_pay_token>](channel_state.as_ptr(), com.as_ptr(), merch_state));
+                    let pay_token = field(&r, "pay_token");
+
+                    let r = parse_response([<ffishim_ $curve _establish_customer_final>](channel_state.as_ptr(), cust_state.as_ptr(), pay_token.as_ptr()));
+                    assert_eq!(r["is_established"], true);
+                    let (channel_state, cust_state) = (field(&r, "channel_state"), field(&r, "cust_state"));
+
+                    let r = parse_response([<ffishim_ $curve _customer_close>](channel_state.as_ptr(), cust_state.as_ptr()));
+                    let old_cust_close = field(&r, "cust_close");
+
+                    // pay
+                    let r = parse_response([<ffishim_ $curve _pay_generate_payment_proof>](channel_state.as_ptr(), cust_state.as_ptr(), 10));
+                    let (payment, new_cust_state) = (field(&r, "payment"), field(&r, "cust_state"));
+
+                    let r = parse_response([<ffishim_ $curve _pay_verify_payment_proof>](channel_state.as_ptr(), payment.as_ptr(), merch_state, merch_ledger.as_ptr()));
+                    let (close_token, merch_ledger) = (field(&r, "close_token"), field(&r, "merch_ledger"));
+
+                    let r = parse_response([<ffishim_ $curve _pay_generate_revoke_token>](channel_state.as_ptr(), cust_state.as_ptr(), new_cust_state.as_ptr(), close_token.as_ptr()));
+                    let (revoke_token, cust_state) = (field(&r, "revoke_token"), field(&r, "cust_state"));
+
+                    let r = parse_response([<ffishim_ $curve _pay_verify_revoke_token>](revoke_token.as_ptr(), merch_ledger.as_ptr()));
+                    let (pay_token, merch_ledger) = (field(&r, "pay_token"), field(&r, "merch_ledger"));
+
+                    let r = parse_response([<ffishim_ $curve _pay_verify_payment_token>](channel_state.as_ptr(), cust_state.as_ptr(), pay_token.as_ptr()));
+                    assert_eq!(r["is_pay_valid"], true);
+                    let cust_state = field(&r, "cust_state");
+
+                    // close
+                    let r = parse_response([<ffishim_ $curve _customer_close>](channel_state.as_ptr(), cust_state.as_ptr()));
+                    let cust_close = field(&r, "cust_close");
+
+                    // closing on the latest state is not a dispute, so the merchant gets a structured error
+                    let address = CString::new("11".repeat(32)).unwrap();
+                    let r = parse_response([<ffishim_ $curve _merchant_close>](channel_state.as_ptr(), channel_token.as_ptr(), address.as_ptr(), cust_close.as_ptr(), merch_state, merch_ledger.as_ptr()));
+                    assert_eq!(r["error"]["code"], ErrorCode::ProtocolFailure as i32);
+                    assert!(r["error"]["message"].is_string());
+                    // the channel is marked as closed, so the ledger comes back with the error
+                    let merch_ledger = CString::new(r["merch_ledger"].as_str().unwrap()).unwrap();
+                    let ledger: Value = serde_json::from_str(merch_ledger.to_str().unwrap()).unwrap();
+                    assert!(ledger["channels"].as_object().unwrap().values().all(|channel| channel["closed"] == true));
+
+                    let r = parse_response([<ffishim_ $curve _merchant_close>](channel_state.as_ptr(), channel_token.as_ptr(), address.as_ptr(), old_cust_close.as_ptr(), merch_state, merch_ledger.as_ptr()));
+                    let (wpk, merch_close) = (field(&r, "wpk"), field(&r, "merch_close"));
+
+                    let r = parse_response([<ffishim_ $curve _wtp_verify_merch_close_message>](channel_token.as_ptr(), wpk.as_ptr(), merch_close.as_ptr()));
+                    assert_eq!(r["result"], true);
+
+                    let mut no_revoke: Value = serde_json::from_str(merch_close.to_str().unwrap()).unwrap();
+                    no_revoke["revoke"] = Value::Null;
+                    let no_revoke = CString::new(no_revoke.to_string()).unwrap();
+                    let r = parse_response([<ffishim_ $curve _wtp_verify_merch_close_message>](channel_token.as_ptr(), wpk.as_ptr(), no_revoke.as_ptr()));
+                    assert_eq!(r["result"], false);
+
+                    let cust_close: Value = serde_json::from_str(cust_close.to_str().unwrap()).unwrap();
+                    let cust_wpk = CString::new(cust_close["wpk"].to_string()).unwrap();
+                    let close_msg = CString::new(cust_close["message"].to_string()).unwrap();
+                    let cust_close_token = CString::new(cust_close["signature"].to_string()).unwrap();
+                    let r = parse_response([<ffishim_ $curve _wtp_verify_cust_close_message>](channel_token.as_ptr(), cust_wpk.as_ptr(), close_msg.as_ptr(), cust_close_token.as_ptr()));
+                    assert!(r["result"].is_boolean());
+
+                    let r = parse_response([<ffishim_ $curve _wtp_check_wpk>](wpk.as_ptr()));
+                    assert_eq!(r["result"], true);
+
+                    [<ffishim_ $curve _free_merchant>](merch_state);
+                }
+
+                #[test]
+                fn [<ffishim_ $curve _load_merchant_rejects_mismatched_secrets>]() {
+                    let channel_name = CString::new("Channel A -> B").unwrap();
+                    let r = parse_response([<ffishim_ $curve _channel_setup>](channel_name.as_ptr(), 0));
+                    let channel_state = field(&r, "channel_state");
+                    let merch_name = CString::new("Bob").unwrap();
+                    let r = parse_response([<ffishim_ $curve _init_merchant>](channel_state.as_ptr(), merch_name.as_ptr()));
+                    let merch_secrets = field(&r, "merch_secrets");
+                    let r = parse_response([<ffishim_ $curve _init_merchant>](channel_state.as_ptr(), merch_name.as_ptr()));
+                    let other_channel_state = field(&r, "channel_state");
+
+                    // the secrets do not match the params of another merchant, nor of an uninitialized channel
+                    assert!([<ffishim_ $curve _load_merchant>](merch_secrets.as_ptr(), other_channel_state.as_ptr()).is_null());
+                    assert!([<ffishim_ $curve _load_merchant>](merch_secrets.as_ptr(), channel_state.as_ptr()).is_null());
+                    let bad_secrets = CString::new("{}").unwrap();
+                    assert!([<ffishim_ $curve _load_merchant>](bad_secrets.as_ptr(), other_channel_state.as_ptr()).is_null());
+                    [<ffishim_ $curve _free_merchant>](ptr::null_mut());
+                }
+
+                #[test]
+                fn [<ffishim_ $curve _invalid_input_returns_error_object>]() {
+                    let bad_channel_state = CString::new("{'channel_state': 'not json'}").unwrap();
+                    let merch_name = CString::new("Bob").unwrap();
+                    let r = parse_response([<ffishim_ $curve _init_merchant>](bad_channel_state.as_ptr(), merch_name.as_ptr()));
+                    assert_eq!(r["error"]["code"], ErrorCode::InvalidInput as i32);
+                    assert!(r["error"]["message"].is_string());
+                }
+
+                #[test]
+                fn [<ffishim_ $curve _customer_close_on_unestablished_channel_returns_error>]() {
+                    let channel_name = CString::new("Channel A -> B").unwrap();
+                    let r = parse_response([<ffishim_ $curve _channel_setup>](channel_name.as_ptr(), 0));
+                    let channel_state = field(&r, "channel_state");
+
+                    let merch_name = CString::new("Bob").unwrap();
+                    let r = parse_response([<ffishim_ $curve _init_merchant>](channel_state.as_ptr(), merch_name.as_ptr()));
+                    let (channel_token, channel_state) = (field(&r, "channel_token"), field(&r, "channel_state"));
+
+                    let cust_name = CString::new("Alice").unwrap();
+                    let r = parse_response([<ffishim_ $curve _init_customer>](channel_state.as_ptr(), channel_token.as_ptr(), 100, 10, cust_name.as_ptr()));
+                    let cust_state = field(&r, "cust_state");
+
+                    // customer_close panics if the channel has not been established
+                    let r = parse_response([<ffishim_ $curve _customer_close>](channel_state.as_ptr(), cust_state.as_ptr()));
+                    assert_eq!(r["error"]["code"], ErrorCode::Panic as i32);
+                    assert_eq!(r["error"]["message"], "Cannot close a channel that has not been established!");
+                }
+
+                #[test]
+                fn [<ffishim_ $curve _invalid_utf8_returns_error>]() {
+                    let invalid_utf8 = CString::new(vec![0x7b, 0xff, 0xfe, 0x7d]).unwrap();
+                    let merch_name = CString::new("Bob").unwrap();
+                    let r = parse_response([<ffishim_ $curve _init_merchant>](invalid_utf8.as_ptr(), merch_name.as_ptr()));
+                    assert_eq!(r["error"]["code"], ErrorCode::InvalidInput as i32);
+
+                    let channel_name = CString::new("Channel A -> B").unwrap();
+                    let r = parse_response([<ffishim_ $curve _channel_setup>](channel_name.as_ptr(), 0));
+                    let channel_state = field(&r, "channel_state");
+                    let r = parse_response([<ffishim_ $curve _init_merchant>](channel_state.as_ptr(), invalid_utf8.as_ptr()));
+                    assert_eq!(r["error"]["code"], ErrorCode::InvalidInput as i32);
+
+                    let r = parse_response([<ffishim_ $curve _channel_setup>](invalid_utf8.as_ptr(), 0));
+                    assert_eq!(r["error"]["code"], ErrorCode::InvalidInput as i32);
+                }
+            }};
+        }
+
+        ffishim_curve_tests!(bls12);
+        ffishim_curve_tests!(bn256);
+
+        // build.rs regenerates include/libbolt_ffi.h from this file when the header feature is on
+        #[cfg(feature = "header")]
+        mod header {
+            use std::collections::BTreeMap;
+            use std::env;
+            use std::fs;
+            use std::io::Write;
+            use std::path::{Path, PathBuf};
+            use std::process::{Command, Stdio};
+
+            const WRAPPERS: [&str; 5] = ["ChannelToken.h", "Wallet.h", "PublicKey.h", "PublicParams.h", "ChannelClosure.h"];
+
+            fn include_dir() -> PathBuf {
+                Path::new(env!("CARGO_MANIFEST_DIR")).join("include")
+            }
+
+            fn strip_comments(src: &str) -> String {
+                let mut out = String::new();
+                let mut chars = src.chars().peekable();
+                while let Some(c) = chars.next() {
+                    if c == '/' && chars.peek() == Some(&'*') {
+                        let mut prev = chars.next().unwrap();
+                        for c in chars.by_ref() {
+                            if prev == '*' && c == '/' {
+                                break;
+                            }
+                            prev = c;
+                        }
+                    } else if c == '/' && chars.peek() == Some(&'/') {
+                        while let Some(&c) = chars.peek() {
+                            if c == '\n' {
+                                break;
+                            }
+                            chars.next();
+                        }
+                    } else {
+                        out.push(c);
+                    }
+                }
+                out.lines().filter(|line| !line.trim_start().starts_with('#')).collect::<Vec<_>>().join("\n")
+            }
+
+            fn is_ident(c: char) -> bool {
+                c.is_ascii_alphanumeric() || c == '_'
+            }
+
+            // `struct BoltChannel` and its typedef name the same type
+            fn normalize_type(ty: &str) -> String {
+                ty.split_whitespace().filter(|token| *token != "struct").collect()
+            }
+
+            // Maps each ffishim_*/bolt_* prototype to its normalized return and parameter types
+            fn prototypes(header: &str) -> BTreeMap<String, String> {
+                let mut protos = BTreeMap::new();
+                for stmt in strip_comments(header).split(';') {
+                    let stmt = stmt.rsplit(&['{', '}'][..]).next().unwrap().trim();
+                    let open = match stmt.find('(') {
+                        Some(open) if !stmt.contains('=') && stmt.ends_with(')') => open,
+                        _ => continue,
+                    };
+                    let prefix = stmt[..open].trim_end();
+                    let name = prefix.trim_end_matches(is_ident);
+                    let (ret, name) = prefix.split_at(name.len());
+                    if !name.starts_with("ffishim_") && !name.starts_with("bolt_") {
+                        continue;
+                    }
+                    let params = stmt[open + 1..stmt.len() - 1].split(',')
+                        .map(|param| param.trim())
+                        .filter(|param| !param.is_empty() && *param != "void")
+                        .map(|param| normalize_type(param.trim_end_matches(is_ident)))
+                        .collect::<Vec<_>>();
+                    protos.insert(name.to_string(), format!("{}({})", normalize_type(ret), params.join(",")));
+                }
+                protos
+            }
+
+            // Runs the C++ compiler in syntax-only mode with the generated header force-included
+            fn check_cpp(input: &str, source: Option<&str>) {
+                let compiler = env::var("CXX").unwrap_or_else(|_| "c++".to_string());
+                let mut child = Command::new(&compiler)
+                    .arg("-fsyntax-only")
+                    .arg("-I").arg(include_dir())
+                    .arg("-include").arg(include_dir().join("libbolt_ffi.h"))
+                    .arg("-x").arg("c++").arg(input)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .expect("a C++ compiler is required for the header tests (set CXX)");
+                if let Some(source) = source {
+                    child.stdin.take().unwrap().write_all(source.as_bytes()).unwrap();
+                }
+                let status = child.wait().unwrap();
+                assert!(status.success(), "{} failed to compile against the generated header", input);
+            }
+
+            #[test]
+            fn libbolt_header_matches_generated_header() {
+                let generated = fs::read_to_string(include_dir().join("libbolt_ffi.h"))
+                    .expect("include/libbolt_ffi.h is written by build.rs with --features header");
+                let handwritten = fs::read_to_string(include_dir().join("libbolt.h")).unwrap();
+                let expected = prototypes(&generated);
+                let actual = prototypes(&handwritten);
+                assert!(!expected.is_empty(), "no prototypes found in include/libbolt_ffi.h");
+
+                let mut drift = Vec::new();
+                for name in expected.keys().chain(actual.keys().filter(|name| !expected.contains_key(*name))) {
+                    match (expected.get(name), actual.get(name)) {
+                        (Some(e), Some(a)) if e == a => continue,
+                        (e, a) => drift.push(format!("{}: generated {:?}, libbolt.h {:?}", name, e, a)),
+                    }
+                }
+                assert!(drift.is_empty(), "include/libbolt.h is out of date:\n{}", drift.join("\n"));
+            }
+
+            #[test]
+            fn cpp_wrappers_compile_against_generated_header() {
+                let cpp = Path::new(env!("CARGO_MANIFEST_DIR")).join("cpp").join("libbolt.cpp");
+                check_cpp(cpp.to_str().unwrap(), None);
+                for wrapper in WRAPPERS.iter() {
+                    check_cpp("-", Some(&format!("#include \"libbolt.h\"\n#include \"{}\"\n", wrapper)));
+                }
+            }
+        }
     }
 }
@@ -13,10 +13,8 @@
 #![allow(non_upper_case_globals)]
 #![allow(unused_results)]
 #![allow(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#![cfg_attr(all(test, feature = "unstable"), feature(test))]
-#[cfg(all(test, feature = "unstable"))]
-extern crate test;
 
 extern crate ff_bl as ff;
 extern crate pairing_bl as pairing;
@@ -24,17 +22,39 @@ extern crate rand;
 // extern crate rand_core;
 
 extern crate secp256k1;
+#[cfg(feature = "std")]
 extern crate time;
 extern crate sha2;
+extern crate ripemd160;
+extern crate zeroize;
 
 extern crate serde;
+#[cfg(feature = "std")]
 extern crate serde_with;
 
+#[cfg(feature = "std")]
 extern crate libc;
 extern crate hex;
-
-#[cfg(test)]
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate paste;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "wasm")]
+extern crate rand_chacha;
+#[cfg(all(test, target_arch = "wasm32"))]
+extern crate wasm_bindgen_test;
+
+#[cfg(feature = "std")]
 extern crate core;
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+#[macro_use]
 extern crate serde_json;
 
 pub mod cl;
@@ -44,12 +64,22 @@ pub mod channels;
 pub mod nizk;
 pub mod util;
 pub mod wallet;
+#[cfg(feature = "std")]
 pub mod ffishim;
-pub mod ffishim_bn256;
-
-use std::fmt;
-use std::str;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use core::fmt;
+use core::str;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
+// the std HashMap is seeded from OS randomness, so an ordered map stands in for it without `std`
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+#[cfg(not(feature = "std"))]
+use alloc::{string::{String, ToString}, vec::Vec};
 use ff::{Rand, Field};
 
 use serde::{Serialize, Deserialize};
@@ -113,14 +143,18 @@ pub mod bidirectional {
     use secp256k1;
     // for on-chain keys
     use HashMap;
+    #[cfg(not(feature = "std"))]
+    use alloc::{string::{String, ToString}, vec::Vec};
 
     use serde::{Serialize, Deserialize};
     use util::{RevokedMessage, hash_to_slice};
     pub use ped92::Commitment;
     pub use cl::{PublicKey, Signature};
+    pub use util::Validate;
     pub use BoltResult;
-    pub use channels::{ChannelState, ChannelToken, CustomerState, MerchantState, ChannelcloseM,
-                       PubKeyMap, ChannelParams, BoltError, ResultBoltType};
+    pub use channels::{ChannelState, ChannelToken, CustomerState, MerchantState, MerchantLedger, ChannelRecord,
+                       ChannelcloseM, PubKeyMap, ChannelParams, BoltError, ResultBoltType, CustomerSecrets, MerchantSecrets,
+                       CustomerStateExport, ChannelTokenUpdate};
     pub use nizk::NIZKProof;
     pub use wallet::Wallet;
     pub use cl::PublicParams;
@@ -155,6 +189,44 @@ pub mod bidirectional {
         amount: i64,
     }
 
+    #[derive(Clone, Serialize, Deserialize)]
+    #[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, \
+    <E as pairing::Engine>::G1: serde::Serialize, \
+    <E as pairing::Engine>::G2: serde::Serialize, \
+    <E as pairing::Engine>::Fqk: serde::Serialize"
+    ))]
+    #[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>, \
+    <E as pairing::Engine>::G1: serde::Deserialize<'de>, \
+    <E as pairing::Engine>::G2: serde::Deserialize<'de>,\
+    <E as pairing::Engine>::Fqk: serde::Deserialize<'de>"
+    ))]
+    pub struct Migration<E: Engine> {
+        proof: NIZKProof<E>,
+        com: Commitment<E>,
+        wpk: secp256k1::PublicKey,
+        channel_id: E::Fr,
+    }
+
+    impl<E: Engine> Validate for Migration<E> {
+        fn validate(&self) -> Result<(), String> {
+            self.proof.validate()?;
+            self.com.validate()
+        }
+    }
+
+    impl<E: Engine> Validate for Payment<E> {
+        fn validate(&self) -> Result<(), String> {
+            self.proof.validate()?;
+            self.com.validate()
+        }
+    }
+
+    impl<E: Engine> Validate for ChannelcloseC<E> {
+        fn validate(&self) -> Result<(), String> {
+            self.signature.validate()
+        }
+    }
+
     #[derive(Clone, Serialize, Deserialize)]
     pub struct RevokeToken {
         message: util::RevokedMessage,
@@ -163,34 +235,48 @@ pub mod bidirectional {
 
     ///
     /// init_merchant - takes as input the public params, merchant balance and keypair.
-    /// Generates merchant data which consists of channel token and merchant state.
+    /// Generates merchant data which consists of channel token, merchant state (the signing keys)
+    /// and an empty merchant ledger (revoked wallet keys, pay tokens and channel records).
     ///
-    pub fn init_merchant<'a, R: Rng, E: Engine>(csprng: &mut R, channel_state: &mut ChannelState<E>, name: &'a str) -> (ChannelToken<E>, MerchantState<E>, ChannelState<E>) {
+    pub fn init_merchant<'a, R: Rng, E: Engine>(csprng: &mut R, channel_state: &mut ChannelState<E>, name: &'a str) -> (ChannelToken<E>, MerchantState<E>, MerchantLedger<E>, ChannelState<E>) {
         // create new merchant state
         let merch_name = String::from(name);
         let (mut merch_state, mut channel_state) = MerchantState::<E>::new(csprng, channel_state, merch_name);
         // initialize the merchant state
         let channel_token = merch_state.init(&mut channel_state);
 
-        return (channel_token, merch_state, channel_state.clone());
+        return (channel_token, merch_state, MerchantLedger::<E>::new(), channel_state.clone());
     }
 
     ///
     /// init_customer - takes as input the public params, channel state, commitment params, keypair,
-    /// and initial balance for customer and merchant. Generate initial customer channel token,
-    /// and wallet commitment.
+    /// and initial balance for customer and merchant. Validates the range proof parameters and the
+    /// channel token from the merchant (see ChannelToken::validate) and generates the initial
+    /// customer channel token and wallet commitment.
     ///
-    pub fn init_customer<'a, R: Rng, E: Engine>(csprng: &mut R, channel_token: &mut ChannelToken<E>,
-                                                b0_cust: i64, b0_merch: i64, name: &'a str) -> CustomerState<E>
+    pub fn init_customer<'a, R: Rng, E: Engine>(csprng: &mut R, channel_state: &ChannelState<E>, channel_token: &mut ChannelToken<E>,
+                                                b0_cust: i64, b0_merch: i64, name: &'a str) -> BoltResult<CustomerState<E>>
         where <E as pairing::Engine>::G1: serde::Serialize,
               <E as pairing::Engine>::G2: serde::Serialize,
               <E as ff::ScalarEngine>::Fr: serde::Serialize
     {
-        assert!(b0_cust >= 0);
-        assert!(b0_merch >= 0);
+        if b0_cust < 0 || b0_merch < 0 {
+            return Err(format!("init_customer - negative initial balance: customer = {}, merchant = {}", b0_cust, b0_merch));
+        }
+
+        let cp = match channel_state.cp {
+            Some(ref cp) => cp,
+            None => return Err(String::from("init_customer - channel params are not initialized"))
+        };
+        if let Err(err) = cp.pub_params.rpParams.validate(csprng) {
+            return Err(format!("init_customer - invalid range proof parameters: {}", err));
+        }
+        if let Err(err) = channel_token.validate(channel_state) {
+            return Err(format!("init_customer - invalid channel token: {}", err));
+        }
 
         let cust_name = String::from(name);
-        return CustomerState::<E>::new(csprng, channel_token, b0_cust, b0_merch, cust_name);
+        return Ok(Some(CustomerState::<E>::new(csprng, channel_token, b0_cust, b0_merch, cust_name)));
     }
 
     ///
@@ -206,14 +292,14 @@ pub mod bidirectional {
     ///
     /// establish_merchant_issue_close_token (Phase 1) - takes as input the channel state,
     /// PoK of committed values from the customer. Generates close token (a blinded
-    /// signature) over the contents of the customer's wallet.
+    /// signature) over the contents of the customer's wallet and records the channel in the ledger.
     ///
     pub fn establish_merchant_issue_close_token<R: Rng, E: Engine>(csprng: &mut R, channel_state: &ChannelState<E>,
                                                                    com: &Commitment<E>, com_proof: &CommitmentProof<E>,
                                                                    channel_id: &E::Fr, init_cust_balance: i64, init_merch_balance: i64,
-                                                                   merch_state: &MerchantState<E>) -> BoltResult<cl::Signature<E>> {
+                                                                   merch_state: &MerchantState<E>, merch_ledger: &mut MerchantLedger<E>) -> BoltResult<cl::Signature<E>> {
         // verifies proof of committed values and derives blind signature on the committed values to the customer's initial wallet
-        match merch_state.verify_proof(csprng, channel_state, com, com_proof, channel_id, init_cust_balance, init_merch_balance) {
+        match merch_state.verify_proof(csprng, channel_state, merch_ledger, com, com_proof, channel_id, init_cust_balance, init_merch_balance) {
             Ok(n) => Ok(Some(n.0)), // just close token
             Err(err) => Err(String::from(err.to_string()))
         }
@@ -239,6 +325,7 @@ pub mod bidirectional {
     pub fn establish_customer_final<E: Engine>(channel_state: &mut ChannelState<E>, cust_state: &mut CustomerState<E>, pay_token: &cl::Signature<E>) -> bool {
         // verify the pay-token first
         if !cust_state.verify_pay_token(&channel_state, pay_token) {
+            #[cfg(feature = "std")]
             println!("establish_customer_final - Failed to verify the pay-token");
             return false;
         }
@@ -273,10 +360,12 @@ pub mod bidirectional {
     ///
     /// verify_payment (phase 1) - takes as input the public params, channel state, payment proof
     /// and merchant keys. If proof is valid, then merchant returns the refund token
-    /// (i.e., partially blind signature on IOU with updated balance)
+    /// (i.e., partially blind signature on IOU with updated balance) and records the
+    /// revealed wpk and pay token in the merchant ledger.
     ///
     pub fn verify_payment_proof<R: Rng, E: Engine>(csprng: &mut R, channel_state: &ChannelState<E>,
-                                                   payment: &Payment<E>, merch_state: &mut MerchantState<E>) -> cl::Signature<E> {
+                                                   payment: &Payment<E>, merch_state: &MerchantState<E>,
+                                                   merch_ledger: &mut MerchantLedger<E>) -> cl::Signature<E> {
         // if payment proof verifies, then returns close-token and records wpk => pay-token
         // if valid revoke_token is provided later for wpk, then release pay-token
         let tx_fee = channel_state.get_channel_fee();
@@ -284,13 +373,45 @@ pub mod bidirectional {
             true => payment.amount + tx_fee,
             false => payment.amount
         };
-        let new_close_token = merch_state.verify_payment(csprng, &channel_state,
-                                                         &payment.proof, &payment.com, &payment.wpk, payment_amount).unwrap();
+        let new_close_token = merch_state.verify_payment(csprng, &channel_state, merch_ledger,
+                                                           &payment.proof, &payment.com, &payment.wpk, payment_amount).unwrap();
         // store the wpk since it has been revealed
-        update_merchant_state(&mut merch_state.keys, &payment.wpk, None);
+        update_merchant_ledger(&mut merch_ledger.keys, &payment.wpk, None);
         return new_close_token;
     }
 
+    ///
+    /// verify_payment_proofs - takes as input the public params, a list of independent payments
+    /// and the merchant state and ledger. The payment proofs are verified concurrently if the `parallel`
+    /// feature is enabled. Returns a close-token for each valid payment and an error otherwise.
+    ///
+    pub fn verify_payment_proofs<R: Rng, E: Engine>(csprng: &mut R, channel_state: &ChannelState<E>,
+                                                    payments: &Vec<Payment<E>>, merch_state: &MerchantState<E>,
+                                                    merch_ledger: &mut MerchantLedger<E>) -> Vec<Result<cl::Signature<E>, String>> {
+        let tx_fee = channel_state.get_channel_fee();
+        let pay_proofs = payments.iter().map(|payment| {
+            let payment_amount = match tx_fee > 0 {
+                true => payment.amount + tx_fee,
+                false => payment.amount
+            };
+            (payment.proof.clone(), payment.com.clone(), payment.wpk.clone(), payment_amount)
+        }).collect();
+
+        let results = merch_state.verify_payments(csprng, &channel_state, merch_ledger, &pay_proofs);
+        let mut close_tokens = Vec::with_capacity(results.len());
+        for (payment, result) in payments.iter().zip(results.into_iter()) {
+            match result {
+                Ok(close_token) => {
+                    // store the wpk since it has been revealed
+                    update_merchant_ledger(&mut merch_ledger.keys, &payment.wpk, None);
+                    close_tokens.push(Ok(close_token));
+                },
+                Err(err) => close_tokens.push(Err(err.to_string()))
+            }
+        }
+        close_tokens
+    }
+
     ///
     /// Verify third party payment proof from two bi-directional channel payments with intermediary (payment amount
     ///
@@ -298,7 +419,8 @@ pub mod bidirectional {
                                                              channel_state: &ChannelState<E>,
                                                              sender_payment: &Payment<E>,
                                                              receiver_payment: &Payment<E>,
-                                                             merch_state: &mut MerchantState<E>)
+                                                             merch_state: &MerchantState<E>,
+                                                             merch_ledger: &mut MerchantLedger<E>)
                                                              -> BoltResult<(cl::Signature<E>, cl::Signature<E>)> {
         let tx_fee = channel_state.get_channel_fee();
         let amount = sender_payment.amount + receiver_payment.amount;
@@ -306,15 +428,24 @@ pub mod bidirectional {
             return Err(String::from("payments do not offset"));
         }
 
-        let new_close_token = merch_state.verify_payment(csprng, &channel_state,
-                                                         &sender_payment.proof, &sender_payment.com, &sender_payment.wpk, sender_payment.amount + tx_fee).unwrap();
+        // the payments are verified against a copy of the ledger, so that it is unchanged unless both are valid
+        let mut ledger = merch_ledger.clone();
+        let new_close_token = match merch_state.verify_payment(csprng, &channel_state, &mut ledger,
+                                                               &sender_payment.proof, &sender_payment.com, &sender_payment.wpk, sender_payment.amount + tx_fee) {
+            Ok(n) => n,
+            Err(err) => return Err(format!("sender payment - {}", err))
+        };
 
-        let cond_close_token = merch_state.verify_payment(csprng, &channel_state,
-                                                          &receiver_payment.proof, &receiver_payment.com, &receiver_payment.wpk, receiver_payment.amount + tx_fee).unwrap();
+        let cond_close_token = match merch_state.verify_payment(csprng, &channel_state, &mut ledger,
+                                                                &receiver_payment.proof, &receiver_payment.com, &receiver_payment.wpk, receiver_payment.amount + tx_fee) {
+            Ok(n) => n,
+            Err(err) => return Err(format!("receiver payment - {}", err))
+        };
 
         // store the wpk since it has been revealed
-        update_merchant_state(&mut merch_state.keys, &sender_payment.wpk, None);
-        update_merchant_state(&mut merch_state.keys, &receiver_payment.wpk, None);
+        update_merchant_ledger(&mut ledger.keys, &sender_payment.wpk, None);
+        update_merchant_ledger(&mut ledger.keys, &receiver_payment.wpk, None);
+        *merch_ledger = ledger;
 
         return Ok(Some((new_close_token, cond_close_token)));
     }
@@ -339,28 +470,28 @@ pub mod bidirectional {
 
     ///
     /// verify_revoke_token (phase 2) - takes as input revoke message and signature
-    /// from the customer and the merchant state. If the revocation token is valid,
+    /// from the customer and the merchant ledger. If the revocation token is valid,
     /// generate a new signature for the new wallet (from the PoK of committed values in new wallet).
     ///
-    pub fn verify_revoke_token<E: Engine>(rt: &RevokeToken, merch_state: &mut MerchantState<E>) -> BoltResult<cl::Signature<E>> {
-        let pay_token_result = merch_state.verify_revoke_token(&rt.signature, &rt.message, &rt.message.wpk);
+    pub fn verify_revoke_token<E: Engine>(rt: &RevokeToken, merch_ledger: &mut MerchantLedger<E>) -> BoltResult<cl::Signature<E>> {
+        let pay_token_result = merch_ledger.verify_revoke_token(&rt.signature, &rt.message, &rt.message.wpk);
         let new_pay_token = match pay_token_result {
             Ok(n) => n,
             Err(err) => return Err(String::from(err.to_string()))
         };
-        update_merchant_state(&mut merch_state.keys, &rt.message.wpk, Some(rt.signature.clone()));
+        update_merchant_ledger(&mut merch_ledger.keys, &rt.message.wpk, Some(rt.signature.clone()));
         Ok(Some(new_pay_token))
     }
 
     ///
     /// verify_multiple_revoke_tokens (phase 2) - takes as input revoke messages and signatures
-    /// from the sender and receiver and the merchant state of the intermediary.
+    /// from the sender and receiver and the merchant ledger of the intermediary.
     /// If the revocation tokens are valid, generate new signatures for the new wallets of both
     /// sender and receiver (from the PoK of committed values in new wallet).
     ///
-    pub fn verify_multiple_revoke_tokens<E: Engine>(rt_sender: &RevokeToken, rt_receiver: &RevokeToken, merch_state: &mut MerchantState<E>) -> BoltResult<(cl::Signature<E>, cl::Signature<E>)> {
-        let pay_token_sender_result = merch_state.verify_revoke_token(&rt_sender.signature, &rt_sender.message, &rt_sender.message.wpk);
-        let pay_token_receiver_result = merch_state.verify_revoke_token(&rt_receiver.signature, &rt_receiver.message, &rt_receiver.message.wpk);
+    pub fn verify_multiple_revoke_tokens<E: Engine>(rt_sender: &RevokeToken, rt_receiver: &RevokeToken, merch_ledger: &mut MerchantLedger<E>) -> BoltResult<(cl::Signature<E>, cl::Signature<E>)> {
+        let pay_token_sender_result = merch_ledger.verify_revoke_token(&rt_sender.signature, &rt_sender.message, &rt_sender.message.wpk);
+        let pay_token_receiver_result = merch_ledger.verify_revoke_token(&rt_receiver.signature, &rt_receiver.message, &rt_receiver.message.wpk);
         let new_pay_token_sender = match pay_token_sender_result {
             Ok(n) => n,
             Err(err) => return Err(String::from(err.to_string()))
@@ -370,14 +501,87 @@ pub mod bidirectional {
             Err(err) => return Err(String::from(err.to_string()))
         };
 
-        update_merchant_state(&mut merch_state.keys, &rt_sender.message.wpk, Some(rt_sender.signature.clone()));
-        update_merchant_state(&mut merch_state.keys, &rt_receiver.message.wpk, Some(rt_receiver.signature.clone()));
+        update_merchant_ledger(&mut merch_ledger.keys, &rt_sender.message.wpk, Some(rt_sender.signature.clone()));
+        update_merchant_ledger(&mut merch_ledger.keys, &rt_receiver.message.wpk, Some(rt_receiver.signature.clone()));
 
         Ok(Some((new_pay_token_sender, new_pay_token_receiver)))
     }
 
     ///// end of pay protocol
 
+    ///
+    /// rotate_merchant_keys (key rotation) - takes as input the channel state and merchant state.
+    /// Generates a new CL key pair for the merchant and returns the new channel token and channel
+    /// state. The replaced keys remain valid for the migration of the existing channels until
+    /// retire_merchant_keys.
+    ///
+    pub fn rotate_merchant_keys<R: Rng, E: Engine>(csprng: &mut R, channel_state: &ChannelState<E>, merch_state: &mut MerchantState<E>) -> BoltResult<(ChannelToken<E>, ChannelState<E>)> {
+        let mut new_channel_state = match merch_state.rotate_keys(csprng, channel_state) {
+            Ok(n) => n,
+            Err(err) => return Err(String::from(err.to_string()))
+        };
+        let channel_token = merch_state.init(&mut new_channel_state);
+        Ok(Some((channel_token, new_channel_state)))
+    }
+
+    ///
+    /// generate_migration_proof (key rotation, phase 1) - takes as input the old and new channel state,
+    /// the new channel token from the merchant and the customer state. Validates the new channel token,
+    /// then generates a PoK of a valid pay token under the old merchant key and of the committed values
+    /// of the new wallet (same channel id and balances) under the new one. Returns the migration
+    /// and the new customer state, to be completed as a payment with the new channel state
+    /// (generate_revoke_token, verify_revoke_token and CustomerState::verify_pay_token).
+    /// The wallet keeps the channel id of the channel token the channel was established with, so the
+    /// WTP verifies the close tokens under the new key with a channel token update (see sign_channel_token_update).
+    ///
+    pub fn generate_migration_proof<R: Rng, E: Engine>(csprng: &mut R, old_channel_state: &ChannelState<E>, new_channel_state: &ChannelState<E>,
+                                                       new_channel_token: &mut ChannelToken<E>, cust_state: &CustomerState<E>) -> BoltResult<(Migration<E>, CustomerState<E>)> {
+        if let Err(err) = new_channel_token.validate(new_channel_state) {
+            return Err(format!("generate_migration_proof - invalid channel token: {}", err));
+        }
+        new_channel_token.set_customer_pk(&cust_state.pk_c);
+
+        let (proof, com, wpk, new_cust_state) = cust_state.generate_migration(csprng, old_channel_state, new_channel_state);
+        let migration = Migration { proof, com, wpk, channel_id: cust_state.get_wallet().channelId };
+        Ok(Some((migration, new_cust_state)))
+    }
+
+    ///
+    /// verify_migration_proof (key rotation, phase 1) - takes as input the new channel state, the
+    /// migration from the customer, the merchant state and ledger. If the proof is valid and the channel
+    /// has not migrated yet, returns a close token under the new merchant key and records the revealed
+    /// wpk and pay token in the merchant ledger.
+    ///
+    pub fn verify_migration_proof<R: Rng, E: Engine>(csprng: &mut R, channel_state: &ChannelState<E>, migration: &Migration<E>,
+                                                     merch_state: &MerchantState<E>, merch_ledger: &mut MerchantLedger<E>) -> BoltResult<cl::Signature<E>> {
+        match merch_state.verify_migration(csprng, channel_state, merch_ledger, &migration.proof, &migration.com, &migration.wpk, &migration.channel_id) {
+            Ok(n) => Ok(Some(n)),
+            Err(err) => Err(String::from(err.to_string()))
+        }
+    }
+
+    ///
+    /// sign_channel_token_update (key rotation, phase 2) - takes as input the new channel state, the merchant
+    /// state and ledger, and the channel tokens of a migrated channel before and after the migration (the latter
+    /// as updated by generate_migration_proof). Returns the new channel token signed under pk_m, which the WTP
+    /// accepts in place of the channel token in escrow (see wtp_verify_migrated_cust_close_message).
+    ///
+    pub fn sign_channel_token_update<E: Engine>(channel_state: &ChannelState<E>, merch_state: &MerchantState<E>, merch_ledger: &MerchantLedger<E>,
+                                                channel_token: &ChannelToken<E>, new_channel_token: &ChannelToken<E>) -> BoltResult<ChannelTokenUpdate<E>> {
+        match merch_state.sign_channel_token_update(channel_state, merch_ledger, channel_token, new_channel_token) {
+            Ok(n) => Ok(Some(n)),
+            Err(err) => Err(String::from(err.to_string()))
+        }
+    }
+
+    ///
+    /// retire_merchant_keys (key rotation) - drops the keys replaced by rotate_merchant_keys. Fails if a
+    /// channel of the ledger has neither migrated nor been closed (see merchant_close).
+    ///
+    pub fn retire_merchant_keys<E: Engine>(merch_state: &mut MerchantState<E>, merch_ledger: &MerchantLedger<E>) -> Result<(), String> {
+        merch_state.retire_keys(merch_ledger).map_err(|err| err.to_string())
+    }
+
     // for customer => on input a wallet w, it outputs a customer channel closure message
     ///
     /// customer_close - takes as input the channel state, merchant's verification
@@ -399,7 +603,7 @@ pub mod bidirectional {
         ChannelcloseC { wpk: cust_state.wpk, message: wallet, signature: close_token }
     }
 
-    fn update_merchant_state(db: &mut HashMap<String, PubKeyMap>, wpk: &secp256k1::PublicKey, rev: Option<secp256k1::Signature>) {
+    fn update_merchant_ledger(db: &mut HashMap<String, PubKeyMap>, wpk: &secp256k1::PublicKey, rev: Option<secp256k1::Signature>) {
         let fingerprint = util::compute_pub_key_fingerprint(wpk);
         //println!("Print fingerprint: {}", fingerprint);
         if !rev.is_none() {
@@ -412,14 +616,15 @@ pub mod bidirectional {
     }
 
     ///
-    /// merchant_close - takes as input the channel state, channel token, customer close msg/sig,
+    /// merchant_close - takes as input the channel state, channel token, customer close msg/sig and merchant ledger.
     /// Returns tokens for merchant close transaction (only if customer close message is found to be a
-    /// double spend). If not, then None is returned.
+    /// double spend). If not, then None is returned. A valid close message marks the channel as closed
+    /// in the merchant ledger, so that it does not prevent retiring rotated keys.
     ///
     pub fn merchant_close<E: Engine>(channel_state: &ChannelState<E>,
                                      channel_token: &ChannelToken<E>,
                                      cust_close: &ChannelcloseC<E>,
-                                     merch_state: &MerchantState<E>) -> BoltResult<PubKeyMap> {
+                                     merch_ledger: &mut MerchantLedger<E>) -> BoltResult<PubKeyMap> {
         if (!channel_state.channel_established) {
             return Err(String::from("merchant_close - Channel not established! Cannot generate channel closure message."));
         }
@@ -433,11 +638,12 @@ pub mod bidirectional {
         let is_valid = pk.verify(&channel_token.mpk, &close_wallet, &close_token);
 
         if is_valid {
+            merch_ledger.close_channel(&cust_close.message.channelId);
             let wpk = cust_close.wpk;
             // found the wpk, which means old close token
             let fingerprint = util::compute_pub_key_fingerprint(&wpk);
-            if merch_state.keys.contains_key(&fingerprint) {
-                let revoked_state = merch_state.keys.get(&fingerprint).unwrap();
+            if merch_ledger.keys.contains_key(&fingerprint) {
+                let revoked_state = merch_ledger.keys.get(&fingerprint).unwrap();
                 if !revoked_state.revoke_token.is_none() {
                     let revoke_token = revoked_state.revoke_token.unwrap().clone();
                     // verify the revoked state first before returning
@@ -476,6 +682,25 @@ pub mod bidirectional {
         return pkc_thesame && wpk_thesame && channel_token.cl_pk_m.verify(&channel_token.mpk, &close_msg.as_fr_vec(), &close_token);
     }
 
+    ///
+    /// Used in open-channel WTP for validating the close_token of a migrated channel: the channel token update must
+    /// be signed by the merchant of the channel token in escrow, and the close_token is verified under its new key
+    ///
+    pub fn wtp_verify_migrated_cust_close_message<E: Engine>(channel_token: &ChannelToken<E>, update: &ChannelTokenUpdate<E>, wpk: &secp256k1::PublicKey,
+                                                             close_msg: &wallet::Wallet<E>, close_token: &Signature<E>) -> bool {
+        if !update.verify(channel_token) {
+            return false;
+        }
+        // the wallet keeps the id of the channel token in escrow
+        let chan_token_cid = channel_token.compute_channel_id();
+        let chan_token_wpk = util::hash_pubkey_to_fr::<E>(&wpk);
+
+        let cid_thesame = (close_msg.channelId == chan_token_cid);
+        let wpk_thesame = (close_msg.wpk == chan_token_wpk);
+        let new_token = &update.channel_token;
+        return cid_thesame && wpk_thesame && new_token.cl_pk_m.verify(&new_token.mpk, &close_msg.as_fr_vec(), &close_token);
+    }
+
     ///
     /// Used in merch-close WTP for validating that revoke_token is a valid signature under <wpk> and the <revoked || wpk> message
     ///
@@ -507,7 +732,9 @@ pub mod bidirectional {
 
 pub mod wtp_utils {
     // Useful routines that simplify the Bolt WTP implementation for Zcash
+    use pairing::Engine;
     use pairing::bls12_381::Bls12;
+    use pairing::bn256::Bn256;
     use ::{util, BoltResult};
     use cl;
     use ped92::CSMultiParams;
@@ -515,12 +742,37 @@ pub mod wtp_utils {
     pub use channels::ChannelToken;
     pub use wallet::Wallet;
     use channels::ChannelcloseM;
+    #[cfg(not(feature = "std"))]
+    use alloc::{string::String, vec::Vec};
 
-    const BLS12_381_CHANNEL_TOKEN_LEN: usize = 1074;
-    const BLS12_381_G1_LEN: usize = 48;
-    const BLS12_381_G2_LEN: usize = 96;
     const SECP256K1_PK_LEN: usize = 33;
     const ADDRESS_LEN: usize = 33;
+    // number of messages in a wallet, i.e. of Y elements in the merchant's CL public key
+    const NUM_Y_ELEMS: usize = 5;
+    // number of commitment bases (one per wallet message plus the randomness)
+    const NUM_COM_PARAMS: usize = 6;
+
+    ///
+    /// WTPEngine - the lengths of the compressed point encodings that the WTP
+    /// uses to pass channel tokens and close tokens of a pairing engine
+    ///
+    pub trait WTPEngine: Engine {
+        const G1_LEN: usize;
+        const G2_LEN: usize;
+        /// pk_c || pk_m || cl_pk_m (X, Y_1 .. Y_5) || mpk (g1, g2) || comParams (g_1 .. g_6)
+        const CHANNEL_TOKEN_LEN: usize = 2 * SECP256K1_PK_LEN + (1 + NUM_Y_ELEMS) * Self::G2_LEN
+                                         + Self::G1_LEN + Self::G2_LEN + NUM_COM_PARAMS * Self::G1_LEN;
+    }
+
+    impl WTPEngine for Bls12 {
+        const G1_LEN: usize = 48;
+        const G2_LEN: usize = 96;
+    }
+
+    impl WTPEngine for Bn256 {
+        const G1_LEN: usize = 32;
+        const G2_LEN: usize = 64;
+    }
 
     pub fn reconstruct_secp_public_key(pk_bytes: &[u8; SECP256K1_PK_LEN]) -> secp256k1::PublicKey {
         return secp256k1::PublicKey::from_slice(pk_bytes).unwrap();
@@ -530,48 +782,53 @@ pub mod wtp_utils {
         return secp256k1::Signature::from_der(sig_bytes).unwrap();
     }
 
-    pub fn reconstruct_close_wallet_bls12(channel_token: &ChannelToken<Bls12>, wpk: &secp256k1::PublicKey, cust_bal: u32, merch_bal: u32) -> Wallet<Bls12> {
+    pub fn reconstruct_close_wallet<E: WTPEngine>(channel_token: &ChannelToken<E>, wpk: &secp256k1::PublicKey, cust_bal: u32, merch_bal: u32) -> Wallet<E>
+        where <E as Engine>::G1: serde::Serialize,
+              <E as Engine>::G2: serde::Serialize,
+              <E as ff::ScalarEngine>::Fr: serde::Serialize
+    {
         let channelId = channel_token.compute_channel_id();
-        let wpk_h = util::hash_pubkey_to_fr::<Bls12>(&wpk);
-        let close = util::hash_to_fr::<Bls12>(String::from("close").into_bytes());
+        let wpk_h = util::hash_pubkey_to_fr::<E>(&wpk);
+        let close = util::hash_to_fr::<E>(String::from("close").into_bytes());
 
         return Wallet {
             channelId, wpk: wpk_h, bc: cust_bal as i64, bm: merch_bal as i64, close: Some(close)
         }
     }
 
-    pub fn reconstruct_signature_bls12(sig: &Vec<u8>) -> BoltResult<cl::Signature<Bls12>> {
-        if (sig.len() != BLS12_381_G1_LEN * 2) {
+    pub fn reconstruct_signature<E: WTPEngine>(sig: &Vec<u8>) -> BoltResult<cl::Signature<E>>
+        where for<'de> <E as Engine>::G1: serde::Deserialize<'de>
+    {
+        if (sig.len() != E::G1_LEN * 2) {
             return Err(String::from("signature has invalid length"));
         }
 
         let mut cur_index = 0;
-        let mut end_index = BLS12_381_G1_LEN;
+        let mut end_index = E::G1_LEN;
         let ser_cl_h = sig[cur_index .. end_index].to_vec();
         let str_cl_h = util::encode_as_hexstring(&ser_cl_h);
         let h = str_cl_h.as_bytes();
 
         cur_index = end_index;
-        end_index += BLS12_381_G1_LEN;
+        end_index += E::G1_LEN;
         let ser_cl_H = sig[cur_index .. end_index].to_vec();
         let str_cl_H = util::encode_as_hexstring(&ser_cl_H);
         let H = str_cl_H.as_bytes();
 
-        let cl_sig = cl::Signature::<Bls12>::from_slice(&h, &H);
+        let cl_sig = cl::Signature::<E>::from_slice(&h, &H);
 
         Ok(Some(cl_sig))
     }
 
-    pub fn reconstruct_channel_token_bls12(channel_token: &Vec<u8>) -> BoltResult<ChannelToken<Bls12>>
+    pub fn reconstruct_channel_token<E: WTPEngine>(channel_token: &Vec<u8>) -> BoltResult<ChannelToken<E>>
+        where for<'de> <E as Engine>::G1: serde::Deserialize<'de>,
+              for<'de> <E as Engine>::G2: serde::Deserialize<'de>
     {
         // parse pkc, pkm, pkM, mpk and comParams
-        if channel_token.len() != BLS12_381_CHANNEL_TOKEN_LEN {
+        if channel_token.len() != E::CHANNEL_TOKEN_LEN {
             return Err(String::from("could not reconstruct the channel token!"));
         }
 
-        let num_y_elems = 5;
-        let num_com_params= 6;
-
         let mut cur_index = 0;
         let mut end_index = SECP256K1_PK_LEN;
         let pkc = secp256k1::PublicKey::from_slice(&channel_token[cur_index .. end_index]).unwrap();
@@ -581,48 +838,48 @@ pub mod wtp_utils {
         let pkm = secp256k1::PublicKey::from_slice(&channel_token[cur_index .. end_index]).unwrap();
 
         cur_index = end_index;
-        end_index += BLS12_381_G2_LEN; // pk_M => (X, Y)
+        end_index += E::G2_LEN; // pk_M => (X, Y)
         let ser_cl_x = channel_token[cur_index .. end_index].to_vec();
         let str_cl_x = util::encode_as_hexstring(&ser_cl_x);
         let X = str_cl_x.as_bytes();
 
         let mut Y = Vec::new();
-        for _ in 0 .. num_y_elems {
+        for _ in 0 .. NUM_Y_ELEMS {
             cur_index = end_index;
-            end_index += BLS12_381_G2_LEN;
+            end_index += E::G2_LEN;
             let cl_y = channel_token[cur_index .. end_index].to_vec();
             let ser_cl_y = util::encode_as_hexstring(&cl_y);
             let str_cl_y = ser_cl_y.as_bytes();
             Y.extend(str_cl_y);
         }
-        let cl_pk= cl::PublicKey::<Bls12>::from_slice(&X, &Y.as_slice(), str_cl_x.len(), num_y_elems);
+        let cl_pk= cl::PublicKey::<E>::from_slice(&X, &Y.as_slice(), str_cl_x.len(), NUM_Y_ELEMS);
 
         cur_index = end_index;
-        end_index += BLS12_381_G1_LEN;
+        end_index += E::G1_LEN;
         let g1 = channel_token[cur_index .. end_index].to_vec();
         let ser_mpk_g1 = util::encode_as_hexstring(&g1);
 
         cur_index = end_index;
-        end_index += BLS12_381_G2_LEN;
+        end_index += E::G2_LEN;
         let g2 = channel_token[cur_index .. end_index].to_vec();
         let ser_mpk_g2 = util::encode_as_hexstring(&g2);
 
         let ser_g1 = ser_mpk_g1.as_bytes();
         let ser_g2 = ser_mpk_g2.as_bytes();
 
-        let mpk = cl::PublicParams::<Bls12>::from_slice(&ser_g1, &ser_g2);
+        let mpk = cl::PublicParams::<E>::from_slice(&ser_g1, &ser_g2);
 
         let mut comparams = Vec::new();
-        for _ in 0 .. num_com_params {
+        for _ in 0 .. NUM_COM_PARAMS {
             cur_index = end_index;
-            end_index += BLS12_381_G1_LEN;
+            end_index += E::G1_LEN;
             let com = channel_token[cur_index .. end_index].to_vec();
             let ser_com = util::encode_as_hexstring(&com);
             let str_com = ser_com.as_bytes();
             comparams.extend(str_com);
         }
 
-        let com_params = CSMultiParams::<Bls12>::from_slice(&comparams.as_slice(), ser_mpk_g1.len(), num_com_params);
+        let com_params = CSMultiParams::<E>::from_slice(&comparams.as_slice(), ser_mpk_g1.len(), NUM_COM_PARAMS);
 
         Ok(Some(ChannelToken {
             pk_c: Some(pkc), pk_m: pkm, cl_pk_m: cl_pk, mpk: mpk, comParams: com_params
@@ -632,15 +889,19 @@ pub mod wtp_utils {
     ///
     /// Used in open-channel WTP for validating that a close_token is a valid signature
     ///
-    pub fn wtp_verify_cust_close_message(channel_token: &ChannelToken<Bls12>, wpk: &secp256k1::PublicKey,
-                                         close_msg: &Wallet<Bls12>, close_token: &cl::Signature<Bls12>) -> bool {
+    pub fn wtp_verify_cust_close_message<E: WTPEngine>(channel_token: &ChannelToken<E>, wpk: &secp256k1::PublicKey,
+                                                       close_msg: &Wallet<E>, close_token: &cl::Signature<E>) -> bool
+        where <E as Engine>::G1: serde::Serialize,
+              <E as Engine>::G2: serde::Serialize,
+              <E as ff::ScalarEngine>::Fr: serde::Serialize
+    {
         // close_msg => <pkc> || <wpk> || <balance-cust> || <balance-merch> || CLOSE
         // close_token = regular CL signature on close_msg
         // channel_token => <pk_c, CL_PK_m, pk_m, mpk, comParams>
 
         // (1) check that channel token and close msg are consistent (e.g., close_msg.channelId == H(channel_token.pk_c) &&
-        let chan_token_cid = channel_token.compute_channel_id(); // util::hash_pubkey_to_fr::<Bls12>(&pk_c);
-        let chan_token_wpk = util::hash_pubkey_to_fr::<Bls12>(&wpk);
+        let chan_token_cid = channel_token.compute_channel_id(); // util::hash_pubkey_to_fr::<E>(&pk_c);
+        let chan_token_wpk = util::hash_pubkey_to_fr::<E>(&wpk);
 
         let cid_thesame = (close_msg.channelId == chan_token_cid);
         // (2) check that wpk matches what's in the close msg
@@ -648,6 +909,18 @@ pub mod wtp_utils {
         return cid_thesame && wpk_thesame && channel_token.cl_pk_m.verify(&channel_token.mpk, &close_msg.as_fr_vec(), &close_token);
     }
 
+    pub fn reconstruct_close_wallet_bls12(channel_token: &ChannelToken<Bls12>, wpk: &secp256k1::PublicKey, cust_bal: u32, merch_bal: u32) -> Wallet<Bls12> {
+        reconstruct_close_wallet(channel_token, wpk, cust_bal, merch_bal)
+    }
+
+    pub fn reconstruct_signature_bls12(sig: &Vec<u8>) -> BoltResult<cl::Signature<Bls12>> {
+        reconstruct_signature(sig)
+    }
+
+    pub fn reconstruct_channel_token_bls12(channel_token: &Vec<u8>) -> BoltResult<ChannelToken<Bls12>> {
+        reconstruct_channel_token(channel_token)
+    }
+
     pub fn wtp_generate_secp_signature(seckey: &[u8; 32], msg: &[u8; 32]) -> Vec<u8> {
         let secp = secp256k1::Secp256k1::signing_only();
 
@@ -679,17 +952,6 @@ pub mod wtp_utils {
     }
 }
 
-#[cfg(all(test, feature = "unstable"))]
-mod benches {
-    use rand::{Rng, thread_rng};
-    use test::{Bencher, black_box};
-
-    #[bench]
-    pub fn bench_one(bh: &mut Bencher) {
-        println!("Run benchmark tests here!");
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -699,7 +961,7 @@ mod tests {
 
     fn setup_new_channel_helper(channel_state: &mut bidirectional::ChannelState<Bls12>,
                                 init_cust_bal: i64, init_merch_bal: i64)
-                                -> (bidirectional::ChannelToken<Bls12>, bidirectional::MerchantState<Bls12>, bidirectional::CustomerState<Bls12>, bidirectional::ChannelState<Bls12>) {
+                                -> (bidirectional::ChannelToken<Bls12>, bidirectional::MerchantState<Bls12>, bidirectional::MerchantLedger<Bls12>, bidirectional::CustomerState<Bls12>, bidirectional::ChannelState<Bls12>) {
         let rng = &mut rand::thread_rng();
         let merch_name = "Bob";
         let cust_name = "Alice";
@@ -710,19 +972,20 @@ mod tests {
         // each party executes the init algorithm on the agreed initial challenge balance
         // in order to derive the channel tokens
         // initialize on the merchant side with balance: b0_merch
-        let (mut channel_token, merch_state, channel_state) = bidirectional::init_merchant(rng, channel_state, merch_name);
+        let (mut channel_token, merch_state, merch_ledger, channel_state) = bidirectional::init_merchant(rng, channel_state, merch_name);
 
         // initialize on the customer side with balance: b0_cust
-        let cust_state = bidirectional::init_customer(rng, &mut channel_token, b0_cust, b0_merch, cust_name);
+        let cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, b0_cust, b0_merch, cust_name).unwrap().unwrap();
 
-        return (channel_token, merch_state, cust_state, channel_state);
+        return (channel_token, merch_state, merch_ledger, cust_state, channel_state);
     }
 
     fn execute_establish_protocol_helper(channel_state: &mut bidirectional::ChannelState<Bls12>,
                                          channel_token: &mut bidirectional::ChannelToken<Bls12>,
                                          cust_balance: i64,
                                          merch_balance: i64,
-                                         merch_state: &mut bidirectional::MerchantState<Bls12>,
+                                         merch_state: &bidirectional::MerchantState<Bls12>,
+                                         merch_ledger: &mut bidirectional::MerchantLedger<Bls12>,
                                          cust_state: &mut bidirectional::CustomerState<Bls12>) {
         let rng = &mut rand::thread_rng();
 
@@ -732,7 +995,7 @@ mod tests {
         // obtain close token for closing out channel
         //let pk_h = hash_pubkey_to_fr::<Bls12>(&cust_state.pk_c.clone());
         let option = bidirectional::establish_merchant_issue_close_token(rng, &channel_state, &com, &com_proof, &cust_state.get_wallet().channelId,
-                                                                         cust_balance, merch_balance, &merch_state);
+                                                                         cust_balance, merch_balance, merch_state, merch_ledger);
         let close_token = match option {
             Ok(n) => n.unwrap(),
             Err(e) => panic!("Failed - bidirectional::establish_merchant_issue_close_token(): {}", e)
@@ -750,19 +1013,20 @@ mod tests {
     }
 
     fn execute_payment_protocol_helper(channel_state: &mut bidirectional::ChannelState<Bls12>,
-                                       merch_state: &mut bidirectional::MerchantState<Bls12>,
+                                       merch_state: &bidirectional::MerchantState<Bls12>,
+                                       merch_ledger: &mut bidirectional::MerchantLedger<Bls12>,
                                        cust_state: &mut bidirectional::CustomerState<Bls12>,
                                        payment_increment: i64) {
         let rng = &mut rand::thread_rng();
 
         let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, channel_state, &cust_state, payment_increment);
 
-        let new_close_token = bidirectional::verify_payment_proof(rng, &channel_state, &payment, merch_state);
+        let new_close_token = bidirectional::verify_payment_proof(rng, &channel_state, &payment, merch_state, merch_ledger);
 
         let revoke_token = bidirectional::generate_revoke_token(&channel_state, cust_state, new_cust_state, &new_close_token);
 
         // send revoke token and get pay-token in response
-        let new_pay_token_result: BoltResult<cl::Signature<Bls12>> = bidirectional::verify_revoke_token(&revoke_token, merch_state);
+        let new_pay_token_result: BoltResult<cl::Signature<Bls12>> = bidirectional::verify_revoke_token(&revoke_token, merch_ledger);
         let new_pay_token = handle_bolt_result!(new_pay_token_result);
 
         // verify the pay token and update internal state
@@ -778,9 +1042,9 @@ mod tests {
         let b0_customer = 90;
         let b0_merchant = 20;
 
-        let (mut channel_token, mut merch_state, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob");
+        let (mut channel_token, merch_state, mut merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob");
 
-        let mut cust_state = bidirectional::init_customer(rng, &mut channel_token, b0_customer, b0_merchant, "Alice");
+        let mut cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, b0_customer, b0_merchant, "Alice").unwrap().unwrap();
 
         println!("{}", cust_state);
 
@@ -789,7 +1053,7 @@ mod tests {
 
         // obtain close token for closing out channel
         let option = bidirectional::establish_merchant_issue_close_token(rng, &channel_state, &com, &com_proof, &cust_state.get_wallet().channelId,
-                                                                         b0_customer, b0_merchant, &merch_state);
+                                                                         b0_customer, b0_merchant, &merch_state, &mut merch_ledger);
         let close_token = match option {
             Ok(n) => n.unwrap(),
             Err(e) => panic!("Failed - bidirectional::establish_merchant_issue_close_token(): {}", e)
@@ -807,12 +1071,12 @@ mod tests {
 
         let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &channel_state, &cust_state, 10);
 
-        let new_close_token = bidirectional::verify_payment_proof(rng, &channel_state, &payment, &mut merch_state);
+        let new_close_token = bidirectional::verify_payment_proof(rng, &channel_state, &payment, &merch_state, &mut merch_ledger);
 
         let revoke_token = bidirectional::generate_revoke_token(&channel_state, &mut cust_state, new_cust_state, &new_close_token);
 
         // send revoke token and get pay-token in response
-        let new_pay_token_result: BoltResult<cl::Signature<Bls12>> = bidirectional::verify_revoke_token(&revoke_token, &mut merch_state);
+        let new_pay_token_result: BoltResult<cl::Signature<Bls12>> = bidirectional::verify_revoke_token(&revoke_token, &mut merch_ledger);
         let new_pay_token = handle_bolt_result!(new_pay_token_result);
 
         // verify the pay token and update internal state
@@ -839,10 +1103,10 @@ mod tests {
         let fee = 5;
         channel_state.set_channel_fee(fee);
 
-        let (mut channel_token, mut merch_state, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
+        let (mut channel_token, merch_state, mut merch_ledger, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
 
         // run establish protocol for customer and merchant channel
-        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &mut merch_state, &mut cust_state);
+        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &merch_state, &mut merch_ledger, &mut cust_state);
 
         assert!(channel_state.channel_established);
 
@@ -850,7 +1114,7 @@ mod tests {
             // make multiple payments in a loop
             let num_payments = total_owed / payment_increment;
             for _i in 0..num_payments {
-                execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, payment_increment);
+                execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, payment_increment);
             }
 
             {
@@ -868,6 +1132,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bidirectional_verify_multiple_independent_payments_works() {
+        let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
+        let rng = &mut rand::thread_rng();
+
+        let b0_customer = 90;
+        let b0_merchant = 20;
+        let (channel_token, merch_state, mut merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob");
+
+        // several customers open a channel with the same merchant
+        let mut payments = Vec::new();
+        let mut cust_states = Vec::new();
+        for i in 0..4 {
+            let mut cust_channel_token = channel_token.clone();
+            let mut cust_state = bidirectional::init_customer(rng, &channel_state, &mut cust_channel_token, b0_customer, b0_merchant, &format!("Customer {}", i)).unwrap().unwrap();
+            execute_establish_protocol_helper(&mut channel_state, &mut cust_channel_token, b0_customer, b0_merchant, &merch_state, &mut merch_ledger, &mut cust_state);
+
+            let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &channel_state, &cust_state, 5 + i);
+            payments.push(payment);
+            cust_states.push((cust_state, new_cust_state));
+        }
+
+        // tamper with the amount of the last payment
+        let mut tampered = serde_json::to_value(&payments[3]).unwrap();
+        tampered["amount"] = serde_json::Value::from(100);
+        payments[3] = serde_json::from_value(tampered).unwrap();
+
+        let close_tokens = bidirectional::verify_payment_proofs(rng, &channel_state, &payments, &merch_state, &mut merch_ledger);
+        assert_eq!(close_tokens.len(), 4);
+        assert!(close_tokens[3].is_err());
+
+        for (i, (mut cust_state, new_cust_state)) in cust_states.into_iter().take(3).enumerate() {
+            let new_close_token = close_tokens[i].as_ref().unwrap();
+            let revoke_token = bidirectional::generate_revoke_token(&channel_state, &mut cust_state, new_cust_state, new_close_token);
+            let new_pay_token_result: BoltResult<cl::Signature<Bls12>> = bidirectional::verify_revoke_token(&revoke_token, &mut merch_ledger);
+            let new_pay_token = handle_bolt_result!(new_pay_token_result);
+            assert!(cust_state.verify_pay_token(&channel_state, &new_pay_token.unwrap()));
+        }
+    }
+
+    #[test]
+    fn bidirectional_key_rotation_works() {
+        let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
+        let rng = &mut rand::thread_rng();
+
+        let b0_customer = 90;
+        let b0_merchant = 20;
+        let (channel_token, mut merch_state, mut merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant Bob");
+
+        // two customers open a channel with the merchant, one of them makes a payment
+        let mut alice_channel_token = channel_token.clone();
+        let mut alice_state = bidirectional::init_customer(rng, &channel_state, &mut alice_channel_token, b0_customer, b0_merchant, "Alice").unwrap().unwrap();
+        execute_establish_protocol_helper(&mut channel_state, &mut alice_channel_token, b0_customer, b0_merchant, &merch_state, &mut merch_ledger, &mut alice_state);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut alice_state, 10);
+
+        let mut carol_channel_token = channel_token.clone();
+        let mut carol_state = bidirectional::init_customer(rng, &channel_state, &mut carol_channel_token, b0_customer, b0_merchant, "Carol").unwrap().unwrap();
+        execute_establish_protocol_helper(&mut channel_state, &mut carol_channel_token, b0_customer, b0_merchant, &merch_state, &mut merch_ledger, &mut carol_state);
+
+        // the merchant rotates its keys (only one rotation at a time)
+        let (mut new_channel_token, mut new_channel_state) = bidirectional::rotate_merchant_keys(rng, &channel_state, &mut merch_state).unwrap().unwrap();
+        assert!(new_channel_token.cl_pk_m != channel_token.cl_pk_m);
+        assert!(new_channel_token.pk_m == channel_token.pk_m);
+        assert!(bidirectional::rotate_merchant_keys(rng, &new_channel_state, &mut merch_state).is_err());
+
+        // alice migrates her wallet to the new keys
+        let (migration, new_alice_state) = bidirectional::generate_migration_proof(rng, &channel_state, &new_channel_state, &mut new_channel_token, &alice_state).unwrap().unwrap();
+        let new_close_token = bidirectional::verify_migration_proof(rng, &new_channel_state, &migration, &merch_state, &mut merch_ledger).unwrap().unwrap();
+        // a channel migrates only once
+        assert!(bidirectional::verify_migration_proof(rng, &new_channel_state, &migration, &merch_state, &mut merch_ledger).is_err());
+
+        let revoke_token = bidirectional::generate_revoke_token(&new_channel_state, &mut alice_state, new_alice_state, &new_close_token);
+        let new_pay_token = bidirectional::verify_revoke_token(&revoke_token, &mut merch_ledger).unwrap().unwrap();
+        assert!(alice_state.verify_pay_token(&new_channel_state, &new_pay_token));
+        assert_eq!(alice_state.cust_balance, b0_customer - 10);
+        assert_eq!(alice_state.merch_balance, b0_merchant + 10);
+
+        let alice_channel_id = alice_state.get_wallet().channelId;
+        assert_eq!(merch_ledger.get_channel(&alice_channel_id).unwrap().key_epoch, 1);
+
+        // the old keys cannot be retired until carol migrates or closes
+        assert!(bidirectional::retire_merchant_keys(&mut merch_state, &merch_ledger).is_err());
+        // carol closes under the old keys, which marks her channel as closed in the ledger
+        let carol_close_msg = bidirectional::customer_close(&channel_state, &carol_state);
+        assert!(bidirectional::merchant_close(&channel_state, &carol_channel_token, &carol_close_msg, &mut merch_ledger).is_err());
+        assert!(merch_ledger.get_channel(&carol_state.get_wallet().channelId).unwrap().closed);
+        assert!(bidirectional::retire_merchant_keys(&mut merch_state, &merch_ledger).is_ok());
+
+        // alice keeps paying on the same channel under the new keys
+        execute_payment_protocol_helper(&mut new_channel_state, &merch_state, &mut merch_ledger, &mut alice_state, 10);
+        assert_eq!(alice_state.get_wallet().channelId, alice_channel_id);
+        assert_eq!(alice_state.cust_balance, b0_customer - 20);
+
+        // the merchant signs alice's new channel token, which the WTP accepts for the channel token in escrow
+        let alice_new_channel_token = new_channel_token.clone();
+        let update = bidirectional::sign_channel_token_update(&new_channel_state, &merch_state, &merch_ledger, &alice_channel_token, &alice_new_channel_token).unwrap().unwrap();
+        assert!(bidirectional::sign_channel_token_update(&new_channel_state, &merch_state, &merch_ledger, &carol_channel_token, &alice_new_channel_token).is_err());
+
+        let alice_close_msg = bidirectional::customer_close(&new_channel_state, &alice_state);
+        let mut close_wallet = alice_close_msg.message.clone();
+        close_wallet.with_close(String::from("close"));
+        assert!(bidirectional::wtp_verify_migrated_cust_close_message(&alice_channel_token, &update, &alice_close_msg.wpk, &close_wallet, &alice_close_msg.signature));
+        // the close token is not valid under the keys of the channel token in escrow
+        assert!(!alice_channel_token.cl_pk_m.verify(&alice_channel_token.mpk, &close_wallet.as_fr_vec(), &alice_close_msg.signature));
+        // nor is the update valid for another channel, or once tampered with
+        assert!(!bidirectional::wtp_verify_migrated_cust_close_message(&carol_channel_token, &update, &alice_close_msg.wpk, &close_wallet, &alice_close_msg.signature));
+        let mut forged_update = update.clone();
+        forged_update.channel_token.cl_pk_m = alice_channel_token.cl_pk_m.clone();
+        assert!(!bidirectional::wtp_verify_migrated_cust_close_message(&alice_channel_token, &forged_update, &alice_close_msg.wpk, &close_wallet, &alice_close_msg.signature));
+
+        // no more migrations once the old keys are retired
+        let (migration, _) = bidirectional::generate_migration_proof(rng, &channel_state, &new_channel_state, &mut new_channel_token, &carol_state).unwrap().unwrap();
+        assert!(bidirectional::verify_migration_proof(rng, &new_channel_state, &migration, &merch_state, &mut merch_ledger).is_err());
+    }
+
     #[test]
     fn bidirectional_payment_negative_payment_works() {
         // just bidirectional case (w/o third party)
@@ -878,14 +1257,14 @@ mod tests {
 
         let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
 
-        let (mut channel_token, mut merch_state, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
+        let (mut channel_token, merch_state, mut merch_ledger, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
 
         // run establish protocol for customer and merchant channel
-        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &mut merch_state, &mut cust_state);
+        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &merch_state, &mut merch_ledger, &mut cust_state);
         assert!(channel_state.channel_established);
 
         {
-            execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, payment_increment);
+            execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, payment_increment);
 
             {
                 // scope localizes the immutable borrow here (for debug purposes only)
@@ -906,30 +1285,30 @@ mod tests {
 
         let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
 
-        let (mut channel_token, mut merch_state, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
+        let (mut channel_token, merch_state, mut merch_ledger, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
 
         // run establish protocol for customer and merchant channel
-        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &mut merch_state, &mut cust_state);
+        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &merch_state, &mut merch_ledger, &mut cust_state);
 
         assert!(channel_state.channel_established);
 
         // let's make a few payments then exit channel (will post an old channel state
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
 
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
 
         // let's close then move state forward
         let old_cust_close_msg = bidirectional::customer_close(&channel_state, &cust_state);
 
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
 
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
         let _cur_cust_close_msg = bidirectional::customer_close(&channel_state, &cust_state);
 
         let merch_close_result = bidirectional::merchant_close(&channel_state,
                                                                &channel_token,
                                                                &old_cust_close_msg,
-                                                               &merch_state);
+                                                               &mut merch_ledger);
         let merch_close_msg = match merch_close_result {
             Ok(n) => n.unwrap(),
             Err(err) => panic!("Merchant close msg: {}", err)
@@ -951,28 +1330,28 @@ mod tests {
 
         let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
 
-        let (mut channel_token, mut merch_state, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
+        let (mut channel_token, merch_state, mut merch_ledger, mut cust_state, mut channel_state) = setup_new_channel_helper(&mut channel_state, b0_customer, b0_merchant);
 
         // run establish protocol for customer and merchant channel
-        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &mut merch_state, &mut cust_state);
+        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_customer, b0_merchant, &merch_state, &mut merch_ledger, &mut cust_state);
 
         assert!(channel_state.channel_established);
 
         // let's make a few payments then exit channel (will post an old channel state
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
 
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
 
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
 
-        execute_payment_protocol_helper(&mut channel_state, &mut merch_state, &mut cust_state, pay_increment);
+        execute_payment_protocol_helper(&mut channel_state, &merch_state, &mut merch_ledger, &mut cust_state, pay_increment);
 
         let cust_close_msg = bidirectional::customer_close(&channel_state, &cust_state);
 
         let merch_close_result = bidirectional::merchant_close(&channel_state,
                                                                &channel_token,
                                                                &cust_close_msg,
-                                                               &merch_state);
+                                                               &mut merch_ledger);
         let _merch_close_msg = match merch_close_result {
             Ok(n) => n.unwrap(),
             Err(err) => panic!("Merchant close msg: {}", err)
@@ -997,19 +1376,19 @@ mod tests {
         // each party executes the init algorithm on the agreed initial challenge balance
         // in order to derive the channel tokens
         // initialize on the merchant side with balance: b0_merch
-        let (mut channel_token, mut merch_state, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, merch_name);
+        let (mut channel_token, merch_state, mut merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, merch_name);
 
         // initialize on the customer side with balance: b0_cust
-        let mut alice_cust_state = bidirectional::init_customer(rng, &mut channel_token, b0_alice, b0_merch_a, "Alice");
+        let mut alice_cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, b0_alice, b0_merch_a, "Alice").unwrap().unwrap();
 
-        let mut bob_cust_state = bidirectional::init_customer(rng, &mut channel_token, b0_bob, b0_merch_b, "Bob");
+        let mut bob_cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, b0_bob, b0_merch_b, "Bob").unwrap().unwrap();
 
         // run establish protocol for customer and merchant channel
         //let mut channel_state_alice = channel_state.clone();
         //let mut channel_state_bob = channel_state.clone();
 
-        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_alice, b0_merch_a, &mut merch_state, &mut alice_cust_state);
-        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_bob, b0_merch_b, &mut merch_state, &mut bob_cust_state);
+        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_alice, b0_merch_a, &merch_state, &mut merch_ledger, &mut alice_cust_state);
+        execute_establish_protocol_helper(&mut channel_state, &mut channel_token, b0_bob, b0_merch_b, &merch_state, &mut merch_ledger, &mut bob_cust_state);
 
         assert!(channel_state.channel_established);
         //assert!(channel_state_bob.channel_established);
@@ -1023,8 +1402,16 @@ mod tests {
 
         // TODO: figure out how to attach conditions on payment recipients close token that they must (1) produce revocation token for sender's old wallet and (2) must have channel open
 
+        // a receiver payment that does not verify is an error, and leaves the ledger unchanged
+        let mut bad_receiver_payment = serde_json::to_value(&receiver_payment).unwrap();
+        bad_receiver_payment["com"] = serde_json::to_value(&sender_payment).unwrap()["com"].clone();
+        let bad_receiver_payment: bidirectional::Payment<Bls12> = serde_json::from_value(bad_receiver_payment).unwrap();
+        let num_keys = merch_ledger.keys.len();
+        assert!(bidirectional::verify_multiple_payment_proofs(rng, &channel_state, &sender_payment, &bad_receiver_payment, &merch_state, &mut merch_ledger).is_err());
+        assert_eq!(merch_ledger.keys.len(), num_keys);
+
         // intermediary executes the following on the two payment proofs
-        let close_token_result = bidirectional::verify_multiple_payment_proofs(rng, &channel_state, &sender_payment, &receiver_payment, &mut merch_state);
+        let close_token_result = bidirectional::verify_multiple_payment_proofs(rng, &channel_state, &sender_payment, &receiver_payment, &merch_state, &mut merch_ledger);
         let (alice_close_token, bob_cond_close_token) = handle_bolt_result!(close_token_result).unwrap();
 
         // both alice and bob generate a revoke token
@@ -1032,7 +1419,7 @@ mod tests {
         let revoke_token_bob = bidirectional::generate_revoke_token(&channel_state, &mut bob_cust_state, new_bob_cust_state, &bob_cond_close_token);
 
         // send both revoke tokens to intermediary and get pay-tokens in response
-        let new_pay_token_result: BoltResult<(cl::Signature<Bls12>,cl::Signature<Bls12>)> = bidirectional::verify_multiple_revoke_tokens(&revoke_token_alice, &revoke_token_bob, &mut merch_state);
+        let new_pay_token_result: BoltResult<(cl::Signature<Bls12>,cl::Signature<Bls12>)> = bidirectional::verify_multiple_revoke_tokens(&revoke_token_alice, &revoke_token_bob, &mut merch_ledger);
         let (new_pay_token_alice, new_pay_token_bob) = handle_bolt_result!(new_pay_token_result).unwrap();
 
         // verify the pay tokens and update internal state
@@ -1052,11 +1439,11 @@ mod tests {
 
         let _chan_state: bidirectional::ChannelState<Bls12> = serde_json::from_str(&serialized).unwrap();
 
-        let (mut channel_token, _merch_state, _channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant A");
+        let (mut channel_token, _merch_state, _merch_ledger, channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant A");
 
         let b0_cust = 100;
         let b0_merch = 10;
-        let cust_state = bidirectional::init_customer(rng, &mut channel_token, b0_cust, b0_merch, "Customer A");
+        let cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, b0_cust, b0_merch, "Customer A").unwrap().unwrap();
 
         let serialized_ct = serde_json::to_string(&channel_token).unwrap();
 
@@ -1066,11 +1453,51 @@ mod tests {
 
         //println!("des_ct: {}", &des_ct);
 
-        let serialized_cw = serde_json::to_string(&cust_state).unwrap();
+        let serialized_cw = serde_json::to_string(&cust_state.export()).unwrap();
 
         println!("serialized cw: {:?}", &serialized_cw);
 
-        let _des_cw: bidirectional::CustomerState<Bls12> = serde_json::from_str(&serialized_cw).unwrap();
+        let des_cw: bidirectional::CustomerStateExport<Bls12> = serde_json::from_str(&serialized_cw).unwrap();
+        let _cust_state = bidirectional::CustomerState::import(des_cw);
+    }
+
+    #[test]
+    fn init_customer_rejects_invalid_range_proof_params() {
+        let rng = &mut rand::thread_rng();
+        let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
+        let (mut channel_token, _merch_state, _merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant A");
+
+        let uninit_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
+        assert!(bidirectional::init_customer(rng, &uninit_state, &mut channel_token.clone(), 100, 10, "Alice").is_err());
+
+        channel_state.cp.as_mut().unwrap().pub_params.rpParams.signatures.swap(0, 1);
+        let err = bidirectional::init_customer(rng, &channel_state, &mut channel_token, 100, 10, "Alice").err().unwrap();
+        assert!(err.starts_with("init_customer - invalid range proof parameters"));
+        assert!(channel_token.pk_c.is_none());
+    }
+
+    #[test]
+    fn init_customer_rejects_negative_balances() {
+        let rng = &mut rand::thread_rng();
+        let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
+        let (mut channel_token, _merch_state, _merch_ledger, channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant A");
+
+        let err = bidirectional::init_customer(rng, &channel_state, &mut channel_token, -1, 10, "Alice").err().unwrap();
+        assert_eq!(err, "init_customer - negative initial balance: customer = -1, merchant = 10");
+        assert!(bidirectional::init_customer(rng, &channel_state, &mut channel_token, 100, -10, "Alice").is_err());
+        assert!(channel_token.pk_c.is_none());
+    }
+
+    #[test]
+    fn init_customer_rejects_invalid_channel_token() {
+        let rng = &mut rand::thread_rng();
+        let mut channel_state = bidirectional::ChannelState::<Bls12>::new(String::from("Channel A -> B"), false);
+        let (mut channel_token, _merch_state, _merch_ledger, channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Merchant A");
+
+        channel_token.comParams.pub_bases.swap(1, 2);
+        let err = bidirectional::init_customer(rng, &channel_state, &mut channel_token, 100, 10, "Alice").err().unwrap();
+        assert_eq!(err, "init_customer - invalid channel token: comParams are not derived from the merchant public key");
+        assert!(channel_token.pk_c.is_none());
     }
 
     #[test]
@@ -1103,7 +1530,13 @@ mod tests {
 
         let channelId = channel_token.compute_channel_id();
 
-        let original_channelId = "[\"0744645c9cbbf4e47f456fa05e2c6888a59f688641d25b2607610ce03b4ae20c\"]";
+        // the channel id is computed over the canonical encoding, which is exactly the compact rep
+        assert_eq!(channel_token.to_bytes(), ser_channel_token);
+
+        let original_channelId = match util::HASH_TO_FIELD_VERSION {
+            util::HashToFieldVersion::Legacy => "[\"020870001ed62d68f894e7954119cd12ce24c1dda81ab1e96a2a26c0a987b5ff\"]",
+            util::HashToFieldVersion::XmdSha256 => "[\"d41e3c3dd50eefffe9b615f39d9c6f1961447e9720a7527a66a715a741d3b757\"]",
+        };
         let computed_channelId = serde_json::to_string(&channelId).unwrap();
 
         println!("channel ID: {}", channelId);
@@ -1111,6 +1544,7 @@ mod tests {
         println!("pkm: {:?}", channel_token.pk_m);
 
         assert_eq!(original_channelId, computed_channelId);
+        assert_eq!(hex::encode(channel_token.compute_channel_id_hash160()), "d64c902057449fd264dcc441a17d325d531ddbf1");
 
         // reconstruct signature
         let _ser_signature = "93f26490b4576c38dfb8dceae547f4b49aeb945ecc9cccc528c39068c78177bda68aaf45743f09c48ad99b6007fe415b\
@@ -1159,4 +1593,47 @@ mod tests {
         assert_eq!(format!("{:?}", channelClose.revoke.unwrap()), "3044022041932b376fe2c5e9e9ad0a3804e2290c3bc40617ea4f7b913be858dbcc3760b50220429d6eb1aabbd4135db4e0776c0b768af844b0af44f2f8f9da5a65e8541b4e9f");
         assert_eq!(format!("{:?}", channelClose.signature), "3045022100e76653c5f8cb4c2f39efc7c5450d4f68ef3d84d482305534f5dfc310095a3124022003c4651ce1305cffe5e483ab99925cc4c9c5df2b5449bb18a51d52b21d789716");
     }
+
+    #[test]
+    fn wtp_channel_token_len_matches_encoding() {
+        use wtp_utils::WTPEngine;
+        assert_eq!(<Bls12 as WTPEngine>::CHANNEL_TOKEN_LEN, 1074);
+        assert_eq!(<pairing::bn256::Bn256 as WTPEngine>::CHANNEL_TOKEN_LEN, 738);
+    }
+
+    #[test]
+    fn wtp_utils_bn256_roundtrip_works() {
+        use pairing::bn256::Bn256;
+        let rng = &mut rand::thread_rng();
+
+        let mut channel_state = bidirectional::ChannelState::<Bn256>::new(String::from("Channel A <-> B"), false);
+        let (mut channel_token, merch_state, mut merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Bob");
+        let mut cust_state = bidirectional::init_customer(rng, &channel_state, &mut channel_token, 100, 10, "Alice").unwrap().unwrap();
+
+        let (com, com_proof) = bidirectional::establish_customer_generate_proof(rng, &channel_token, &cust_state);
+        let close_token = bidirectional::establish_merchant_issue_close_token(rng, &channel_state, &com, &com_proof, &cust_state.get_wallet().channelId,
+                                                                              100, 10, &merch_state, &mut merch_ledger).unwrap().unwrap();
+        assert!(cust_state.verify_close_token(&channel_state, &close_token));
+        let pay_token = bidirectional::establish_merchant_issue_pay_token(rng, &channel_state, &com, &merch_state);
+        assert!(bidirectional::establish_customer_final(&mut channel_state, &mut cust_state, &pay_token));
+
+        let ser_channel_token = channel_token.to_bytes();
+        assert_eq!(ser_channel_token.len(), <Bn256 as wtp_utils::WTPEngine>::CHANNEL_TOKEN_LEN);
+
+        let rec_channel_token = wtp_utils::reconstruct_channel_token::<Bn256>(&ser_channel_token).unwrap().unwrap();
+        assert_eq!(rec_channel_token.to_bytes(), ser_channel_token);
+        assert_eq!(rec_channel_token.compute_channel_id(), channel_token.compute_channel_id());
+        assert!(wtp_utils::reconstruct_channel_token::<Bn256>(&ser_channel_token[1..].to_vec()).is_err());
+
+        let cust_close = bidirectional::customer_close(&channel_state, &cust_state);
+        let mut ser_signature = util::compress_point(&cust_close.signature.h);
+        ser_signature.extend(util::compress_point(&cust_close.signature.H));
+        let rec_signature = wtp_utils::reconstruct_signature::<Bn256>(&ser_signature).unwrap().unwrap();
+        assert!(rec_signature == cust_close.signature);
+
+        let close_wallet = wtp_utils::reconstruct_close_wallet(&rec_channel_token, &cust_close.wpk, 100, 10);
+        assert!(wtp_utils::wtp_verify_cust_close_message(&rec_channel_token, &cust_close.wpk, &close_wallet, &rec_signature));
+        let wrong_wallet = wtp_utils::reconstruct_close_wallet(&rec_channel_token, &cust_close.wpk, 99, 11);
+        assert!(!wtp_utils::wtp_verify_cust_close_message(&rec_channel_token, &cust_close.wpk, &wrong_wallet, &rec_signature));
+    }
 }
@@ -1,12 +1,16 @@
 use super::*;
 use rand::Rng;
-use cl::{Signature, PublicParams, setup, BlindKeyPair, SignatureProof, BlindPublicKey};
+use cl::{Signature, PublicParams, SecretKey, setup_from_seed, BlindKeyPair, SignatureProof, BlindPublicKey, PreparedBlindPublicKey};
 use ped92::{Commitment, CSMultiParams, CommitmentProof};
 use pairing::{Engine, CurveProjective};
 use wallet::Wallet;
-use ccs08::{SecretParamsUL, ParamsUL, ProofUL};
+use ccs08::{SecretParamsUL, ParamsUL, ProofUL, RANGE_PROOF_PUBLIC_PARAMS_SEED};
 use serde::{Serialize, Deserialize};
 use util;
+use util::Validate;
+
+/// Public seed of the public params of the merchant keys (see cl::setup_from_seed)
+pub const NIZK_PUBLIC_PARAMS_SEED: &'static [u8] = b"libbolt nizk public params";
 
 /// NIZKProof is the object that represents the NIZK Proof of Knowledge during the payment and closing protocol
 #[derive(Clone, Serialize, Deserialize)]
@@ -28,6 +32,16 @@ pub struct NIZKProof<E: Engine> {
     pub rpBM: ProofUL<E>,
 }
 
+impl<E: Engine> Validate for NIZKProof<E> {
+    fn validate(&self) -> Result<(), String> {
+        self.sig.validate()?;
+        self.sigProof.validate()?;
+        self.comProof.validate()?;
+        self.rpBC.validate().map_err(|err| format!("rpBC: {}", err))?;
+        self.rpBM.validate().map_err(|err| format!("rpBM: {}", err))
+    }
+}
+
 /// NIZKPublicParams are public parameters to perform a NIZK Proof of Knowledge during the payment and closing protocol
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize, \
@@ -55,25 +69,70 @@ pub struct NIZKPublicParams<E: Engine> {
 <E as pairing::Engine>::G1: serde::Deserialize<'de>, \
 <E as pairing::Engine>::G2: serde::Deserialize<'de>"
 ))]
+#[serde(from = "NIZKSecretParamsRepr<E>")]
 pub struct NIZKSecretParams<E: Engine> {
     pub pubParams: NIZKPublicParams<E>,
     pub keypair: BlindKeyPair<E>,
     pub rpParams: SecretParamsUL<E>,
+    // prepared G2 elements of keypair.public, not serialized but rebuilt on deserialization (see prepare())
+    #[serde(skip)]
+    preparedPk: Option<PreparedBlindPublicKey<E>>,
+}
+
+// NIZKSecretParams as serialized, which is deserialized and then prepared
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "<E as ff::ScalarEngine>::Fr: serde::Deserialize<'de>, \
+<E as pairing::Engine>::G1: serde::Deserialize<'de>, \
+<E as pairing::Engine>::G2: serde::Deserialize<'de>"
+))]
+struct NIZKSecretParamsRepr<E: Engine> {
+    pubParams: NIZKPublicParams<E>,
+    keypair: BlindKeyPair<E>,
+    rpParams: SecretParamsUL<E>,
+}
+
+impl<E: Engine> From<NIZKSecretParamsRepr<E>> for NIZKSecretParams<E> {
+    fn from(repr: NIZKSecretParamsRepr<E>) -> Self {
+        // the range proof params are prepared by their own deserialization
+        let preparedPk = Some(repr.keypair.public.prepare(&repr.pubParams.mpk));
+        NIZKSecretParams { pubParams: repr.pubParams, keypair: repr.keypair, rpParams: repr.rpParams, preparedPk }
+    }
 }
 
 impl<E: Engine> NIZKSecretParams<E> {
     /// Basic setup for the NIZKPublicParams
     /// Takes as input a random generator and the length of the message which should be 4 during payment protocol and 5 for the closing protocol
     pub fn setup<R: Rng>(rng: &mut R, messageLength: usize) -> Self {
-        let mpk = setup(rng);
+        let mpk = setup_from_seed(NIZK_PUBLIC_PARAMS_SEED);
         let keypair = BlindKeyPair::<E>::generate(rng, &mpk, messageLength);
         let comParams = keypair.generate_cs_multi_params(&mpk);
         let u = 128; //TODO: make u and l configurable
         let l = 9;
         let rpParams = SecretParamsUL::setup_ul(rng, u, l, comParams.clone());
         let pubParams = NIZKPublicParams { mpk, pk: keypair.public.clone(), comParams, rpParams: rpParams.pubParams.clone() };
+        let preparedPk = Some(keypair.public.prepare(&pubParams.mpk));
+
+        NIZKSecretParams { pubParams, keypair, rpParams, preparedPk }
+    }
 
-        NIZKSecretParams { pubParams, keypair, rpParams }
+    /// Restores the secret params from the public params, the merchant signing key and the signing
+    /// key of the range proofs. Returns None if either key does not match the public params.
+    pub fn from_secrets(pubParams: NIZKPublicParams<E>, secret: SecretKey<E>, rpSecret: SecretKey<E>) -> Option<Self> {
+        let public = BlindPublicKey::from_secret(&pubParams.mpk, &secret);
+        if public != pubParams.pk {
+            return None;
+        }
+        let rpParams = SecretParamsUL::from_secret(pubParams.rpParams.clone(), rpSecret)?;
+        let preparedPk = Some(public.prepare(&pubParams.mpk));
+
+        Some(NIZKSecretParams { pubParams, keypair: BlindKeyPair { secret, public }, rpParams, preparedPk })
+    }
+
+    /// Caches the prepared G2 elements of the merchant keys used by verify().
+    /// The cache is not serialized, and is rebuilt when the params are deserialized.
+    pub fn prepare(&mut self) {
+        self.preparedPk = Some(self.keypair.public.prepare(&self.pubParams.mpk));
+        self.rpParams.prepare();
     }
 
     /**
@@ -85,24 +144,67 @@ impl<E: Engine> NIZKSecretParams<E> {
         wpk: reveal of wallet public key of the old wallet.
     */
     pub fn verify(&self, proof: NIZKProof<E>, epsilon: E::Fr, com: &Commitment<E>, wpk: E::Fr) -> bool {
+        let prepared;
+        let preparedPk = match self.preparedPk {
+            Some(ref pk) => pk,
+            None => {
+                prepared = self.keypair.public.prepare(&self.pubParams.mpk);
+                &prepared
+            }
+        };
+        NIZKSecretParams::verify_under(preparedPk, &self.pubParams.comParams, &self.rpParams, proof, epsilon, com, wpk, None)
+    }
+
+    /**
+        Verify a NIZK Proof of Knowledge during the key rotation protocol (see NIZKPublicParams::prove_migration)
+        Input:
+        oldParams: the secret params of the retired merchant key, which signed the old wallet
+        proof: A NIZK proof created by the Customer
+        com: Commitment of the new wallet under the commitment bases of self, that needs to be signed
+        wpk: reveal of wallet public key of the old wallet.
+        channelId: reveal of the channel id of both wallets
+    */
+    pub fn verify_migration(&self, oldParams: &NIZKSecretParams<E>, proof: NIZKProof<E>, com: &Commitment<E>, wpk: E::Fr, channelId: E::Fr) -> bool {
+        let prepared;
+        let preparedPk = match oldParams.preparedPk {
+            Some(ref pk) => pk,
+            None => {
+                prepared = oldParams.keypair.public.prepare(&oldParams.pubParams.mpk);
+                &prepared
+            }
+        };
+        NIZKSecretParams::verify_under(preparedPk, &self.pubParams.comParams, &self.rpParams, proof, E::Fr::zero(), com, wpk, Some(channelId))
+    }
+
+    // verifies the signature proof under preparedPk and the commitment and range proofs under
+    // comParams/rpParams, optionally checking that the channel id of the new wallet is revealed
+    fn verify_under(preparedPk: &PreparedBlindPublicKey<E>, comParams: &CSMultiParams<E>, rpParams: &SecretParamsUL<E>,
+                    proof: NIZKProof<E>, epsilon: E::Fr, com: &Commitment<E>, wpk: E::Fr, channelId: Option<E::Fr>) -> bool {
+        //reject points at infinity and outside the prime order subgroups
+        if proof.validate().is_err() || com.validate().is_err() {
+            return false;
+        }
+        //reject truncated or padded responses before indexing into them
+        if proof.sigProof.zsig.len() != preparedPk.Y2.len() || proof.comProof.z.len() != comParams.pub_bases.len() {
+            return false;
+        }
         //verify signature is not the identity
         let r0 = proof.sig.h != E::G1::one();
 
         //compute challenge
         let challenge = NIZKPublicParams::<E>::hash(proof.sigProof.a, vec! {proof.comProof.T, proof.rpBC.D, proof.rpBM.D});
 
-        //verify knowledge of signature
-        let mut r1 = self.keypair.public.verify_proof(&self.pubParams.mpk, proof.sig, proof.sigProof.clone(), challenge);
         let mut wpkc = wpk.clone();
         wpkc.mul_assign(&challenge.clone());
-        r1 = r1 && proof.sigProof.zsig[1] == wpkc;
+        let reveal = channelId.map(|id| vec! {None, Some(id)});
 
-        //verify knowledge of commitment
-        let r2 = proof.comProof.verify_proof(&self.pubParams.comParams, &com.c.clone(), &challenge, None);
-
-        //verify range proofs
-        let r3 = self.rpParams.verify_ul(&proof.rpBC.clone(), challenge.clone(), 3);
-        let r4 = self.rpParams.verify_ul(&proof.rpBM.clone(), challenge.clone(), 4);
+        //verify knowledge of signature and commitment, and the range proofs
+        //(the sub-proofs are independent and checked concurrently with the `parallel` feature)
+        let ((r1, r2), (r3, r4)) = util::join(
+            || util::join(|| preparedPk.verify_proof(&proof.sig, &proof.sigProof, challenge) && proof.sigProof.zsig[1] == wpkc,
+                          || proof.comProof.verify_proof(comParams, &com.c, &challenge, reveal)),
+            || util::join(|| rpParams.verify_ul(&proof.rpBC, challenge.clone(), 3),
+                          || rpParams.verify_ul(&proof.rpBM, challenge.clone(), 4)));
 
         //verify linear relationship
         let mut r5 = proof.comProof.z[1] == proof.sigProof.zsig[0];
@@ -120,6 +222,12 @@ impl<E: Engine> NIZKSecretParams<E> {
 }
 
 impl<E: Engine> NIZKPublicParams<E> {
+    /// Recomputes the public params of the merchant keys and of the range proofs from their public
+    /// seeds, so that the customer can check that the merchant did not pick them (no trusted setup)
+    pub fn verify_public_params(&self) -> bool {
+        self.mpk.verify_seed(NIZK_PUBLIC_PARAMS_SEED) && self.rpParams.mpk.verify_seed(RANGE_PROOF_PUBLIC_PARAMS_SEED)
+    }
+
     /** This method can be called to create the proof during the payment and closing protocol
         Input:
         rng: random generator
@@ -133,6 +241,32 @@ impl<E: Engine> NIZKPublicParams<E> {
     */
     pub fn prove<R: Rng>(&self, rng: &mut R, oldWallet: Wallet<E>, newWallet: Wallet<E>,
                          newWalletCom: Commitment<E>, rPrime: E::Fr, paymentToken: &Signature<E>) -> NIZKProof<E> {
+        self.prove_with(rng, &self.pk, oldWallet, newWallet, newWalletCom, rPrime, paymentToken, None)
+    }
+
+    /** This method can be called to create the proof during the key rotation protocol, where the old wallet
+        is signed under a retired merchant key and the new wallet is committed under the bases of self.
+        The balances of both wallets must be the same and the channel id is revealed to the merchant.
+        Input:
+        rng: random generator
+        oldPk: The retired merchant public key that signed the old wallet
+        oldWallet: This is the wallet before the key rotation
+        newWallet: This is the wallet to be signed under the new merchant key
+        newWalletCom: A commitment of the new wallet
+        rPrime: blinding value of commitment of new wallet
+        paymentToken: A signature on the old wallet under oldPk
+        Output:
+        NIZKProof: a proof that can be verified by the merchant with NIZKSecretParams::verify_migration
+    */
+    pub fn prove_migration<R: Rng>(&self, rng: &mut R, oldPk: &BlindPublicKey<E>, oldWallet: Wallet<E>, newWallet: Wallet<E>,
+                                   newWalletCom: Commitment<E>, rPrime: E::Fr, paymentToken: &Signature<E>) -> NIZKProof<E> {
+        // no blinding for the channel id, so that its response reveals it
+        let tOptional = Some(vec!(E::Fr::rand(rng), E::Fr::zero()));
+        self.prove_with(rng, oldPk, oldWallet, newWallet, newWalletCom, rPrime, paymentToken, tOptional)
+    }
+
+    fn prove_with<R: Rng>(&self, rng: &mut R, pk: &BlindPublicKey<E>, oldWallet: Wallet<E>, newWallet: Wallet<E>,
+                          newWalletCom: Commitment<E>, rPrime: E::Fr, paymentToken: &Signature<E>, comT: Option<Vec<E::Fr>>) -> NIZKProof<E> {
         //Commitment phase
         //commit commitment
         let w_len = newWallet.as_fr_vec().len();
@@ -142,7 +276,7 @@ impl<E: Engine> NIZKPublicParams<E> {
             false => self.comParams.pub_bases.len()
         };
 
-        let (D, t) = CommitmentProof::<E>::prove_commitment(rng, &self.comParams, &newWallet.as_fr_vec(), None);
+        let (D, t) = CommitmentProof::<E>::prove_commitment(rng, &self.comParams, &newWallet.as_fr_vec(), comT);
 
         //commit signature
         let zero = E::Fr::zero();
@@ -150,7 +284,7 @@ impl<E: Engine> NIZKPublicParams<E> {
             true => Some(vec!(t[1], zero, t[3].clone(), t[4].clone())),
             false => Some(vec!(t[1], zero, t[3].clone()))
         };
-        let proofState = self.pk.prove_commitment(rng, &self.mpk, &paymentToken, tOptional, None);
+        let proofState = pk.prove_commitment(rng, &self.mpk, &paymentToken, tOptional, None);
 
         //commit range proof
         let rpStateBC = self.rpParams.prove_ul_commitment(rng, newWallet.bc.clone(), 3, None, None);
@@ -162,7 +296,7 @@ impl<E: Engine> NIZKPublicParams<E> {
         //Response phase
         //response for signature
         let oldWalletVec = oldWallet.as_fr_vec();
-        let sigProof = self.pk.prove_response(&proofState, challenge, &mut oldWalletVec.clone());
+        let sigProof = pk.prove_response(&proofState, challenge, &mut oldWalletVec.clone());
 
         //response commitment
         let newWalletVec = newWallet.as_fr_vec();
@@ -192,7 +326,7 @@ impl<E: Engine> NIZKPublicParams<E> {
             x_vec.extend(format!("{}", t).bytes());
         }
 
-        util::hash_to_fr::<E>(x_vec)
+        util::hash_to_challenge::<E>(x_vec)
     }
 }
 
@@ -200,6 +334,9 @@ impl<E: Engine> NIZKPublicParams<E> {
 /// Verify PoK for the opening of a commitment during the establishment protocol
 ///
 pub fn verify_opening<E: Engine>(com_params: &CSMultiParams<E>, com: &E::G1, proof: &CommitmentProof<E>, channelId: &E::Fr, init_cust: i64, init_merch: i64) -> bool {
+    if !util::is_valid_point(com) || proof.validate().is_err() {
+        return false;
+    }
     let xvec: Vec<E::G1> = vec![proof.T.clone(), com.clone()];
     let challenge = util::hash_g1_to_fr::<E>(&xvec);
 
@@ -213,7 +350,8 @@ pub fn verify_opening<E: Engine>(com_params: &CSMultiParams<E>, com: &E::G1, pro
 #[cfg(test)]
 mod tests {
     use super::*;
-    use pairing::bls12_381::{Bls12, Fr};
+    use pairing::bls12_381::{Bls12, Fr, G1};
+    use cl::setup;
     use util::convert_int_to_fr;
     use ff::PrimeField;
 
@@ -247,6 +385,166 @@ mod tests {
         assert_eq!(secParams.verify(proof, fr, &commitment2, wpk), true);
     }
 
+    #[test]
+    fn nizk_migration_proof_works() {
+        let rng = &mut rand::thread_rng();
+        let channelId = Fr::rand(rng);
+        let wpk = Fr::rand(rng);
+        let wpkprime = Fr::rand(rng);
+        let bc = rng.gen_range(100, 1000);
+        let bm = rng.gen_range(100, 1000);
+        let r = Fr::rand(rng);
+        let rprime = Fr::rand(rng);
+
+        let oldParams = NIZKSecretParams::<Bls12>::setup(rng, 4);
+        let newParams = NIZKSecretParams::<Bls12>::setup(rng, 4);
+        let wallet1 = Wallet { channelId: channelId, wpk, bc, bm, close: None };
+        let commitment1 = oldParams.pubParams.comParams.commit(&wallet1.as_fr_vec(), &r);
+        let wallet2 = Wallet { channelId: channelId, wpk: wpkprime, bc, bm, close: None };
+        let commitment2 = newParams.pubParams.comParams.commit(&wallet2.as_fr_vec(), &rprime);
+        let blindPaymentToken = oldParams.keypair.sign_blind(rng, &oldParams.pubParams.mpk, commitment1.clone());
+        let paymentToken = oldParams.keypair.unblind(&r, &blindPaymentToken);
+
+        let proof = newParams.pubParams.prove_migration(rng, &oldParams.pubParams.pk, wallet1.clone(), wallet2.clone(),
+                                                        commitment2.clone(), rprime, &paymentToken);
+        assert_eq!(newParams.verify_migration(&oldParams, proof.clone(), &commitment2, wpk, channelId), true);
+        assert_eq!(newParams.verify_migration(&oldParams, proof.clone(), &commitment2, wpk, Fr::rand(rng)), false);
+        assert_eq!(newParams.verify_migration(&newParams, proof.clone(), &commitment2, wpk, channelId), false);
+        assert_eq!(newParams.verify(proof, Fr::zero(), &commitment2, wpk), false);
+
+        // the balances cannot change during the migration
+        let wallet3 = Wallet { channelId: channelId, wpk: wpkprime, bc: bc + 10, bm, close: None };
+        let commitment3 = newParams.pubParams.comParams.commit(&wallet3.as_fr_vec(), &rprime);
+        let proof = newParams.pubParams.prove_migration(rng, &oldParams.pubParams.pk, wallet1, wallet3,
+                                                        commitment3.clone(), rprime, &paymentToken);
+        assert_eq!(newParams.verify_migration(&oldParams, proof, &commitment3, wpk, channelId), false);
+    }
+
+    #[test]
+    fn verify_public_params_works() {
+        let rng = &mut rand::thread_rng();
+        let secParams = NIZKSecretParams::<Bls12>::setup(rng, 4);
+        assert!(secParams.pubParams.verify_public_params());
+
+        let mut pubParams = secParams.pubParams.clone();
+        pubParams.mpk = setup(rng);
+        assert!(!pubParams.verify_public_params());
+
+        let mut pubParams = secParams.pubParams.clone();
+        pubParams.rpParams.mpk = setup(rng);
+        assert!(!pubParams.verify_public_params());
+    }
+
+    #[test]
+    fn nizk_proof_verifies_after_deserialization() {
+        let rng = &mut rand::thread_rng();
+        let channelId = Fr::rand(rng);
+        let wpk = Fr::rand(rng);
+        let wpkprime = Fr::rand(rng);
+        let bc = rng.gen_range(100, 1000);
+        let bm = rng.gen_range(100, 1000);
+        let epsilon = rng.gen_range(1, 100);
+        let r = Fr::rand(rng);
+        let rprime = Fr::rand(rng);
+
+        let secParams = NIZKSecretParams::<Bls12>::setup(rng, 4);
+        let wallet1 = Wallet { channelId: channelId, wpk, bc, bm, close: None };
+        let commitment1 = secParams.pubParams.comParams.commit(&wallet1.as_fr_vec(), &r);
+        let wallet2 = Wallet { channelId: channelId, wpk: wpkprime, bc: bc - epsilon, bm: bm + epsilon, close: None };
+        let commitment2 = secParams.pubParams.comParams.commit(&wallet2.as_fr_vec(), &rprime);
+        let blindPaymentToken = secParams.keypair.sign_blind(rng, &secParams.pubParams.mpk, commitment1.clone());
+        let paymentToken = secParams.keypair.unblind(&r, &blindPaymentToken);
+
+        let proof = secParams.pubParams.prove(rng, wallet1, wallet2,
+                                              commitment2.clone(), rprime, &paymentToken);
+        let fr = convert_int_to_fr::<Bls12>(epsilon);
+
+        // the prepared keys are not serialized but rebuilt on deserialization
+        let ser = serde_json::to_vec(&secParams).unwrap();
+        let mut desParams: NIZKSecretParams<Bls12> = serde_json::from_slice(&ser).unwrap();
+        assert!(desParams.preparedPk.is_some());
+        assert_eq!(desParams.verify(proof.clone(), fr, &commitment2, wpk), true);
+
+        // without the cache, the keys get recomputed during verification
+        desParams.preparedPk = None;
+        assert_eq!(desParams.verify(proof, fr, &commitment2, wpk), true);
+    }
+
+    #[test]
+    fn nizk_proof_rejects_invalid_points() {
+        let rng = &mut rand::thread_rng();
+        let channelId = Fr::rand(rng);
+        let wpk = Fr::rand(rng);
+        let r = Fr::rand(rng);
+        let rprime = Fr::rand(rng);
+
+        let secParams = NIZKSecretParams::<Bls12>::setup(rng, 4);
+        let wallet1 = Wallet { channelId: channelId, wpk, bc: 100, bm: 100, close: None };
+        let commitment1 = secParams.pubParams.comParams.commit(&wallet1.as_fr_vec(), &r);
+        let wallet2 = Wallet { channelId: channelId, wpk: Fr::rand(rng), bc: 90, bm: 110, close: None };
+        let commitment2 = secParams.pubParams.comParams.commit(&wallet2.as_fr_vec(), &rprime);
+        let blindPaymentToken = secParams.keypair.sign_blind(rng, &secParams.pubParams.mpk, commitment1.clone());
+        let paymentToken = secParams.keypair.unblind(&r, &blindPaymentToken);
+        let proof = secParams.pubParams.prove(rng, wallet1, wallet2, commitment2.clone(), rprime, &paymentToken);
+        let fr = convert_int_to_fr::<Bls12>(10);
+        assert!(proof.validate().is_ok());
+        assert!(secParams.verify(proof.clone(), fr, &commitment2, wpk));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.rpBC.D = G1::zero();
+        assert_eq!(bad_proof.validate().err().unwrap(), "rpBC: D is the identity or not in the prime order subgroup");
+        assert!(!secParams.verify(bad_proof, fr, &commitment2, wpk));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.sig.h = util::non_subgroup_g1();
+        assert_eq!(bad_proof.validate().err().unwrap(), "signature h is the identity or not in the prime order subgroup");
+        assert!(!secParams.verify(bad_proof, fr, &commitment2, wpk));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.comProof.T = G1::zero();
+        assert!(!secParams.verify(bad_proof, fr, &commitment2, wpk));
+
+        let bad_com = Commitment { c: util::non_subgroup_g1() };
+        assert!(!secParams.verify(proof, fr, &bad_com, wpk));
+    }
+
+    #[test]
+    fn nizk_proof_rejects_truncated_responses() {
+        let rng = &mut rand::thread_rng();
+        let channelId = Fr::rand(rng);
+        let wpk = Fr::rand(rng);
+        let r = Fr::rand(rng);
+        let rprime = Fr::rand(rng);
+
+        let secParams = NIZKSecretParams::<Bls12>::setup(rng, 4);
+        let wallet1 = Wallet { channelId: channelId, wpk, bc: 100, bm: 100, close: None };
+        let commitment1 = secParams.pubParams.comParams.commit(&wallet1.as_fr_vec(), &r);
+        let wallet2 = Wallet { channelId: channelId, wpk: Fr::rand(rng), bc: 90, bm: 110, close: None };
+        let commitment2 = secParams.pubParams.comParams.commit(&wallet2.as_fr_vec(), &rprime);
+        let blindPaymentToken = secParams.keypair.sign_blind(rng, &secParams.pubParams.mpk, commitment1.clone());
+        let paymentToken = secParams.keypair.unblind(&r, &blindPaymentToken);
+        let proof = secParams.pubParams.prove(rng, wallet1, wallet2, commitment2.clone(), rprime, &paymentToken);
+        let fr = convert_int_to_fr::<Bls12>(10);
+        assert!(secParams.verify(proof.clone(), fr, &commitment2, wpk));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.sigProof.zsig.truncate(1);
+        assert!(!secParams.verify(bad_proof, fr, &commitment2, wpk));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.comProof.z.truncate(2);
+        assert!(!secParams.verify(bad_proof, fr, &commitment2, wpk));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.sigProof.zsig.clear();
+        bad_proof.comProof.z.clear();
+        assert!(!secParams.verify(bad_proof, fr, &commitment2, wpk));
+
+        let mut bad_proof = proof.clone();
+        bad_proof.comProof.z.push(Fr::rand(rng));
+        assert!(!secParams.verify(bad_proof, fr, &commitment2, wpk));
+    }
+
     #[test]
     fn nizk_proof_negative_value_works() {
         let rng = &mut rand::thread_rng();
@@ -376,6 +674,11 @@ mod tests {
                                                       &com.c, &wallet.as_fr_vec(), &t, &vec![1, 3, 4]);
 
         assert!(verify_opening(&secParams.pubParams.comParams, &com.c, &com_proof, &channelId.clone(), bc, bm));
+        assert!(!verify_opening(&secParams.pubParams.comParams, &G1::zero(), &com_proof, &channelId.clone(), bc, bm));
+
+        let mut bad_proof = com_proof.clone();
+        bad_proof.T = util::non_subgroup_g1();
+        assert!(!verify_opening(&secParams.pubParams.comParams, &com.c, &bad_proof, &channelId.clone(), bc, bm));
     }
 
     #[test]
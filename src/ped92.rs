@@ -2,10 +2,13 @@
 use rand::{Rng, AsByteSliceMut};
 use pairing::{Engine, CurveProjective};
 use ff::{Rand, Field, PrimeField};
-use std::fmt;
+use core::fmt;
 use util::is_vec_g1_equal;
 use serde::{Serialize, Deserialize};
 use util;
+use util::{Validate, validate_point};
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
 
 #[derive(Clone)]
 pub struct CSParams<E: Engine> {
@@ -26,6 +29,12 @@ impl<E: Engine> PartialEq for Commitment<E> {
     }
 }
 
+impl<E: Engine> Validate for Commitment<E> {
+    fn validate(&self) -> Result<(), String> {
+        validate_point(&self.c, "commitment c")
+    }
+}
+
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(bound(serialize = "<E as pairing::Engine>::G1: serde::Serialize"))]
@@ -139,6 +148,31 @@ impl<E: Engine> CSMultiParams<E> {
         CSMultiParams { pub_bases: p }
     }
 
+    /*
+    Derives the bases for a vector of messages of length len from a public seed, by hashing
+    seed || I2OSP(i, 4) to G1 for the i-th base (see util::hash_to_g1), so that nobody knows the
+    discrete logs between them. The bases of the wallet commitments in the protocol are not
+    seeded: they are [g1, Y1_1, ..., Y1_n] of the merchant's blind signing key (see
+    BlindKeyPair::generate_cs_multi_params), as the blind signature requires. There g1 is
+    seeded (see cl::setup_from_seed) and the customer checks the Y1_i against the key with
+    BlindPublicKey::is_well_formed, while the merchant knows their discrete logs, which only
+    lets the merchant open its own commitments in more than one way.
+    */
+    pub fn setup_from_seed(seed: &[u8], len: usize) -> Self {
+        let mut msg = seed.to_vec();
+        msg.extend_from_slice(&[0u8; 4]);
+        let p = (0..len + 1).map(|i| {
+            msg[seed.len()..].copy_from_slice(&(i as u32).to_be_bytes());
+            util::hash_to_g1::<E>(&msg, util::DST_NUMS_COM_PARAMS)
+        }).collect();
+        CSMultiParams { pub_bases: p }
+    }
+
+    /// verify_seed - recomputes the bases from the public seed and checks that they match
+    pub fn verify_seed(&self, seed: &[u8]) -> bool {
+        !self.pub_bases.is_empty() && *self == CSMultiParams::setup_from_seed(seed, self.pub_bases.len() - 1)
+    }
+
     pub fn commit(&self, x: &Vec<E::Fr>, r: &E::Fr) -> Commitment<E> {
         // c = g1^m1 * ... * gn^mn * h^r
         let mut c = self.pub_bases[0].clone();
@@ -206,6 +240,12 @@ pub struct CommitmentProof<E: Engine> {
     pub z: Vec<E::Fr>,
 }
 
+impl<E: Engine> Validate for CommitmentProof<E> {
+    fn validate(&self) -> Result<(), String> {
+        validate_point(&self.T, "commitment proof T")
+    }
+}
+
 impl<E: Engine> CommitmentProof<E> {
     pub fn new<R: Rng>(csprng: &mut R, com_params: &CSMultiParams<E>, com: &E::G1, wallet: &Vec<E::Fr>, r: &E::Fr, reveal_index: &Vec<usize>) -> Self {
         let mut rt = Vec::new();
@@ -328,6 +368,40 @@ mod tests {
         assert_eq!(csp.decommit(&c, &m, &r1), false);
     }
 
+    #[test]
+    fn commitment_validate_works() {
+        let rng = &mut thread_rng();
+        let csp = CSMultiParams::<Bls12>::setup_gen_params(rng, 2);
+        let c = csp.commit(&vec![Fr::rand(rng), Fr::rand(rng)], &Fr::rand(rng));
+        assert!(c.validate().is_ok());
+        assert!(Commitment::<Bls12> { c: G1::zero() }.validate().is_err());
+        assert_eq!(Commitment::<Bls12> { c: util::non_subgroup_g1() }.validate().err().unwrap(),
+                   "commitment c is the identity or not in the prime order subgroup");
+    }
+
+    #[test]
+    fn setup_from_seed_works() {
+        let csp = CSMultiParams::<Bls12>::setup_from_seed(b"seed", 3);
+        assert_eq!(csp.pub_bases.len(), 4);
+        assert!(csp == CSMultiParams::<Bls12>::setup_from_seed(b"seed", 3));
+        assert!(csp.verify_seed(b"seed"));
+        assert!(!csp.verify_seed(b"other seed"));
+        assert!(!CSMultiParams::<Bls12>::setup_gen_params(&mut thread_rng(), 3).verify_seed(b"seed"));
+
+        // the bases for shorter vectors are a prefix of the longer ones
+        let csp2 = CSMultiParams::<Bls12>::setup_from_seed(b"seed", 1);
+        assert!(csp2.pub_bases[..] == csp.pub_bases[..2]);
+        assert_eq!(format!("{}", csp2.pub_bases[0]), "G1(x=Fq(0x146337e773285d44541de2461d919eee31e1b83ddd4bc3886c5acfda52dbb3719a00402d34fb578b1fcc8859eeef5805), y=Fq(0x090faf98ebc4e64bbc84f3f856421b7397cd10571413ed9328545626751cba514d63eab84cebb7ae7314dad55e487abf))");
+        assert_eq!(format!("{}", csp2.pub_bases[1]), "G1(x=Fq(0x0716af313faa0e76a7190350b36bcc19a253500fc29801d8c3acd1846da497e7a3e16b5e937a5127e25265b8214e3c6c), y=Fq(0x1855ebf5d782ccabf25668c4f6227726a723c65e5f091ba4270e34c19bd7bc7d77b86862f0d01188b7cefe1bd612a24c))");
+
+        let mut r = Fr::rand(&mut thread_rng());
+        let m = vec![Fr::one(), Fr::zero(), Fr::one()];
+        let c = csp.commit(&m, &r);
+        assert_eq!(csp.decommit(&c, &m, &r), true);
+        r.add_assign(&Fr::one());
+        assert_eq!(csp.decommit(&c, &m, &r), false);
+    }
+
     #[test]
     fn commit_variable_messages_works() {
         let rng = &mut thread_rng();
@@ -0,0 +1,393 @@
+//! Native Python bindings for the bidirectional payment channel API on BLS12-381
+//! (enabled with the `python` feature).
+//!
+//! The channel, channel token, customer and merchant states are exposed as Python classes
+//! that stay in Rust between calls, so only the protocol messages exchanged between the
+//! parties (commitments, proofs and tokens) are passed around as JSON strings. Protocol
+//! failures are raised as `bolt.BoltError`, malformed JSON as `ValueError`.
+
+use super::*;
+use bidirectional;
+use pairing::bls12_381::Bls12;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use pyo3::prelude::*;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+
+type CURVE = Bls12;
+
+create_exception!(bolt, BoltError, PyException);
+
+impl From<channels::BoltError> for PyErr {
+    fn from(err: channels::BoltError) -> PyErr {
+        BoltError::new_err(err.to_string())
+    }
+}
+
+fn to_json<T: Serialize>(object: &T) -> PyResult<String> {
+    serde_json::to_string(object).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+fn from_json<T: DeserializeOwned>(ser: &str) -> PyResult<T> {
+    serde_json::from_str(ser).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+fn bolt_result<T>(result: BoltResult<T>) -> PyResult<T> {
+    match result {
+        Ok(Some(n)) => Ok(n),
+        Ok(None) => Err(BoltError::new_err("no result")),
+        Err(err) => Err(BoltError::new_err(err)),
+    }
+}
+
+#[pyclass(name = "ChannelState")]
+#[derive(Clone)]
+pub struct PyChannelState {
+    state: bidirectional::ChannelState<CURVE>,
+}
+
+#[pymethods]
+impl PyChannelState {
+    #[new]
+    #[pyo3(signature = (name, third_party = false))]
+    fn new(name: String, third_party: bool) -> Self {
+        PyChannelState { state: bidirectional::ChannelState::<CURVE>::new(name, third_party) }
+    }
+
+    #[getter]
+    fn channel_established(&self) -> bool {
+        self.state.channel_established
+    }
+
+    fn set_channel_fee(&mut self, fee: i64) {
+        self.state.set_channel_fee(fee);
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.state)
+    }
+
+    #[staticmethod]
+    fn from_json(ser: &str) -> PyResult<Self> {
+        Ok(PyChannelState { state: from_json(ser)? })
+    }
+}
+
+#[pyclass(name = "ChannelToken")]
+#[derive(Clone)]
+pub struct PyChannelToken {
+    token: bidirectional::ChannelToken<CURVE>,
+}
+
+#[pymethods]
+impl PyChannelToken {
+    /// the channel id (JSON encoded), available once the customer public key is set
+    fn channel_id(&self) -> PyResult<String> {
+        if self.token.pk_c.is_none() {
+            return Err(BoltError::new_err("channel token has no customer public key"));
+        }
+        to_json(&self.token.compute_channel_id())
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.token)
+    }
+
+    #[staticmethod]
+    fn from_json(ser: &str) -> PyResult<Self> {
+        Ok(PyChannelToken { token: from_json(ser)? })
+    }
+}
+
+#[pyclass(name = "MerchantState")]
+#[derive(Clone)]
+pub struct PyMerchantState {
+    state: bidirectional::MerchantState<CURVE>,
+}
+
+#[pymethods]
+impl PyMerchantState {
+    /// Serializes the merchant state without the signing keys
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.state)
+    }
+
+    /// Serializes the signing keys of the merchant, to persist them
+    fn export_secrets_json(&self) -> PyResult<String> {
+        to_json(&self.state.export_secrets())
+    }
+
+    /// Restores the merchant state from its signing keys and the channel state.
+    /// Raises `BoltError` if the keys do not match the public params of the channel.
+    #[staticmethod]
+    fn from_secrets_json(ser: &str, channel_state: &PyChannelState) -> PyResult<Self> {
+        Ok(PyMerchantState { state: bidirectional::MerchantState::from_secrets(from_json(ser)?, &channel_state.state)? })
+    }
+}
+
+#[pyclass(name = "MerchantLedger")]
+#[derive(Clone)]
+pub struct PyMerchantLedger {
+    ledger: bidirectional::MerchantLedger<CURVE>,
+}
+
+#[pymethods]
+impl PyMerchantLedger {
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.ledger)
+    }
+
+    #[staticmethod]
+    fn from_json(ser: &str) -> PyResult<Self> {
+        Ok(PyMerchantLedger { ledger: from_json(ser)? })
+    }
+}
+
+#[pyclass(name = "CustomerState")]
+#[derive(Clone)]
+pub struct PyCustomerState {
+    state: bidirectional::CustomerState<CURVE>,
+}
+
+#[pymethods]
+impl PyCustomerState {
+    #[getter]
+    fn cust_balance(&self) -> i64 {
+        self.state.cust_balance
+    }
+
+    #[getter]
+    fn merch_balance(&self) -> i64 {
+        self.state.merch_balance
+    }
+
+    /// Serializes the customer state without the secret keys
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.state)
+    }
+
+    /// Serializes the customer state with the secret keys, to persist it
+    fn export_json(&self) -> PyResult<String> {
+        to_json(&self.state.export())
+    }
+
+    #[staticmethod]
+    fn import_json(ser: &str) -> PyResult<Self> {
+        Ok(PyCustomerState { state: bidirectional::CustomerState::import(from_json(ser)?) })
+    }
+}
+
+// INIT
+
+/// Returns the channel token, merchant state and merchant ledger, and updates the channel state in place.
+#[pyfunction]
+fn init_merchant(channel_state: &mut PyChannelState, name: &str) -> (PyChannelToken, PyMerchantState, PyMerchantLedger) {
+    let rng = &mut rand::thread_rng();
+    let (channel_token, merch_state, merch_ledger, new_channel_state) = bidirectional::init_merchant(rng, &mut channel_state.state, name);
+    channel_state.state = new_channel_state;
+    (PyChannelToken { token: channel_token }, PyMerchantState { state: merch_state }, PyMerchantLedger { ledger: merch_ledger })
+}
+
+/// Sets the customer public key on the channel token in place and returns the customer state.
+/// Raises `BoltError` if the range proof parameters in the channel state do not verify.
+#[pyfunction]
+fn init_customer(channel_state: &PyChannelState, channel_token: &mut PyChannelToken, b0_cust: i64, b0_merch: i64, name: &str) -> PyResult<PyCustomerState> {
+    let rng = &mut rand::thread_rng();
+    let cust_state = bolt_result(bidirectional::init_customer(rng, &channel_state.state, &mut channel_token.token, b0_cust, b0_merch, name))?;
+    Ok(PyCustomerState { state: cust_state })
+}
+
+// ESTABLISH
+
+/// Returns the commitment and commitment proof (JSON encoded).
+#[pyfunction]
+fn establish_customer_generate_proof(channel_token: &PyChannelToken, cust_state: &PyCustomerState) -> PyResult<(String, String)> {
+    let rng = &mut rand::thread_rng();
+    let (com, com_proof) = bidirectional::establish_customer_generate_proof(rng, &channel_token.token, &cust_state.state);
+    Ok((to_json(&com)?, to_json(&com_proof)?))
+}
+
+#[pyfunction]
+fn establish_merchant_issue_close_token(channel_state: &PyChannelState, com: &str, com_proof: &str, channel_id: &str,
+                                        init_cust_bal: i64, init_merch_bal: i64, merch_state: &PyMerchantState,
+                                        merch_ledger: &mut PyMerchantLedger) -> PyResult<String> {
+    let rng = &mut rand::thread_rng();
+    let com: bidirectional::Commitment<CURVE> = from_json(com)?;
+    let com_proof: bidirectional::CommitmentProof<CURVE> = from_json(com_proof)?;
+    let channel_id: <CURVE as ff::ScalarEngine>::Fr = from_json(channel_id)?;
+    let close_token = bolt_result(bidirectional::establish_merchant_issue_close_token(rng, &channel_state.state, &com, &com_proof,
+                                                                                      &channel_id, init_cust_bal, init_merch_bal, &merch_state.state,
+                                                                                      &mut merch_ledger.ledger))?;
+    to_json(&close_token)
+}
+
+#[pyfunction]
+fn establish_merchant_issue_pay_token(channel_state: &PyChannelState, com: &str, merch_state: &PyMerchantState) -> PyResult<String> {
+    let rng = &mut rand::thread_rng();
+    let com: bidirectional::Commitment<CURVE> = from_json(com)?;
+    let pay_token = bidirectional::establish_merchant_issue_pay_token(rng, &channel_state.state, &com, &merch_state.state);
+    to_json(&pay_token)
+}
+
+#[pyfunction]
+fn establish_customer_verify_close_token(channel_state: &PyChannelState, cust_state: &mut PyCustomerState, close_token: &str) -> PyResult<bool> {
+    let close_token: bidirectional::Signature<CURVE> = from_json(close_token)?;
+    Ok(cust_state.state.verify_close_token(&channel_state.state, &close_token))
+}
+
+#[pyfunction]
+fn establish_customer_final(channel_state: &mut PyChannelState, cust_state: &mut PyCustomerState, pay_token: &str) -> PyResult<bool> {
+    let pay_token: bidirectional::Signature<CURVE> = from_json(pay_token)?;
+    Ok(bidirectional::establish_customer_final(&mut channel_state.state, &mut cust_state.state, &pay_token))
+}
+
+// PAY
+
+/// Returns the payment (JSON encoded) and the new customer state.
+#[pyfunction]
+fn pay_generate_payment_proof(channel_state: &PyChannelState, cust_state: &PyCustomerState, amount: i64) -> PyResult<(String, PyCustomerState)> {
+    let rng = &mut rand::thread_rng();
+    let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &channel_state.state, &cust_state.state, amount);
+    Ok((to_json(&payment)?, PyCustomerState { state: new_cust_state }))
+}
+
+#[pyfunction]
+fn pay_verify_payment_proof(channel_state: &PyChannelState, payment: &str, merch_state: &PyMerchantState,
+                            merch_ledger: &mut PyMerchantLedger) -> PyResult<String> {
+    let rng = &mut rand::thread_rng();
+    let payment: bidirectional::Payment<CURVE> = from_json(payment)?;
+    // unlike verify_payment_proof, reports an invalid proof as an error rather than a panic
+    let mut results = bidirectional::verify_payment_proofs(rng, &channel_state.state, &vec![payment], &merch_state.state,
+                                                           &mut merch_ledger.ledger);
+    let close_token = results.remove(0).map_err(|err| BoltError::new_err(err))?;
+    to_json(&close_token)
+}
+
+/// Returns the close token for the sender and the conditional close token for the receiver.
+/// Raises BoltError, and leaves the ledger unchanged, if either payment proof does not verify.
+#[pyfunction]
+fn pay_verify_multiple_payment_proofs(channel_state: &PyChannelState, sender_payment: &str, receiver_payment: &str,
+                                      merch_state: &PyMerchantState, merch_ledger: &mut PyMerchantLedger) -> PyResult<(String, String)> {
+    let rng = &mut rand::thread_rng();
+    let sender_payment: bidirectional::Payment<CURVE> = from_json(sender_payment)?;
+    let receiver_payment: bidirectional::Payment<CURVE> = from_json(receiver_payment)?;
+    let (close_token, cond_close_token) = bolt_result(bidirectional::verify_multiple_payment_proofs(rng, &channel_state.state, &sender_payment,
+                                                                                                    &receiver_payment, &merch_state.state,
+                                                                                                    &mut merch_ledger.ledger))?;
+    Ok((to_json(&close_token)?, to_json(&cond_close_token)?))
+}
+
+/// Moves the customer state to `new_cust_state` and returns the revoke token for the old one.
+/// The customer state is left unchanged if the close token does not verify.
+#[pyfunction]
+fn pay_generate_revoke_token(channel_state: &PyChannelState, cust_state: &mut PyCustomerState, new_cust_state: &PyCustomerState,
+                             close_token: &str) -> PyResult<String> {
+    let close_token: bidirectional::Signature<CURVE> = from_json(close_token)?;
+    let mut updated_state = cust_state.state.clone();
+    if !updated_state.update(new_cust_state.state.clone()) {
+        return Err(BoltError::new_err("pay_generate_revoke_token - could not update the customer state"));
+    }
+    if !updated_state.verify_close_token(&channel_state.state, &close_token) {
+        return Err(BoltError::new_err("pay_generate_revoke_token - could not verify the close token"));
+    }
+
+    let revoke_token = bidirectional::generate_revoke_token(&channel_state.state, &mut cust_state.state,
+                                                            new_cust_state.state.clone(), &close_token);
+    to_json(&revoke_token)
+}
+
+#[pyfunction]
+fn pay_verify_revoke_token(revoke_token: &str, merch_ledger: &mut PyMerchantLedger) -> PyResult<String> {
+    let revoke_token: bidirectional::RevokeToken = from_json(revoke_token)?;
+    let pay_token = bolt_result(bidirectional::verify_revoke_token(&revoke_token, &mut merch_ledger.ledger))?;
+    to_json(&pay_token)
+}
+
+/// Returns the pay tokens for the sender and the receiver.
+#[pyfunction]
+fn pay_verify_multiple_revoke_tokens(sender_revoke_token: &str, receiver_revoke_token: &str, merch_ledger: &mut PyMerchantLedger) -> PyResult<(String, String)> {
+    let sender_revoke_token: bidirectional::RevokeToken = from_json(sender_revoke_token)?;
+    let receiver_revoke_token: bidirectional::RevokeToken = from_json(receiver_revoke_token)?;
+    let (sender_pay_token, receiver_pay_token) = bolt_result(bidirectional::verify_multiple_revoke_tokens(&sender_revoke_token, &receiver_revoke_token,
+                                                                                                          &mut merch_ledger.ledger))?;
+    Ok((to_json(&sender_pay_token)?, to_json(&receiver_pay_token)?))
+}
+
+#[pyfunction]
+fn pay_verify_payment_token(channel_state: &PyChannelState, cust_state: &mut PyCustomerState, pay_token: &str) -> PyResult<bool> {
+    let pay_token: bidirectional::Signature<CURVE> = from_json(pay_token)?;
+    Ok(cust_state.state.verify_pay_token(&channel_state.state, &pay_token))
+}
+
+// CLOSE
+
+#[pyfunction]
+fn customer_close(channel_state: &PyChannelState, cust_state: &PyCustomerState) -> PyResult<String> {
+    if !channel_state.state.channel_established {
+        return Err(BoltError::new_err("Cannot close a channel that has not been established!"));
+    }
+    let cust_close = bidirectional::customer_close(&channel_state.state, &cust_state.state);
+    to_json(&cust_close)
+}
+
+/// Returns the revoked wallet public key and the merchant close message (JSON encoded). A valid close
+/// marks the channel as closed in the merchant ledger, also when it is not a dispute.
+#[pyfunction]
+fn merchant_close(channel_state: &PyChannelState, channel_token: &PyChannelToken, address: &str, cust_close: &str,
+                  merch_state: &PyMerchantState, merch_ledger: &mut PyMerchantLedger) -> PyResult<(String, String)> {
+    let cust_close: bidirectional::ChannelcloseC<CURVE> = from_json(cust_close)?;
+    let keys = bolt_result(bidirectional::merchant_close(&channel_state.state, &channel_token.token, &cust_close, &mut merch_ledger.ledger))?;
+    let merch_close = merch_state.state.sign_revoke_message(address.to_string(), &keys.revoke_token);
+    Ok((to_json(&keys.wpk)?, to_json(&merch_close)?))
+}
+
+#[pyfunction]
+fn wtp_verify_cust_close_message(channel_token: &PyChannelToken, wpk: &str, close_msg: &str, close_token: &str) -> PyResult<bool> {
+    let wpk: secp256k1::PublicKey = from_json(wpk)?;
+    let close_msg: bidirectional::Wallet<CURVE> = from_json(close_msg)?;
+    let close_token: bidirectional::Signature<CURVE> = from_json(close_token)?;
+    Ok(bidirectional::wtp_verify_cust_close_message(&channel_token.token, &wpk, &close_msg, &close_token))
+}
+
+#[pyfunction]
+fn wtp_verify_merch_close_message(channel_token: &PyChannelToken, wpk: &str, merch_close: &str) -> PyResult<bool> {
+    let wpk: secp256k1::PublicKey = from_json(wpk)?;
+    let merch_close: bidirectional::ChannelcloseM = from_json(merch_close)?;
+    let revoke_token_valid = match merch_close.revoke {
+        Some(revoke_token) => bidirectional::wtp_verify_revoke_message(&wpk, &revoke_token),
+        None => false
+    };
+    Ok(revoke_token_valid && bidirectional::wtp_verify_merch_close_message(&channel_token.token, &merch_close))
+}
+
+#[pymodule]
+fn bolt(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("BoltError", py.get_type::<BoltError>())?;
+    m.add_class::<PyChannelState>()?;
+    m.add_class::<PyChannelToken>()?;
+    m.add_class::<PyMerchantState>()?;
+    m.add_class::<PyMerchantLedger>()?;
+    m.add_class::<PyCustomerState>()?;
+
+    m.add_function(wrap_pyfunction!(self::init_merchant, m)?)?;
+    m.add_function(wrap_pyfunction!(self::init_customer, m)?)?;
+    m.add_function(wrap_pyfunction!(self::establish_customer_generate_proof, m)?)?;
+    m.add_function(wrap_pyfunction!(self::establish_merchant_issue_close_token, m)?)?;
+    m.add_function(wrap_pyfunction!(self::establish_merchant_issue_pay_token, m)?)?;
+    m.add_function(wrap_pyfunction!(self::establish_customer_verify_close_token, m)?)?;
+    m.add_function(wrap_pyfunction!(self::establish_customer_final, m)?)?;
+    m.add_function(wrap_pyfunction!(self::pay_generate_payment_proof, m)?)?;
+    m.add_function(wrap_pyfunction!(self::pay_verify_payment_proof, m)?)?;
+    m.add_function(wrap_pyfunction!(self::pay_verify_multiple_payment_proofs, m)?)?;
+    m.add_function(wrap_pyfunction!(self::pay_generate_revoke_token, m)?)?;
+    m.add_function(wrap_pyfunction!(self::pay_verify_revoke_token, m)?)?;
+    m.add_function(wrap_pyfunction!(self::pay_verify_multiple_revoke_tokens, m)?)?;
+    m.add_function(wrap_pyfunction!(self::pay_verify_payment_token, m)?)?;
+    m.add_function(wrap_pyfunction!(self::customer_close, m)?)?;
+    m.add_function(wrap_pyfunction!(self::merchant_close, m)?)?;
+    m.add_function(wrap_pyfunction!(self::wtp_verify_cust_close_message, m)?)?;
+    m.add_function(wrap_pyfunction!(self::wtp_verify_merch_close_message, m)?)?;
+    Ok(())
+}
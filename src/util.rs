@@ -1,7 +1,43 @@
 use super::*;
-use pairing::Engine;
-use ff::{PrimeField};
+use pairing::{Engine, CurveProjective, CurveAffine};
+use ff::{Field, PrimeField};
 use sha2::Digest;
+use zeroize::Zeroize;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+/// Domain separation tags for hashing into Fr, one for each use of the hash
+pub const DST_HASH: &'static [u8] = b"LIBBOLT-V01-XMD:SHA-256_HASH";
+pub const DST_PUBKEY: &'static [u8] = b"LIBBOLT-V01-XMD:SHA-256_PUBKEY";
+pub const DST_BUFFER: &'static [u8] = b"LIBBOLT-V01-XMD:SHA-256_BUFFER";
+pub const DST_CHALLENGE: &'static [u8] = b"LIBBOLT-V01-XMD:SHA-256_CHALLENGE";
+pub const DST_CHANNEL_ID: &'static [u8] = b"LIBBOLT-V01-XMD:SHA-256_CHANNEL-ID";
+
+/// Domain separation tags for hashing public seeds to group elements (see hash_to_g1)
+pub const DST_NUMS_PUBLIC_PARAMS_G1: &'static [u8] = b"LIBBOLT-V01-NUMS:XMD:SHA-256_TAI_PUBLIC-PARAMS-G1";
+pub const DST_NUMS_PUBLIC_PARAMS_G2: &'static [u8] = b"LIBBOLT-V01-NUMS:XMD:SHA-256_TAI_PUBLIC-PARAMS-G2";
+pub const DST_NUMS_COM_PARAMS: &'static [u8] = b"LIBBOLT-V01-NUMS:XMD:SHA-256_TAI_COM-PARAMS";
+
+/// How bytes are hashed into Fr. Both parties of a channel must use the same version.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HashToFieldVersion {
+    /// SHA-256 digest printed as concatenated decimal bytes and parsed with Fr::from_str
+    /// (see compute_the_hash). Ignores the domain separation tag.
+    Legacy,
+    /// hash_to_field from RFC 9380 with expand_message_xmd over SHA-256
+    XmdSha256,
+}
+
+/// The version used by hash_to_fr and the other hash functions below. The `legacy-hash`
+/// feature keeps the old mapping for compatibility with existing channels.
+#[cfg(not(feature = "legacy-hash"))]
+pub const HASH_TO_FIELD_VERSION: HashToFieldVersion = HashToFieldVersion::XmdSha256;
+#[cfg(feature = "legacy-hash")]
+pub const HASH_TO_FIELD_VERSION: HashToFieldVersion = HashToFieldVersion::Legacy;
+
+// L = ceil((ceil(log2(r)) + k) / 8) for k = 128 bits of security, which is 48 for the
+// scalar fields of both BLS12-381 and BN256, so the bias of the reduction mod r is negligible
+const HASH_TO_FIELD_LEN: usize = 48;
 
 pub fn is_vec_fr_equal<E: Engine>(a: &Vec<E::Fr>, b: &Vec<E::Fr>) -> bool {
     (a.len() == b.len()) &&
@@ -24,6 +60,39 @@ pub fn is_vec_g2_equal<E: Engine>(a: &Vec<E::G2>, b: &Vec<E::G2>) -> bool {
          .all(|(a, b)| a == b)
 }
 
+/// runs both closures and returns their results, on the rayon thread pool
+/// if the `parallel` feature is enabled and sequentially otherwise
+#[cfg(feature = "parallel")]
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+    where A: FnOnce() -> RA + Send, B: FnOnce() -> RB + Send, RA: Send, RB: Send
+{
+    rayon::join(a, b)
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+    where A: FnOnce() -> RA + Send, B: FnOnce() -> RB + Send, RA: Send, RB: Send
+{
+    (a(), b())
+}
+
+/// maps f over the items (in parallel if the `parallel` feature is enabled),
+/// preserving their order
+#[cfg(feature = "parallel")]
+pub fn par_map<T, U, F>(items: &[T], f: F) -> Vec<U>
+    where T: Sync, U: Send, F: Fn(&T) -> U + Sync + Send
+{
+    use rayon::prelude::*;
+    items.par_iter().map(f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn par_map<T, U, F>(items: &[T], f: F) -> Vec<U>
+    where T: Sync, U: Send, F: Fn(&T) -> U + Sync + Send
+{
+    items.iter().map(f).collect()
+}
+
 pub fn encode_as_hexstring(bytes: &Vec<u8>) -> String {
     let mut ser_hex = hex::encode(bytes);
     ser_hex.insert(0, '"');
@@ -36,13 +105,82 @@ pub fn hash_g1_to_fr<E: Engine>(x: &Vec<E::G1>) -> E::Fr {
     for i in x.iter() {
         x_vec.extend(format!("{}", i).bytes());
     }
-    hash_to_fr::<E>(x_vec)
+    hash_to_challenge::<E>(x_vec)
 }
 
 pub fn hash_g2_to_fr<E: Engine>(x: &E::G2) -> E::Fr {
     let mut x_vec: Vec<u8> = Vec::new();
     x_vec.extend(format!("{}", x).bytes());
-    hash_to_fr::<E>(x_vec)
+    hash_to_challenge::<E>(x_vec)
+}
+
+/// checks that a point is not the identity and is in the prime order subgroup (r * p = 0)
+pub fn is_valid_point<G: CurveProjective>(p: &G) -> bool {
+    if p.is_zero() {
+        return false;
+    }
+    let mut q = p.clone();
+    q.mul_assign(G::Scalar::char());
+    q.is_zero()
+}
+
+/// a point on the BLS12-381 G1 curve (x = 4) that is not in the prime order subgroup
+#[cfg(test)]
+pub fn non_subgroup_g1() -> pairing::bls12_381::G1 {
+    use pairing::EncodedPoint;
+    let mut encoded = pairing::bls12_381::G1Uncompressed::empty();
+    encoded.as_mut().copy_from_slice(&hex::decode("000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000004\
+        0a989badd40d6212b33cffc3f3763e9bc760f988c9926b26da9dd85e928483446346b8ed00e1de5d5ea93e354abe706c").unwrap());
+    assert!(encoded.into_affine().is_err());
+    encoded.into_affine_unchecked().unwrap().into_projective()
+}
+
+/// checks that an element of the target group is not zero and is in the subgroup of order r
+pub fn is_valid_gt<E: Engine>(a: &E::Fqk) -> bool {
+    !a.is_zero() && a.pow(E::Fr::char()) == E::Fqk::one()
+}
+
+/// Validate checks the group elements of a message received from the other party: points at
+/// infinity and points outside the prime order subgroup are rejected (see is_valid_point).
+/// Deserializing a message does not do these checks, so call validate before using it.
+pub trait Validate {
+    fn validate(&self) -> Result<(), String>;
+}
+
+/// returns an error naming the point if it is not valid (see is_valid_point)
+pub fn validate_point<G: CurveProjective>(p: &G, name: &str) -> Result<(), String> {
+    match is_valid_point(p) {
+        true => Ok(()),
+        false => Err(format!("{} is the identity or not in the prime order subgroup", name))
+    }
+}
+
+/// validates each element of a vector, naming the failing one by its index
+pub fn validate_vec<T: Validate>(v: &Vec<T>, name: &str) -> Result<(), String> {
+    for (i, x) in v.iter().enumerate() {
+        if let Err(err) = x.validate() {
+            return Err(format!("{}[{}]: {}", name, i, err));
+        }
+    }
+    Ok(())
+}
+
+pub fn compress_point<G: CurveProjective>(x: &G) -> Vec<u8> {
+    x.into_affine().into_compressed().as_ref().to_vec()
+}
+
+/// inverse of compress_point, which checks that the point is on the curve and in the prime order subgroup
+pub fn decompress_point<G: CurveProjective>(bytes: &[u8]) -> Result<G, String> {
+    use pairing::EncodedPoint;
+    let mut encoded = <G::Affine as CurveAffine>::Compressed::empty();
+    if bytes.len() != encoded.as_ref().len() {
+        return Err(format!("compressed point has {} bytes, expected {}", bytes.len(), encoded.as_ref().len()));
+    }
+    encoded.as_mut().copy_from_slice(bytes);
+    match encoded.into_affine() {
+        Ok(p) => Ok(p.into_projective()),
+        Err(err) => Err(format!("invalid compressed point: {}", err))
+    }
 }
 
 pub fn fmt_bytes_to_int(bytearray: [u8; 32]) -> String {
@@ -72,13 +210,75 @@ pub fn compute_the_hash<E: Engine>(bytes: &Vec<u8>) -> E::Fr {
     return E::Fr::from_str(&hexresult).unwrap();
 }
 
+/// expand_message_xmd from RFC 9380 (section 5.3.1) with SHA-256
+pub fn expand_message_xmd(msg: &[u8], dst: &[u8], len_in_bytes: usize) -> Vec<u8> {
+    let ell = (len_in_bytes + 31) / 32;
+    assert!(ell <= 255 && len_in_bytes <= 65535 && dst.len() <= 255);
+    let mut dst_prime = dst.to_vec();
+    dst_prime.push(dst.len() as u8);
+
+    // b_0 = H(Z_pad || msg || I2OSP(len_in_bytes, 2) || I2OSP(0, 1) || DST_prime)
+    let mut hasher = sha2::Sha256::new();
+    hasher.input(&[0u8; 64][..]);
+    hasher.input(msg);
+    hasher.input(&[(len_in_bytes >> 8) as u8, len_in_bytes as u8, 0u8][..]);
+    hasher.input(&dst_prime);
+    let b_0 = hasher.result();
+
+    // b_1 = H(b_0 || I2OSP(1, 1) || DST_prime)
+    let mut hasher = sha2::Sha256::new();
+    hasher.input(&b_0);
+    hasher.input(&[1u8][..]);
+    hasher.input(&dst_prime);
+    let mut b_i = hasher.result();
+    let mut uniform_bytes = b_i.to_vec();
+
+    // b_i = H(strxor(b_0, b_(i-1)) || I2OSP(i, 1) || DST_prime)
+    for i in 2..(ell + 1) {
+        let xored: Vec<u8> = b_0.iter().zip(b_i.iter()).map(|(a, b)| a ^ b).collect();
+        let mut hasher = sha2::Sha256::new();
+        hasher.input(&xored);
+        hasher.input(&[i as u8][..]);
+        hasher.input(&dst_prime);
+        b_i = hasher.result();
+        uniform_bytes.extend_from_slice(&b_i);
+    }
+    uniform_bytes.truncate(len_in_bytes);
+    uniform_bytes
+}
+
+/// hash_to_field from RFC 9380 (section 5.2) for a single element: the output of
+/// expand_message_xmd is read as a big-endian integer and reduced mod r
+pub fn hash_to_field_xmd<E: Engine>(msg: &[u8], dst: &[u8]) -> E::Fr {
+    let uniform_bytes = expand_message_xmd(msg, dst, HASH_TO_FIELD_LEN);
+    let base = E::Fr::from_repr(<E::Fr as PrimeField>::Repr::from(256)).unwrap();
+    let mut result = E::Fr::zero();
+    for byte in uniform_bytes.iter() {
+        result.mul_assign(&base);
+        result.add_assign(&E::Fr::from_repr(<E::Fr as PrimeField>::Repr::from(*byte as u64)).unwrap());
+    }
+    result
+}
+
+pub fn hash_to_field<E: Engine>(version: HashToFieldVersion, dst: &[u8], msg: &[u8]) -> E::Fr {
+    match version {
+        HashToFieldVersion::Legacy => compute_the_hash::<E>(&msg.to_vec()),
+        HashToFieldVersion::XmdSha256 => hash_to_field_xmd::<E>(msg, dst),
+    }
+}
+
 pub fn hash_to_fr<E: Engine>(byteVec: Vec<u8>) -> E::Fr {
-    return compute_the_hash::<E>(&byteVec);
+    return hash_to_field::<E>(HASH_TO_FIELD_VERSION, DST_HASH, &byteVec);
+}
+
+/// hash for deriving the challenge of a proof of knowledge
+pub fn hash_to_challenge<E: Engine>(byteVec: Vec<u8>) -> E::Fr {
+    return hash_to_field::<E>(HASH_TO_FIELD_VERSION, DST_CHALLENGE, &byteVec);
 }
 
 pub fn hash_pubkey_to_fr<E: Engine>(wpk: &secp256k1::PublicKey) -> E::Fr {
     let x_slice = wpk.serialize_uncompressed();
-    return compute_the_hash::<E>(&x_slice.to_vec());
+    return hash_to_field::<E>(HASH_TO_FIELD_VERSION, DST_PUBKEY, &x_slice);
 }
 
 pub fn convert_int_to_fr<E: Engine>(value: i64) -> E::Fr {
@@ -107,7 +307,7 @@ pub fn hash_buffer_to_fr<'a, E: Engine>(prefix: &'a str, buf: &[u8; 64]) -> E::F
     let mut input_buf = Vec::new();
     input_buf.extend_from_slice(prefix.as_bytes());
     input_buf.extend_from_slice(buf);
-    return compute_the_hash::<E>(&input_buf);
+    return hash_to_field::<E>(HASH_TO_FIELD_VERSION, DST_BUFFER, &input_buf);
 }
 
 pub fn hash_to_slice(input_buf: &Vec<u8>) -> [u8; 32] {
@@ -120,6 +320,266 @@ pub fn hash_to_slice(input_buf: &Vec<u8>) -> [u8; 32] {
     return hash_buf;
 }
 
+// HASH160 as used in Bitcoin/Zcash scripts: RIPEMD160(SHA256(input))
+pub fn hash160(input_buf: &Vec<u8>) -> [u8; 20] {
+    let sha2_digest = hash_to_slice(input_buf);
+    let mut hasher = ripemd160::Ripemd160::new();
+    hasher.input(&sha2_digest);
+    let ripemd_digest = hasher.result();
+
+    let mut hash_buf: [u8; 20] = [0; 20];
+    hash_buf.copy_from_slice(&ripemd_digest);
+    return hash_buf;
+}
+
+// Compressed point encoding and cofactors of a supported curve (see hash_to_curve)
+struct NumsCurve {
+    // bits of the first byte of a base field element that belong to the coordinate
+    coordinate_mask: u8,
+    // flags in the first byte of a compressed point: the compression flag, if the encoding has
+    // one, and the flag that selects the lexicographically largest y
+    compressed_flag: u8,
+    greatest_flag: u8,
+    // cofactors of G1 and G2 as little-endian u64 limbs
+    g1_cofactor: &'static [u64],
+    g2_cofactor: &'static [u64],
+}
+
+const NUMS_BLS12_381: NumsCurve = NumsCurve {
+    coordinate_mask: 0x1f,
+    compressed_flag: 0x80,
+    greatest_flag: 0x20,
+    g1_cofactor: &[0x8c00aaab0000aaab, 0x396c8c005555e156],
+    g2_cofactor: &[0xcf1c38e31c7238e5, 0x1616ec6e786f0c70, 0x21537e293a6691ae, 0xa628f1cb4d9e82ef,
+                   0xa68a205b2e5a7ddf, 0xcd91de4547085aba, 0x091d50792876a202, 0x05d543a95414e7f1],
+};
+
+// the BN256 encoding has no compression flag: bit 7 selects y and bit 6 is the infinity flag
+const NUMS_BN256: NumsCurve = NumsCurve {
+    coordinate_mask: 0x3f,
+    compressed_flag: 0x00,
+    greatest_flag: 0x80,
+    g1_cofactor: &[1],
+    g2_cofactor: &[0x345f2299c0f9fa8d, 0x06ceecda572a2489, 0xb85045b68181585e, 0x30644e72e131a029],
+};
+
+// the supported curves are told apart by the length of a base field element
+fn nums_curve<E: Engine>() -> (&'static NumsCurve, usize) {
+    use pairing::EncodedPoint;
+    match <E::G1Affine as CurveAffine>::Compressed::size() {
+        48 => (&NUMS_BLS12_381, 48),
+        32 => (&NUMS_BN256, 32),
+        len => panic!("no hash to the curve for a base field of {} bytes", len)
+    }
+}
+
+// multiplies p by a scalar given as little-endian u64 limbs, which may exceed the group order
+fn mul_by_limbs<G: CurveProjective>(p: &G, limbs: &[u64]) -> G {
+    let mut res = G::zero();
+    for limb in limbs.iter().rev() {
+        for i in (0..64).rev() {
+            res.double();
+            if (limb >> i) & 1 == 1 {
+                res.add_assign(p);
+            }
+        }
+    }
+    res
+}
+
+/// Try-and-increment hash of msg to the prime order subgroup, for public seeds only (it is not
+/// constant time). For ctr = 0, 1, ..., 255:
+/// 1. u = expand_message_xmd(msg || I2OSP(ctr, 1), dst, n) with SHA-256, where n is the length
+///    of a base field element (L bytes for G1, 2L bytes for G2 as c1 || c0);
+/// 2. the top bit of u[0] is the sign bit, and the bits above the bit length of the modulus are
+///    cleared in the first byte of each base field element, which is read as big-endian;
+/// 3. if an element is not below the modulus, or x^3 + b is not a square, try the next ctr;
+/// 4. y is the lexicographically largest square root of x^3 + b if the sign bit is set and the
+///    smallest otherwise (elements of Fq2 are compared on c1 first, then on c0);
+/// 5. the result is (x, y) times the cofactor of the group, unless that is the identity.
+fn hash_to_curve<G: CurveProjective>(curve: &NumsCurve, fq_len: usize, cofactor: &[u64], msg: &[u8], dst: &[u8]) -> G {
+    use pairing::EncodedPoint;
+    let mut encoded = <G::Affine as CurveAffine>::Compressed::empty();
+    let len = encoded.as_ref().len();
+    let mut msg_ctr = msg.to_vec();
+    msg_ctr.push(0);
+    for ctr in 0..256 {
+        msg_ctr[msg.len()] = ctr as u8;
+        let mut u = expand_message_xmd(&msg_ctr, dst, len);
+        let sign = u[0] & 0x80 != 0;
+        for i in (0..len).step_by(fq_len) {
+            u[i] &= curve.coordinate_mask;
+        }
+        u[0] |= curve.compressed_flag;
+        if sign {
+            u[0] |= curve.greatest_flag;
+        }
+        encoded.as_mut().copy_from_slice(&u);
+        // decoding fails if an element is not below the modulus or x is not on the curve
+        if let Ok(p) = encoded.into_affine_unchecked() {
+            let p = mul_by_limbs(&p.into_projective(), cofactor);
+            if !p.is_zero() {
+                return p;
+            }
+        }
+    }
+    panic!("no point found in 256 tries")
+}
+
+/// hashes msg to a point of G1 whose discrete log nobody knows (see hash_to_curve)
+pub fn hash_to_g1<E: Engine>(msg: &[u8], dst: &[u8]) -> E::G1 {
+    let (curve, fq_len) = nums_curve::<E>();
+    hash_to_curve(curve, fq_len, curve.g1_cofactor, msg, dst)
+}
+
+/// hashes msg to a point of G2 whose discrete log nobody knows (see hash_to_curve)
+pub fn hash_to_g2<E: Engine>(msg: &[u8], dst: &[u8]) -> E::G2 {
+    let (curve, fq_len) = nums_curve::<E>();
+    hash_to_curve(curve, fq_len, curve.g2_cofactor, msg, dst)
+}
+
+/// overwrites a scalar with zero using a volatile write that is not optimized away.
+/// Only use it on values about to be dropped.
+pub fn zeroize_scalar<F: PrimeField>(value: &mut F) {
+    unsafe { core::ptr::write_volatile(value, F::zero()) };
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Key types that can be held in a Secret. The trait is sealed: it is implemented for the
+/// secp256k1 secret keys, which are plain bytes and can be overwritten with zeros (scalars are
+/// held in a SecretScalar).
+pub trait SecretValue: Copy + sealed::Sealed {
+    /// overwrites the value with zeros, using volatile writes that are not optimized away
+    fn zeroize_value(&mut self);
+}
+
+impl sealed::Sealed for secp256k1::SecretKey {}
+
+impl SecretValue for secp256k1::SecretKey {
+    fn zeroize_value(&mut self) {
+        // SecretKey wraps a [u8; 32], for which any byte pattern is a valid value
+        let bytes = unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len()) };
+        bytes.zeroize();
+    }
+}
+
+/// Secret holds a secret key (see SecretValue) and zeroizes it when dropped.
+/// It derefs to the value and serializes as the value, but Display and Debug redact it.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T: SecretValue>(T);
+
+impl<T: SecretValue> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: SecretValue> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: SecretValue> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: SecretValue> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        Secret(self.0)
+    }
+}
+
+impl<T: SecretValue + PartialEq> PartialEq for Secret<T> {
+    fn eq(&self, other: &Secret<T>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: SecretValue> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize_value();
+    }
+}
+
+impl<T: SecretValue> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl<T: SecretValue> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret(<redacted>)")
+    }
+}
+
+/// SecretScalar holds a secret scalar (e.g. the randomness of a commitment) and sets it to zero
+/// when dropped. Like Secret, it derefs to the value and serializes as the value, but Display and
+/// Debug redact it.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretScalar<F: PrimeField>(F);
+
+impl<F: PrimeField> SecretScalar<F> {
+    pub fn new(value: F) -> Self {
+        SecretScalar(value)
+    }
+}
+
+impl<F: PrimeField> Deref for SecretScalar<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.0
+    }
+}
+
+impl<F: PrimeField> DerefMut for SecretScalar<F> {
+    fn deref_mut(&mut self) -> &mut F {
+        &mut self.0
+    }
+}
+
+impl<F: PrimeField> Clone for SecretScalar<F> {
+    fn clone(&self) -> Self {
+        SecretScalar(self.0)
+    }
+}
+
+impl<F: PrimeField> PartialEq for SecretScalar<F> {
+    fn eq(&self, other: &SecretScalar<F>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<F: PrimeField> Drop for SecretScalar<F> {
+    fn drop(&mut self) {
+        zeroize_scalar(&mut self.0);
+    }
+}
+
+impl<F: PrimeField> fmt::Display for SecretScalar<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl<F: PrimeField> fmt::Debug for SecretScalar<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretScalar(<redacted>)")
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RevokedMessage {
     pub msgtype: String,
@@ -163,8 +623,11 @@ mod tests {
     fn hash_g2_to_fr_works() {
         let mut two = G2::one();
         two.double();
-        assert_eq!(format!("{}", hash_g2_to_fr::<Bls12>(&two).into_repr()),
-                   "0x6550a1431236024424ac8e7f65781f244b70a38e5b3c275000a2b91089706868");
+        let expected = match HASH_TO_FIELD_VERSION {
+            HashToFieldVersion::XmdSha256 => "0x5dc094ae5664fdf76462944059faf7b40d39bf3aed4c2c1f99d6e4a5b374c31a",
+            HashToFieldVersion::Legacy => "0x6550a1431236024424ac8e7f65781f244b70a38e5b3c275000a2b91089706868",
+        };
+        assert_eq!(format!("{}", hash_g2_to_fr::<Bls12>(&two).into_repr()), expected);
     }
 
     #[test]
@@ -173,8 +636,100 @@ mod tests {
         two.double();
         let mut x_vec: Vec<u8> = Vec::new();
         x_vec.extend(format!("{}", two).bytes());
-        assert_eq!(format!("{}", hash_to_fr::<Bls12>(x_vec).into_repr()),
+        assert_eq!(format!("{}", hash_to_field::<Bls12>(HashToFieldVersion::Legacy, DST_HASH, &x_vec).into_repr()),
                    "0x6550a1431236024424ac8e7f65781f244b70a38e5b3c275000a2b91089706868");
+        assert_eq!(hash_to_fr::<Bls12>(x_vec.clone()), hash_to_field::<Bls12>(HASH_TO_FIELD_VERSION, DST_HASH, &x_vec));
+    }
+
+    #[test]
+    fn expand_message_xmd_works() {
+        // test vectors from RFC 9380, appendix K.1
+        let dst = b"QUUX-V01-CS02-with-expander-SHA256-128";
+        assert_eq!(hex::encode(expand_message_xmd(b"", dst, 0x20)),
+                   "68a985b87eb6b46952128911f2a4412bbc302a9d759667f87f7a21d803f07235");
+        assert_eq!(hex::encode(expand_message_xmd(b"abc", dst, 0x20)),
+                   "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615");
+        assert_eq!(hex::encode(expand_message_xmd(b"", dst, 0x80)),
+                   "af84c27ccfd45d41914fdff5df25293e221afc53d8ad2ac06d5e3e29485dadbee0d121587713a3e0dd4d5e69e93eb7cd\
+                    4f5df4cd103e188cf60cb02edc3edf18eda8576c412b18ffb658e3dd6ec849469b979d444cf7b26911a08e63cf31f9dcc\
+                    541708d3491184472c2c29bb749d4286b004ceb5ee6b9a7fa5b646c993f0ced");
+    }
+
+    #[test]
+    fn hash_to_field_xmd_works() {
+        assert_eq!(format!("{}", hash_to_field_xmd::<Bls12>(b"", DST_HASH).into_repr()),
+                   "0x3e677e22d9e05edd2d9e3c078bacd63a21d37ce7323be03bab7067b17993fda7");
+        assert_eq!(format!("{}", hash_to_field_xmd::<Bls12>(b"abc", DST_HASH).into_repr()),
+                   "0x193789fc148f1be4e6a8a4dbf6c0e9f22bbd55da7046425628bfb8e85905d1b1");
+        assert_eq!(format!("{}", hash_to_field_xmd::<pairing::bn256::Bn256>(b"abc", DST_HASH).into_repr()),
+                   "0x203d3c488d36c81347a9d03b56e8bf4762e0121ce9792b670ab64f8ae2a85e64");
+        assert_ne!(hash_to_field_xmd::<Bls12>(b"abc", DST_HASH), hash_to_field_xmd::<Bls12>(b"abc", DST_CHALLENGE));
+    }
+
+    #[test]
+    fn is_valid_point_works() {
+        use pairing::bls12_381::G1;
+        assert!(is_valid_point(&G1::one()));
+        assert!(is_valid_point(&G2::one()));
+        assert!(!is_valid_point(&G1::zero()));
+        assert!(!is_valid_point(&G2::zero()));
+        assert!(!is_valid_point(&non_subgroup_g1()));
+        assert_eq!(validate_point(&non_subgroup_g1(), "p").err().unwrap(), "p is the identity or not in the prime order subgroup");
+    }
+
+    #[test]
+    fn is_valid_gt_works() {
+        use pairing::bls12_381::{G1, Fq12};
+        assert!(is_valid_gt::<Bls12>(&Bls12::pairing(G1::one(), G2::one())));
+        assert!(is_valid_gt::<Bls12>(&Fq12::one()));
+        assert!(!is_valid_gt::<Bls12>(&Fq12::zero()));
+        let mut two = Fq12::one();
+        two.double();
+        assert!(!is_valid_gt::<Bls12>(&two));
+    }
+
+    #[test]
+    fn hash_to_curve_works() {
+        use pairing::bls12_381::{Bls12, G1, G2};
+        use pairing::bn256::Bn256;
+        let g1 = hash_to_g1::<Bls12>(b"seed", b"DST");
+        let g2 = hash_to_g2::<Bls12>(b"seed", b"DST");
+        assert!(is_valid_point(&g1) && is_valid_point(&g2));
+        assert!(g1 == hash_to_g1::<Bls12>(b"seed", b"DST"));
+        assert!(g1 != hash_to_g1::<Bls12>(b"seed2", b"DST"));
+        assert!(g1 != hash_to_g1::<Bls12>(b"seed", b"DST2"));
+        assert!(is_valid_point(&hash_to_g1::<Bn256>(b"seed", b"DST")));
+        assert!(is_valid_point(&hash_to_g2::<Bn256>(b"seed", b"DST")));
+
+        let mut five = G1::one();
+        five.mul_assign(5u64);
+        assert!(mul_by_limbs(&G1::one(), &[5]) == five);
+        // the G2 cofactor does not fit in a scalar, and clears the cofactor of a point on the curve
+        assert!(mul_by_limbs(&G2::one(), NUMS_BLS12_381.g2_cofactor) != G2::zero());
+        assert!(is_valid_point(&mul_by_limbs(&non_subgroup_g1(), NUMS_BLS12_381.g1_cofactor)));
+    }
+
+    #[test]
+    fn secret_works() {
+        use pairing::bls12_381::Fr;
+        use ff::Field;
+        let mut x = Fr::one();
+        x.double();
+        let secret = SecretScalar::new(x);
+        assert_eq!(*secret, x);
+        assert_eq!(format!("{} {:?}", secret, secret), "<redacted> SecretScalar(<redacted>)");
+        assert_eq!(serde_json::to_string(&secret).unwrap(), serde_json::to_string(&x).unwrap());
+        let secret2: SecretScalar<Fr> = serde_json::from_str(&serde_json::to_string(&x).unwrap()).unwrap();
+        assert!(secret == secret2);
+        zeroize_scalar(&mut x);
+        assert!(x.is_zero());
+
+        let key = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let mut secret = Secret::new(key);
+        assert_eq!(*secret, key);
+        assert_eq!(format!("{} {:?}", secret, secret), "<redacted> Secret(<redacted>)");
+        secret.zeroize_value();
+        assert_eq!(&secret[..], &[0u8; 32][..]);
     }
 
     #[test]
@@ -192,4 +747,10 @@ mod tests {
         assert_eq!(format!("{}", convert_int_to_fr::<Bls12>(365).into_repr()),
                    "0x000000000000000000000000000000000000000000000000000000000000016d");
     }
+
+    #[test]
+    fn hash160_works() {
+        assert_eq!(hex::encode(hash160(&Vec::new())), "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb");
+        assert_eq!(hex::encode(hash160(&b"bolt".to_vec())), "decadbedb465fc2b6376888ae1c9d0b634e2aea2");
+    }
 }
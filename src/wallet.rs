@@ -2,7 +2,7 @@ use super::*;
 use pairing::Engine;
 use ff::PrimeField;
 use util::hash_to_fr;
-use std::fmt;
+use core::fmt;
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(bound(serialize = "<E as ff::ScalarEngine>::Fr: serde::Serialize"))]
@@ -0,0 +1,170 @@
+//! WebAssembly bindings for the customer side of the bidirectional payment channel API on
+//! BLS12-381 (enabled with the `wasm` feature).
+//!
+//! States and protocol messages are passed in as JSON strings and returned as a JSON object
+//! string. The customer state is exchanged in its exported form (see `CustomerState::export`),
+//! which holds the customer's secret keys and must not leave the customer's device.
+//! `thread_rng` has no entropy source on `wasm32-unknown-unknown`, so every call that needs
+//! randomness takes a fresh 32-byte `seed` (e.g. from `crypto.getRandomValues`) that seeds a
+//! ChaCha20 generator for that call only. Errors are thrown as JS `Error`s.
+
+use super::*;
+use bidirectional;
+use pairing::bls12_381::Bls12;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use wasm_bindgen::prelude::*;
+
+type CURVE = Bls12;
+
+fn seeded_rng(seed: &[u8]) -> Result<ChaCha20Rng, JsError> {
+    if seed.len() != 32 {
+        return Err(JsError::new("seed must be 32 bytes"));
+    }
+    let mut s = [0u8; 32];
+    s.copy_from_slice(seed);
+    Ok(ChaCha20Rng::from_seed(s))
+}
+
+fn from_json<T: DeserializeOwned>(ser: &str) -> Result<T, JsError> {
+    serde_json::from_str(ser).map_err(|err| JsError::new(&err.to_string()))
+}
+
+fn cust_state_from_json(ser: &str) -> Result<bidirectional::CustomerState<CURVE>, JsError> {
+    Ok(bidirectional::CustomerState::import(from_json(ser)?))
+}
+
+fn json_response(response: Value) -> Result<String, JsError> {
+    Ok(response.to_string())
+}
+
+/// Returns `{"channel_token", "cust_state"}`: the channel token with the customer public key set
+/// and the initial customer state. Fails if the range proof parameters in the channel state do
+/// not verify.
+#[wasm_bindgen]
+pub fn init_customer(seed: &[u8], ser_channel_state: &str, ser_channel_token: &str, balance_customer: i64, balance_merchant: i64, name: &str) -> Result<String, JsError> {
+    let rng = &mut seeded_rng(seed)?;
+    let channel_state: bidirectional::ChannelState<CURVE> = from_json(ser_channel_state)?;
+    let mut channel_token: bidirectional::ChannelToken<CURVE> = from_json(ser_channel_token)?;
+    if balance_customer < 0 || balance_merchant < 0 {
+        return Err(JsError::new("initial balances must not be negative"));
+    }
+    let cust_state = match bidirectional::init_customer(rng, &channel_state, &mut channel_token, balance_customer, balance_merchant, name) {
+        Ok(Some(cust_state)) => cust_state,
+        Ok(None) => return Err(JsError::new("init_customer - no customer state")),
+        Err(err) => return Err(JsError::new(&err)),
+    };
+    json_response(json!({"channel_token": channel_token, "cust_state": cust_state.export()}))
+}
+
+/// Returns `{"channel_id", "com", "com_proof"}` to send to the merchant.
+#[wasm_bindgen]
+pub fn establish_customer_generate_proof(seed: &[u8], ser_channel_token: &str, ser_cust_state: &str) -> Result<String, JsError> {
+    let rng = &mut seeded_rng(seed)?;
+    let channel_token: bidirectional::ChannelToken<CURVE> = from_json(ser_channel_token)?;
+    let cust_state = cust_state_from_json(ser_cust_state)?;
+    let (com, com_proof) = bidirectional::establish_customer_generate_proof(rng, &channel_token, &cust_state);
+    json_response(json!({"channel_id": cust_state.get_wallet().channelId, "com": com, "com_proof": com_proof}))
+}
+
+/// Returns `{"payment", "cust_state"}`: the payment for the merchant and the new customer state,
+/// which only replaces the current one in `generate_revoke_token`.
+#[wasm_bindgen]
+pub fn generate_payment_proof(seed: &[u8], ser_channel_state: &str, ser_cust_state: &str, amount: i64) -> Result<String, JsError> {
+    let rng = &mut seeded_rng(seed)?;
+    let channel_state: bidirectional::ChannelState<CURVE> = from_json(ser_channel_state)?;
+    let cust_state = cust_state_from_json(ser_cust_state)?;
+    let (payment, new_cust_state) = bidirectional::generate_payment_proof(rng, &channel_state, &cust_state, amount);
+    json_response(json!({"payment": payment, "cust_state": new_cust_state.export()}))
+}
+
+/// Returns `{"revoke_token", "cust_state"}`: the revoke token for the merchant and the updated
+/// customer state. Fails without revoking anything if the close token does not verify.
+#[wasm_bindgen]
+pub fn generate_revoke_token(ser_channel_state: &str, ser_cust_state: &str, ser_new_cust_state: &str, ser_close_token: &str) -> Result<String, JsError> {
+    let channel_state: bidirectional::ChannelState<CURVE> = from_json(ser_channel_state)?;
+    let mut cust_state = cust_state_from_json(ser_cust_state)?;
+    let new_cust_state = cust_state_from_json(ser_new_cust_state)?;
+    let close_token: bidirectional::Signature<CURVE> = from_json(ser_close_token)?;
+
+    // a panic aborts the wasm instance, so check what generate_revoke_token asserts first
+    let mut updated_state = cust_state.clone();
+    if !updated_state.update(new_cust_state.clone()) || !updated_state.verify_close_token(&channel_state, &close_token) {
+        return Err(JsError::new("generate_revoke_token - could not verify the close token"));
+    }
+
+    let revoke_token = bidirectional::generate_revoke_token(&channel_state, &mut cust_state, new_cust_state, &close_token);
+    json_response(json!({"revoke_token": revoke_token, "cust_state": cust_state.export()}))
+}
+
+/// Returns `{"cust_close"}`, the customer close message for the current state.
+#[wasm_bindgen]
+pub fn customer_close(ser_channel_state: &str, ser_cust_state: &str) -> Result<String, JsError> {
+    let channel_state: bidirectional::ChannelState<CURVE> = from_json(ser_channel_state)?;
+    let cust_state = cust_state_from_json(ser_cust_state)?;
+    if !channel_state.channel_established {
+        return Err(JsError::new("Cannot close a channel that has not been established!"));
+    }
+    let cust_close = bidirectional::customer_close(&channel_state, &cust_state);
+    json_response(json!({"cust_close": cust_close}))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    fn field(response: &str, key: &str) -> String {
+        let v: Value = serde_json::from_str(response).unwrap();
+        v[key].to_string()
+    }
+
+    #[wasm_bindgen_test]
+    fn customer_api_works() {
+        let rng = &mut ChaCha20Rng::from_seed([7u8; 32]);
+        let mut channel_state = bidirectional::ChannelState::<CURVE>::new(String::from("Channel A -> B"), false);
+        let (channel_token, merch_state, mut merch_ledger, mut channel_state) = bidirectional::init_merchant(rng, &mut channel_state, "Bob");
+
+        let ser_channel_state = serde_json::to_string(&channel_state).unwrap();
+        let r = init_customer(&[1u8; 32], &ser_channel_state, &serde_json::to_string(&channel_token).unwrap(), 100, 10, "Alice").unwrap();
+        let (channel_token, cust_state) = (field(&r, "channel_token"), field(&r, "cust_state"));
+
+        let r = establish_customer_generate_proof(&[2u8; 32], &channel_token, &cust_state).unwrap();
+        let com: bidirectional::Commitment<CURVE> = from_json(&field(&r, "com")).unwrap();
+        let com_proof: bidirectional::CommitmentProof<CURVE> = from_json(&field(&r, "com_proof")).unwrap();
+        let channel_id: <CURVE as ff::ScalarEngine>::Fr = from_json(&field(&r, "channel_id")).unwrap();
+
+        let close_token = bidirectional::establish_merchant_issue_close_token(rng, &channel_state, &com, &com_proof, &channel_id, 100, 10, &merch_state, &mut merch_ledger).unwrap().unwrap();
+        let mut cust = cust_state_from_json(&cust_state).unwrap();
+        assert!(cust.verify_close_token(&channel_state, &close_token));
+        let pay_token = bidirectional::establish_merchant_issue_pay_token(rng, &channel_state, &com, &merch_state);
+        assert!(bidirectional::establish_customer_final(&mut channel_state, &mut cust, &pay_token));
+
+        let ser_channel_state = serde_json::to_string(&channel_state).unwrap();
+        let cust_state = serde_json::to_string(&cust.export()).unwrap();
+
+        let r = generate_payment_proof(&[3u8; 32], &ser_channel_state, &cust_state, 10).unwrap();
+        let payment: bidirectional::Payment<CURVE> = from_json(&field(&r, "payment")).unwrap();
+        let new_cust_state = field(&r, "cust_state");
+        let close_token = bidirectional::verify_payment_proof(rng, &channel_state, &payment, &merch_state, &mut merch_ledger);
+
+        assert!(generate_revoke_token(&ser_channel_state, &cust_state, &new_cust_state, &serde_json::to_string(&pay_token).unwrap()).is_err());
+        let r = generate_revoke_token(&ser_channel_state, &cust_state, &new_cust_state, &serde_json::to_string(&close_token).unwrap()).unwrap();
+        let revoke_token: bidirectional::RevokeToken = from_json(&field(&r, "revoke_token")).unwrap();
+        let cust_state = field(&r, "cust_state");
+        assert!(bidirectional::verify_revoke_token(&revoke_token, &mut merch_ledger).unwrap().is_some());
+
+        let r = customer_close(&ser_channel_state, &cust_state).unwrap();
+        let cust_close: bidirectional::ChannelcloseC<CURVE> = from_json(&field(&r, "cust_close")).unwrap();
+        assert_eq!(cust_close.message.bc, 90);
+    }
+
+    #[wasm_bindgen_test]
+    fn seed_must_be_32_bytes() {
+        assert!(seeded_rng(&[0u8; 16]).is_err());
+        assert!(seeded_rng(&[0u8; 32]).is_ok());
+    }
+}